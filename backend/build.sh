@@ -10,3 +10,4 @@ mkdir -p ../bin
 #cp ./target/x86_64-unknown-linux-musl/debug/powertools ../bin/backend
 #cp ./target/debug/powertools ../bin/backend
 cp ./target/debug/powertools ../bin/backend
+cp ./target/debug/powertools-ctl ../bin/powertools-ctl
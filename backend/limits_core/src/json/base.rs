@@ -29,6 +29,7 @@ impl Default for Base {
                         super::Limits::Cpu(super::CpuLimit::SteamDeckAdvance),
                         super::Limits::Gpu(super::GpuLimit::SteamDeckAdvance),
                         super::Limits::Battery(super::BatteryLimit::SteamDeckAdvance),
+                        super::Limits::Fan(super::FanLimit::SteamDeckAdvance),
                     ]
                 },
                 super::Config {
@@ -44,6 +45,7 @@ impl Default for Base {
                         super::Limits::Cpu(super::CpuLimit::SteamDeck),
                         super::Limits::Gpu(super::GpuLimit::SteamDeck),
                         super::Limits::Battery(super::BatteryLimit::SteamDeck),
+                        super::Limits::Fan(super::FanLimit::SteamDeck),
                     ]
                 },
                 super::Config {
@@ -71,6 +73,7 @@ impl Default for Base {
                             ..Default::default()
                         })),
                         super::Limits::Battery(super::BatteryLimit::Generic(super::GenericBatteryLimit{})),
+                        super::Limits::Fan(super::FanLimit::Generic(super::GenericFanLimit::default())),
                     ]
                 },
                 super::Config {
@@ -98,6 +101,7 @@ impl Default for Base {
                             ..Default::default()
                         })),
                         super::Limits::Battery(super::BatteryLimit::Generic(super::GenericBatteryLimit{})),
+                        super::Limits::Fan(super::FanLimit::Generic(super::GenericFanLimit::default())),
                     ]
                 },
                 super::Config {
@@ -125,6 +129,7 @@ impl Default for Base {
                             ..Default::default()
                         })),
                         super::Limits::Battery(super::BatteryLimit::Generic(super::GenericBatteryLimit{})),
+                        super::Limits::Fan(super::FanLimit::Generic(super::GenericFanLimit::default())),
                     ]
                 },
                 super::Config {
@@ -152,6 +157,7 @@ impl Default for Base {
                             ..Default::default()
                         })),
                         super::Limits::Battery(super::BatteryLimit::Generic(super::GenericBatteryLimit{})),
+                        super::Limits::Fan(super::FanLimit::Generic(super::GenericFanLimit::default())),
                     ]
                 },
                 super::Config {
@@ -167,6 +173,7 @@ impl Default for Base {
                         super::Limits::Cpu(super::CpuLimit::Unknown),
                         super::Limits::Gpu(super::GpuLimit::Unknown),
                         super::Limits::Battery(super::BatteryLimit::Unknown),
+                        super::Limits::Fan(super::FanLimit::Unknown),
                     ]
                 }
             ],
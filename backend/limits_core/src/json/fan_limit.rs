@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use super::RangeLimit;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "target")]
+pub enum FanLimit {
+    SteamDeck,
+    SteamDeckAdvance,
+    Generic(GenericFanLimit),
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GenericFanLimit {
+    /// hwmon device name to control (first device with a pwm1 output when not set)
+    pub hwmon: Option<String>,
+    pub pwm: Option<RangeLimit<u64>>,
+}
@@ -6,4 +6,5 @@ pub enum Limits {
     Cpu(super::CpuLimit),
     Gpu(super::GpuLimit),
     Battery(super::BatteryLimit),
+    Fan(super::FanLimit),
 }
@@ -4,6 +4,7 @@ mod conditions;
 mod config;
 mod cpu_limit;
 mod devel_message;
+mod fan_limit;
 mod gpu_limit;
 mod limits;
 mod range;
@@ -14,6 +15,7 @@ pub use battery_limit::{BatteryLimit, GenericBatteryLimit};
 pub use conditions::Conditions;
 pub use cpu_limit::{CpuLimit, GenericCpuLimit};
 pub use devel_message::DeveloperMessage;
+pub use fan_limit::{FanLimit, GenericFanLimit};
 pub use gpu_limit::{GpuLimit, GenericGpuLimit};
 pub use config::Config;
 pub use limits::Limits;
@@ -21,6 +21,7 @@ pub struct SettingsLimits {
     pub battery: BatteryLimits,
     pub cpu: CpusLimits,
     pub gpu: GpuLimits,
+    pub fan: FanLimits,
     pub general: GeneralLimits,
 }
 
@@ -65,3 +66,109 @@ pub struct GpuLimits {
     pub clock_step: u64,
     pub memory_control_capable: bool,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct FanLimits {
+    pub speed_limits: Option<RangeLimit<u64>>,
+    pub speed_step: u64,
+    /// "rpm" or "pwm"
+    pub speed_unit: String,
+    pub sensors: Vec<crate::persist::ThermalSensorJson>,
+}
+
+/// A value written by on_set(), next to what the hardware reports afterwards
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AppliedValue {
+    /// Battery, CPU, GPU or Fan
+    pub setting: String,
+    /// sysfs path (or a driver-specific name) of the value
+    pub name: String,
+    pub requested: String,
+    /// None when the value can't be read back
+    pub actual: Option<String>,
+}
+
+impl AppliedValue {
+    /// Whether the value was read back, but doesn't match what was written
+    pub fn is_drifted(&self) -> bool {
+        self.actual
+            .as_ref()
+            .map(|actual| actual != &self.requested)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ApplyReport {
+    /// Seconds since the UNIX epoch
+    pub time: u64,
+    pub values: Vec<AppliedValue>,
+    /// Number of values which are drifted
+    pub drifted: usize,
+    /// Whether settings were applied again because of drift
+    pub reapplied: bool,
+    /// committed, rolled-back or rollback-failed
+    pub outcome: String,
+}
+
+impl ApplyReport {
+    pub fn new(values: Vec<AppliedValue>) -> Self {
+        Self {
+            time: unix_time(),
+            drifted: values.iter().filter(|v| v.is_drifted()).count(),
+            values,
+            reapplied: false,
+            outcome: crate::settings::transaction::Outcome::Committed.to_string(),
+        }
+    }
+
+    pub fn has_drift(&self) -> bool {
+        self.drifted != 0
+    }
+}
+
+/// What importing a shared profile changed to make it fit this device
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ImportReport {
+    /// Profile was made on a device which matched the same limits config
+    pub same_device: bool,
+    /// Limits config of the device the profile was made on
+    pub device: Option<String>,
+    /// Values which were changed to fit this device
+    pub adjusted: Vec<String>,
+    /// Settings file the profile was saved to
+    pub path: String,
+}
+
+/// A SettingError, as the front-end sees it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SettingErrorInfo {
+    /// Battery, CPU, GPU, Fan or General
+    pub setting: String,
+    /// permission, unsupported, io or out-of-range
+    pub kind: String,
+    pub msg: String,
+    /// What was happening when the error occurred (e.g. on_set)
+    pub source: String,
+    /// Seconds since the UNIX epoch
+    pub time: u64,
+}
+
+impl SettingErrorInfo {
+    pub fn new(source: &str, error: &crate::settings::SettingError) -> Self {
+        Self {
+            setting: error.setting.to_string(),
+            kind: error.kind.to_string(),
+            msg: error.msg.clone(),
+            source: source.to_owned(),
+            time: unix_time(),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -58,23 +58,10 @@ impl<
 {
     async fn call(&self, _params: super::ApiParameterType) -> super::ApiParameterType {
         let getter = (self.set_get)();
-        let t_got = match tokio::task::spawn_blocking(move || getter()).await {
+        let t_got = match tokio::task::spawn_blocking(getter).await {
             Ok(t) => t,
             Err(e) => return vec![e.to_string().into()],
         };
         (self.trans_getter)(t_got)
     }
 }
-
-pub struct Blocking<F: (Fn(super::ApiParameterType) -> super::ApiParameterType) + Send + Sync> {
-    pub func: F,
-}
-
-#[async_trait::async_trait]
-impl<F: (Fn(super::ApiParameterType) -> super::ApiParameterType) + Send + Sync> AsyncCallable
-    for Blocking<F>
-{
-    async fn call(&self, params: super::ApiParameterType) -> super::ApiParameterType {
-        (self.func)(params)
-    }
-}
@@ -30,7 +30,7 @@ pub fn current_now(sender: Sender<ApiMessage>) -> impl AsyncCallable {
     }
 }
 
-/// Current current (ha!) web method
+// Current current (ha!) web method
 /*pub fn current_now(_: super::ApiParameterType) -> super::ApiParameterType {
     super::utility::map_optional_result(crate::settings::driver::read_current_now())
 }*/
@@ -116,18 +116,15 @@ pub fn set_charge_rate(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |rate: f64| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Battery(BatteryMessage::SetChargeRate(Some(
-                rate as u64,
-            ))))
-            .expect("set_charge_rate send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Battery(BatteryMessage::SetChargeRate(Some(rate as u64))),
+            "set_charge_rate",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(new_val)) = params_in.get(0) {
-            setter(new_val);
-            vec![(new_val).into()]
+        if let Some(&Primitive::F64(new_val)) = params_in.first() {
+            super::utility::map_checked(setter(new_val))
         } else {
             vec!["set_charge_rate missing parameter".into()]
         }
@@ -179,18 +176,15 @@ pub fn set_charge_mode(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |mode: String| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Battery(BatteryMessage::SetChargeMode(Some(
-                mode,
-            ))))
-            .expect("set_charge_mode send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Battery(BatteryMessage::SetChargeMode(Some(mode))),
+            "set_charge_mode",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(Primitive::String(new_val)) = params_in.get(0) {
-            setter(new_val.to_owned());
-            vec![new_val.to_owned().into()]
+        if let Some(Primitive::String(new_val)) = params_in.first() {
+            super::utility::map_checked(setter(new_val.to_owned()))
         } else {
             vec!["set_charge_rate missing parameter".into()]
         }
@@ -282,7 +276,7 @@ pub fn set_charge_limit(
             .expect("set_charge_limit send failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(new_val)) = params_in.get(0) {
+        if let Some(&Primitive::F64(new_val)) = params_in.first() {
             setter(new_val);
             vec![new_val.into()]
         } else {
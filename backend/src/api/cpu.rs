@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use usdpl_back::core::serdes::Primitive;
 use usdpl_back::AsyncCallable;
 
-use crate::settings::{MinMax, SettingError, SettingVariant};
+use crate::settings::{ErrorKind, MinMax, SettingError, SettingVariant};
 //use crate::utility::{unwrap_lock, unwrap_maybe_fatal};
 use super::handler::{ApiMessage, CpuMessage};
 
@@ -15,6 +15,7 @@ pub fn max_cpus(_: super::ApiParameterType) -> super::ApiParameterType {
             .ok_or_else(|| SettingError {
                 msg: "Failed to parse CPU count".to_owned(),
                 setting: SettingVariant::Cpu,
+                kind: ErrorKind::Unsupported,
             }),
     )
 }
@@ -25,18 +26,17 @@ pub fn set_cpu_online(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |index: usize, value: bool| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Cpu(CpuMessage::SetCpuOnline(index, value)))
-            .expect("set_cpu_online send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Cpu(CpuMessage::SetCpuOnline(index, value)),
+            "set_cpu_online",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(index)) = params_in.get(0) {
+        if let Some(&Primitive::F64(index)) = params_in.first() {
             //let mut settings_lock = unwrap_lock(settings.lock(), "cpu");
             if let Some(&Primitive::Bool(online)) = params_in.get(1) {
-                setter(index as usize, online);
-                vec![online.into()]
+                super::utility::map_checked(setter(index as usize, online))
             } else {
                 vec!["set_cpu_online missing parameter 1".into()]
             }
@@ -51,17 +51,17 @@ pub fn set_cpus_online(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |values: Vec<bool>| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Cpu(CpuMessage::SetCpusOnline(values)))
-            .expect("set_cpus_online send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Cpu(CpuMessage::SetCpusOnline(values)),
+            "set_cpus_online",
+        )
     };
     move |params_in: super::ApiParameterType| {
         let mut result = Vec::with_capacity(params_in.len());
         let mut values = Vec::with_capacity(params_in.len());
-        for i in 0..params_in.len() {
-            if let Primitive::Bool(online) = params_in[i] {
+        for (i, param) in params_in.iter().enumerate() {
+            if let Primitive::Bool(online) = *param {
                 values.push(online);
                 result.push(online.into());
             } else {
@@ -69,8 +69,7 @@ pub fn set_cpus_online(
                 result.push(format!("Invalid parameter {}", i).into())
             }
         }
-        setter(values);
-        result
+        super::utility::map_checked(setter(values).map(|_| result))
     }
 }
 
@@ -112,7 +111,7 @@ pub fn set_smt(sender: Sender<ApiMessage>) -> impl AsyncCallable {
     };
     super::async_utils::AsyncIsh {
         trans_setter: |params| {
-            if let Some(&Primitive::Bool(smt_value)) = params.get(0) {
+            if let Some(&Primitive::Bool(smt_value)) = params.first() {
                 Ok(smt_value)
             } else {
                 Err("set_smt missing/invalid parameter 0".to_owned())
@@ -187,29 +186,24 @@ pub fn set_clock_limits(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |index: usize, value: MinMax<u64>| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Cpu(CpuMessage::SetClockLimits(
-                index,
-                Some(value),
-            )))
-            .expect("set_clock_limits send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Cpu(CpuMessage::SetClockLimits(index, Some(value))),
+            "set_clock_limits",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(index)) = params_in.get(0) {
+        if let Some(&Primitive::F64(index)) = params_in.first() {
             if let Some(&Primitive::F64(min)) = params_in.get(1) {
                 if let Some(&Primitive::F64(max)) = params_in.get(2) {
-                    let safe_max = if max < min { min } else { max };
-                    let safe_min = if min > max { max } else { min };
-                    setter(
+                    let result = setter(
                         index as usize,
                         MinMax {
-                            min: safe_min as u64,
-                            max: safe_max as u64,
+                            min: min as u64,
+                            max: max as u64,
                         },
                     );
-                    vec![safe_min.into(), safe_max.into()]
+                    super::utility::map_checked(result)
                 } else {
                     vec!["set_clock_limits missing parameter 2".into()]
                 }
@@ -243,7 +237,7 @@ pub fn get_clock_limits(
         rx.recv().expect("get_clock_limits callback recv failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(index)) = params_in.get(0) {
+        if let Some(&Primitive::F64(index)) = params_in.first() {
             if let Some(min_max) = getter(index as usize) {
                 vec![min_max.min.into(), min_max.max.into()]
             } else {
@@ -267,7 +261,7 @@ pub fn unset_clock_limits(
             .expect("unset_clock_limits send failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(index)) = params_in.get(0) {
+        if let Some(&Primitive::F64(index)) = params_in.first() {
             setter(index as usize);
             vec![true.into()]
         } else {
@@ -281,17 +275,16 @@ pub fn set_cpu_governor(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |index: usize, governor: String| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Cpu(CpuMessage::SetCpuGovernor(index, governor)))
-            .expect("set_cpu_governor send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Cpu(CpuMessage::SetCpuGovernor(index, governor)),
+            "set_cpu_governor",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(index)) = params_in.get(0) {
+        if let Some(&Primitive::F64(index)) = params_in.first() {
             if let Some(Primitive::String(governor)) = params_in.get(1) {
-                setter(index as usize, governor.to_owned());
-                vec![(governor as &str).into()]
+                super::utility::map_checked(setter(index as usize, governor.to_owned()))
             } else {
                 vec!["set_cpu_governor missing parameter 1".into()]
             }
@@ -306,17 +299,17 @@ pub fn set_cpus_governors(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |governors: Vec<String>| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Cpu(CpuMessage::SetCpusGovernor(governors)))
-            .expect("set_cpus_governor send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Cpu(CpuMessage::SetCpusGovernor(governors)),
+            "set_cpus_governor",
+        )
     };
     move |params_in: super::ApiParameterType| {
         let mut result = Vec::with_capacity(params_in.len());
         let mut values = Vec::with_capacity(params_in.len());
-        for i in 0..params_in.len() {
-            if let Primitive::String(gov) = &params_in[i] {
+        for (i, param) in params_in.iter().enumerate() {
+            if let Primitive::String(gov) = param {
                 values.push(gov.to_owned());
                 result.push((gov as &str).into());
             } else {
@@ -324,8 +317,7 @@ pub fn set_cpus_governors(
                 result.push(format!("Invalid parameter {}", i).into())
             }
         }
-        setter(values);
-        result
+        super::utility::map_checked(setter(values).map(|_| result))
     }
 }
 
@@ -0,0 +1,534 @@
+//! D-Bus service with the same settings and readings as the USDPL API, for other programs on the system
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use zbus::blocking::connection::Builder;
+use zbus::blocking::object_server::InterfaceRef;
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::object_server::SignalContext;
+
+use super::handler::{
+    ApiMessage, BatteryMessage, CpuMessage, GeneralMessage, GpuMessage, PresetMessage,
+};
+use super::power_profiles::{PowerProfiles, UPowerPowerProfiles};
+use crate::events::{Event, EventBus};
+use crate::settings::MinMax;
+
+pub const BUS_NAME: &str = "org.powertools.Manager";
+pub const OBJECT_PATH: &str = "/org/powertools/Manager";
+
+/// Longest the signal thread waits on the event bus at once
+const EVENT_WAIT: Duration = Duration::from_secs(60);
+
+/// D-Bus has no optional values, so unknown readings are -1
+fn reading(value: Option<f64>) -> f64 {
+    value.unwrap_or(-1.0)
+}
+
+/// D-Bus has no optional values, so unset limits are 0
+fn limit(value: Option<u64>) -> u64 {
+    value.unwrap_or(0)
+}
+
+fn limit_pair(value: Option<MinMax<u64>>) -> (u64, u64) {
+    value.map(|x| (x.min, x.max)).unwrap_or((0, 0))
+}
+
+fn non_zero(value: u64) -> Option<u64> {
+    Some(value).filter(|x| *x != 0)
+}
+
+pub struct Manager {
+    sender: Mutex<Sender<ApiMessage>>, // Sender is not Sync; this is required for safety
+}
+
+impl Manager {
+    pub fn new(sender: Sender<ApiMessage>) -> Self {
+        Self {
+            sender: Mutex::new(sender),
+        }
+    }
+
+    /// Send a message to the handler, which fails once the handler has stopped
+    fn send(&self, message: ApiMessage) -> fdo::Result<()> {
+        self.sender
+            .lock()
+            .unwrap()
+            .send(message)
+            .map_err(|_| fdo::Error::Failed("PowerTools is shutting down".to_owned()))
+    }
+
+    /// Send a getter message and wait for the value its callback gets
+    fn request<T: Send + 'static>(
+        &self,
+        message: impl FnOnce(Box<dyn FnOnce(T) + Send>) -> ApiMessage,
+    ) -> fdo::Result<T> {
+        let (tx, rx) = mpsc::channel();
+        self.send(message(Box::new(move |value: T| {
+            tx.send(value).unwrap_or(())
+        })))?;
+        rx.recv()
+            .map_err(|_| fdo::Error::Failed("PowerTools didn't respond".to_owned()))
+    }
+
+    /// Send a setter message, turning rejected values into an InvalidArgs error
+    fn checked(&self, message: ApiMessage) -> fdo::Result<()> {
+        self.request(|cb| ApiMessage::Checked(Box::new(message), cb))?
+            .map(|_| ())
+            .map_err(|errors| fdo::Error::InvalidArgs(errors.join("\n")))
+    }
+}
+
+#[zbus::interface(name = "org.powertools.Manager")]
+impl Manager {
+    /// Battery charge (Wh), or -1 if unknown
+    #[zbus(property)]
+    fn charge_now(&self) -> fdo::Result<f64> {
+        self.request(|cb| ApiMessage::Battery(BatteryMessage::ReadChargeNow(cb)))
+            .map(reading)
+    }
+
+    /// Battery capacity (Wh), or -1 if unknown
+    #[zbus(property)]
+    fn charge_full(&self) -> fdo::Result<f64> {
+        self.request(|cb| ApiMessage::Battery(BatteryMessage::ReadChargeFull(cb)))
+            .map(reading)
+    }
+
+    /// Battery current (mA), or -1 if unknown
+    #[zbus(property)]
+    fn current_now(&self) -> fdo::Result<f64> {
+        self.request(|cb| ApiMessage::Battery(BatteryMessage::ReadCurrentNow(cb)))
+            .map(reading)
+    }
+
+    /// Charge rate limit (mA), or 0 if not set
+    #[zbus(property)]
+    fn charge_rate(&self) -> fdo::Result<u64> {
+        self.request(|cb| ApiMessage::Battery(BatteryMessage::GetChargeRate(cb)))
+            .map(limit)
+    }
+
+    /// Charge mode, or an empty string if not set
+    #[zbus(property)]
+    fn charge_mode(&self) -> fdo::Result<String> {
+        self.request(|cb| ApiMessage::Battery(BatteryMessage::GetChargeMode(cb)))
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Set the charge rate limit (mA); 0 stops setting it
+    fn set_charge_rate(&self, rate: u64) -> fdo::Result<()> {
+        self.checked(ApiMessage::Battery(BatteryMessage::SetChargeRate(
+            non_zero(rate),
+        )))
+    }
+
+    /// Set the charge mode; an empty string stops setting it
+    fn set_charge_mode(&self, mode: String) -> fdo::Result<()> {
+        let mode = Some(mode).filter(|m| !m.is_empty());
+        self.checked(ApiMessage::Battery(BatteryMessage::SetChargeMode(mode)))
+    }
+
+    #[zbus(property)]
+    fn cpus_online(&self) -> fdo::Result<Vec<bool>> {
+        self.request(|cb| ApiMessage::Cpu(CpuMessage::GetCpusOnline(cb)))
+    }
+
+    #[zbus(property)]
+    fn cpu_governors(&self) -> fdo::Result<Vec<String>> {
+        self.request(|cb| ApiMessage::Cpu(CpuMessage::GetCpusGovernor(cb)))
+    }
+
+    #[zbus(property)]
+    fn smt(&self) -> fdo::Result<bool> {
+        self.request(|cb| ApiMessage::Cpu(CpuMessage::GetSmt(cb)))
+    }
+
+    #[zbus(property)]
+    fn set_smt(&self, smt: bool) -> zbus::Result<()> {
+        self.request(|cb| ApiMessage::Cpu(CpuMessage::SetSmt(smt, cb)))?;
+        Ok(())
+    }
+
+    fn set_cpu_online(&self, index: u32, online: bool) -> fdo::Result<()> {
+        self.checked(ApiMessage::Cpu(CpuMessage::SetCpuOnline(
+            index as usize,
+            online,
+        )))
+    }
+
+    fn set_cpu_governor(&self, index: u32, governor: String) -> fdo::Result<()> {
+        self.checked(ApiMessage::Cpu(CpuMessage::SetCpuGovernor(
+            index as usize,
+            governor,
+        )))
+    }
+
+    /// Clock limits (MHz) of a CPU as (min, max), or (0, 0) if not set
+    fn get_cpu_clock_limits(&self, index: u32) -> fdo::Result<(u64, u64)> {
+        self.request(|cb| ApiMessage::Cpu(CpuMessage::GetClockLimits(index as usize, cb)))
+            .map(limit_pair)
+    }
+
+    fn set_cpu_clock_limits(&self, index: u32, min: u64, max: u64) -> fdo::Result<()> {
+        self.checked(ApiMessage::Cpu(CpuMessage::SetClockLimits(
+            index as usize,
+            Some(MinMax { min, max }),
+        )))
+    }
+
+    fn unset_cpu_clock_limits(&self, index: u32) -> fdo::Result<()> {
+        self.send(ApiMessage::Cpu(CpuMessage::SetClockLimits(
+            index as usize,
+            None,
+        )))
+    }
+
+    /// GPU (fast, slow) PPT, or 0 for a value which isn't set
+    #[zbus(property)]
+    fn gpu_ppt(&self) -> fdo::Result<(u64, u64)> {
+        let (fast, slow) = self.request(|cb| ApiMessage::Gpu(GpuMessage::GetPpt(cb)))?;
+        Ok((limit(fast), limit(slow)))
+    }
+
+    /// GPU clock limits (MHz) as (min, max), or (0, 0) if not set
+    #[zbus(property)]
+    fn gpu_clock_limits(&self) -> fdo::Result<(u64, u64)> {
+        self.request(|cb| ApiMessage::Gpu(GpuMessage::GetClockLimits(cb)))
+            .map(limit_pair)
+    }
+
+    #[zbus(property)]
+    fn gpu_slow_memory(&self) -> fdo::Result<bool> {
+        self.request(|cb| ApiMessage::Gpu(GpuMessage::GetSlowMemory(cb)))
+    }
+
+    #[zbus(property)]
+    fn set_gpu_slow_memory(&self, slow: bool) -> zbus::Result<()> {
+        Ok(self.send(ApiMessage::Gpu(GpuMessage::SetSlowMemory(slow)))?)
+    }
+
+    fn set_gpu_ppt(&self, fast: u64, slow: u64) -> fdo::Result<()> {
+        self.checked(ApiMessage::Gpu(GpuMessage::SetPpt(Some(fast), Some(slow))))
+    }
+
+    fn unset_gpu_ppt(&self) -> fdo::Result<()> {
+        self.send(ApiMessage::Gpu(GpuMessage::SetPpt(None, None)))
+    }
+
+    fn set_gpu_clock_limits(&self, min: u64, max: u64) -> fdo::Result<()> {
+        self.checked(ApiMessage::Gpu(GpuMessage::SetClockLimits(Some(MinMax {
+            min,
+            max,
+        }))))
+    }
+
+    fn unset_gpu_clock_limits(&self) -> fdo::Result<()> {
+        self.send(ApiMessage::Gpu(GpuMessage::SetClockLimits(None)))
+    }
+
+    /// Name of the loaded profile
+    #[zbus(property)]
+    fn profile(&self) -> fdo::Result<String> {
+        self.request(|cb| ApiMessage::General(GeneralMessage::GetCurrentProfileName(cb)))
+    }
+
+    #[zbus(property)]
+    fn profile_path(&self) -> fdo::Result<String> {
+        self.request(|cb| ApiMessage::General(GeneralMessage::GetPath(cb)))
+            .map(|path| path.display().to_string())
+    }
+
+    /// Load the profile of an app id
+    fn load_profile(&self, id: u64, name: String) -> fdo::Result<()> {
+        self.send(ApiMessage::LoadSettings(id, name))
+    }
+
+    fn load_main_profile(&self) -> fdo::Result<()> {
+        self.send(ApiMessage::LoadMainSettings)
+    }
+
+    #[zbus(property)]
+    fn presets(&self) -> fdo::Result<Vec<String>> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::List(cb)))
+    }
+
+    /// Preset the loaded profile follows, or an empty string if none
+    #[zbus(property)]
+    fn preset(&self) -> fdo::Result<String> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::GetCurrent(cb)))
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Make the loaded profile follow a preset
+    fn apply_preset(&self, name: String) -> fdo::Result<()> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::Apply(name, cb)))?
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    /// Save the current settings as a new preset
+    fn create_preset(&self, name: String) -> fdo::Result<()> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::Create(name, cb)))?
+            .map_err(fdo::Error::Failed)
+    }
+
+    /// Replace a preset with the current settings
+    fn update_preset(&self, name: String) -> fdo::Result<()> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::Update(name, cb)))?
+            .map_err(fdo::Error::Failed)
+    }
+
+    fn delete_preset(&self, name: String) -> fdo::Result<()> {
+        self.request(|cb| ApiMessage::Preset(PresetMessage::Delete(name, cb)))?
+            .map_err(fdo::Error::Failed)
+    }
+
+    /// Apply all settings again
+    fn apply_now(&self) -> fdo::Result<()> {
+        self.send(ApiMessage::General(GeneralMessage::ApplyNow))
+    }
+
+    /// Limits of the current drivers, as JSON
+    fn get_limits(&self) -> fdo::Result<String> {
+        Ok(serde_json::to_string(&self.request(ApiMessage::GetLimits)?).unwrap())
+    }
+
+    /// All settings, as JSON
+    fn get_settings_json(&self) -> fdo::Result<String> {
+        Ok(serde_json::to_string(&self.request(ApiMessage::GetSettingsJson)?).unwrap())
+    }
+
+    /// Replace all settings with JSON in the same format as GetSettingsJson
+    fn set_settings_json(&self, json: String) -> fdo::Result<()> {
+        let settings = serde_json::from_str(&json)
+            .map_err(|e| fdo::Error::InvalidArgs(format!("Invalid settings JSON: {}", e)))?;
+        self.request(|cb| ApiMessage::SetSettingsJson(Box::new(settings), cb))?
+            .map_err(|errors| fdo::Error::InvalidArgs(errors.join("\n")))
+    }
+
+    #[zbus(signal)]
+    async fn power_source_changed(ctxt: &SignalContext<'_>, plugged_in: bool) -> zbus::Result<()>;
+
+    /// Battery fill amount crossed into a new value: 0 = empty, 1 = full
+    #[zbus(signal)]
+    async fn battery_charge_changed(ctxt: &SignalContext<'_>, charge: f64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn resumed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn profile_loaded(ctxt: &SignalContext<'_>, name: &str, path: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn applied(ctxt: &SignalContext<'_>, outcome: &str, drifted: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn setting_error(
+        ctxt: &SignalContext<'_>,
+        setting: &str,
+        kind: &str,
+        msg: &str,
+    ) -> zbus::Result<()>;
+
+    /// A profile switching rule started matching
+    #[zbus(signal)]
+    async fn rule_matched(ctxt: &SignalContext<'_>, name: &str) -> zbus::Result<()>;
+}
+
+/// Connect to `bus` (system, session or a bus address) and serve the manager there,
+/// then turn back-end events into signals
+pub fn spawn(
+    bus: &str,
+    power_profiles: bool,
+    sender: Sender<ApiMessage>,
+    events: EventBus,
+) -> zbus::Result<JoinHandle<()>> {
+    let builder = match bus {
+        "system" => Builder::system()?,
+        "session" => Builder::session()?,
+        address => Builder::address(address)?,
+    };
+    let mut builder = builder.name(BUS_NAME)?;
+    if power_profiles {
+        let profiles = PowerProfiles::new(sender.clone());
+        let upower = profiles.upower();
+        builder = builder
+            .name(super::power_profiles::BUS_NAME)?
+            .name(super::power_profiles::UPOWER_BUS_NAME)?
+            .serve_at(super::power_profiles::OBJECT_PATH, profiles)?
+            .serve_at(super::power_profiles::UPOWER_OBJECT_PATH, upower)?;
+        log::info!(
+            "Serving {} and {} on the {} D-Bus",
+            super::power_profiles::BUS_NAME,
+            super::power_profiles::UPOWER_BUS_NAME,
+            bus
+        );
+    }
+    let connection = serve(builder, sender)?;
+    log::info!("Serving {} on the {} D-Bus", BUS_NAME, bus);
+    Ok(thread::spawn(move || {
+        log::info!("dbus_worker starting...");
+        forward_events(connection, events)
+    }))
+}
+
+fn serve(builder: Builder<'_>, sender: Sender<ApiMessage>) -> zbus::Result<Connection> {
+    builder.serve_at(OBJECT_PATH, Manager::new(sender))?.build()
+}
+
+fn forward_events(connection: Connection, events: EventBus) {
+    let server = connection.object_server();
+    let iface = server
+        .interface::<_, Manager>(OBJECT_PATH)
+        .expect("dbus interface missing");
+    let power_profiles = server
+        .interface::<_, PowerProfiles>(super::power_profiles::OBJECT_PATH)
+        .ok();
+    if let Some(ppd) = &power_profiles {
+        ppd.get()
+            .set_signal_context(ppd.signal_context().to_owned());
+    }
+    if let Ok(upower) =
+        server.interface::<_, UPowerPowerProfiles>(super::power_profiles::UPOWER_OBJECT_PATH)
+    {
+        upower
+            .get()
+            .set_signal_context(upower.signal_context().to_owned());
+    }
+    // signals are for what happens from now on, so don't replay the older events
+    let mut seq = events.last_seq();
+    loop {
+        let batch = events.wait(seq, EVENT_WAIT);
+        seq = batch.seq;
+        for record in batch.events {
+            if let Err(e) = emit(&iface, power_profiles.as_ref(), record.event) {
+                log::warn!("Failed to emit D-Bus signal: {}", e);
+            }
+        }
+    }
+}
+
+fn emit(
+    iface: &InterfaceRef<Manager>,
+    power_profiles: Option<&InterfaceRef<PowerProfiles>>,
+    event: Event,
+) -> zbus::Result<()> {
+    let manager = iface.get();
+    let power_profiles = power_profiles.map(|ppd| ppd.get());
+    let ctxt = iface.signal_context();
+    zbus::block_on(async {
+        match event {
+            Event::PluggedIn => Manager::power_source_changed(ctxt, true).await,
+            Event::PluggedOut => Manager::power_source_changed(ctxt, false).await,
+            Event::BatteryCharge { charge } => Manager::battery_charge_changed(ctxt, charge).await,
+            Event::Battery { .. } => {
+                manager.charge_now_changed(ctxt).await?;
+                manager.charge_full_changed(ctxt).await?;
+                manager.current_now_changed(ctxt).await
+            }
+            Event::Resume => Manager::resumed(ctxt).await,
+            Event::ProfileLoaded { name, path } => {
+                Manager::profile_loaded(ctxt, &name, &path).await?;
+                manager.profile_changed(ctxt).await?;
+                manager.profile_path_changed(ctxt).await?;
+                manager.preset_changed(ctxt).await?;
+                match &power_profiles {
+                    Some(ppd) => ppd.profile_loaded(&path).await,
+                    None => Ok(()),
+                }
+            }
+            Event::Applied { outcome, drifted } => {
+                Manager::applied(ctxt, &outcome, drifted as u32).await
+            }
+            Event::Error(e) => Manager::setting_error(ctxt, &e.setting, &e.kind, &e.msg).await,
+            Event::RuleMatched { name } => Manager::rule_matched(ctxt, &name).await,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::unix::net::UnixStream;
+
+    use crate::settings::PowerMode;
+
+    #[zbus::proxy(
+        interface = "org.powertools.Manager",
+        default_service = "org.powertools.Manager",
+        default_path = "/org/powertools/Manager"
+    )]
+    trait Manager {
+        #[zbus(property)]
+        fn profile(&self) -> zbus::Result<String>;
+
+        #[zbus(property)]
+        fn charge_now(&self) -> zbus::Result<f64>;
+
+        fn set_gpu_ppt(&self, fast: u64, slow: u64) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        fn power_source_changed(&self, plugged_in: bool) -> zbus::Result<()>;
+    }
+
+    /// Stand-in for ApiMessageHandler which answers the few messages this test sends
+    fn fake_handler(receiver: mpsc::Receiver<ApiMessage>) {
+        for message in receiver {
+            match message {
+                ApiMessage::General(GeneralMessage::GetCurrentProfileName(cb)) => {
+                    cb("Test profile".to_owned())
+                }
+                ApiMessage::Battery(BatteryMessage::ReadChargeNow(cb)) => cb(None),
+                ApiMessage::Checked(_, cb) => cb(Err(vec!["too fast".to_owned()])),
+                _ => panic!("unexpected message"),
+            }
+        }
+    }
+
+    #[test]
+    fn private_connection_test() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || fake_handler(receiver));
+        let events = EventBus::default();
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = thread::spawn(move || {
+            serve(
+                Builder::unix_stream(server_stream)
+                    .server(guid)
+                    .unwrap()
+                    .p2p(),
+                sender,
+            )
+            .unwrap()
+        });
+        let client = Builder::unix_stream(client_stream).p2p().build().unwrap();
+        let server = server.join().unwrap();
+        let events2 = events.clone();
+        thread::spawn(move || forward_events(server, events2));
+
+        let proxy = ManagerProxyBlocking::builder(&client)
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .unwrap();
+        assert_eq!(proxy.profile().unwrap(), "Test profile");
+        assert_eq!(proxy.charge_now().unwrap(), -1.0);
+        match proxy.set_gpu_ppt(1, 2) {
+            Err(zbus::Error::MethodError(name, Some(msg), _)) => {
+                assert_eq!(name.as_str(), "org.freedesktop.DBus.Error.InvalidArgs");
+                assert_eq!(msg, "too fast");
+            }
+            other => panic!("expected InvalidArgs, got {:?}", other),
+        }
+
+        let mut signals = proxy.receive_power_source_changed().unwrap();
+        events.publish((&PowerMode::PluggedOut).into());
+        let signal = signals.next().unwrap();
+        assert!(!signal.args().unwrap().plugged_in);
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use usdpl_back::core::serdes::Primitive;
+use usdpl_back::AsyncCallable;
+
+use super::handler::{ApiMessage, FanMessage};
+use crate::persist::FanJson;
+use crate::settings::{FanCurve, LATEST_VERSION};
+
+/// Generate set fan curve web method.
+/// The only parameter is a FanJson object (as a JSON string); a null curve means firmware fan control
+pub fn set_curve(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |fan: FanJson| {
+        let curve = fan
+            .curve
+            .map(|points| FanCurve::from_json(points, fan.sensor, LATEST_VERSION));
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Fan(FanMessage::SetCurve(curve)))
+            .expect("set_curve send failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::Json(json)) | Some(Primitive::String(json)) => {
+            match serde_json::from_str::<FanJson>(json) {
+                Ok(fan) => {
+                    setter(fan);
+                    vec![true.into()]
+                }
+                Err(e) => {
+                    log::warn!("set_curve got invalid fan curve: {}", e);
+                    vec![format!("set_curve invalid parameter: {}", e).into()]
+                }
+            }
+        }
+        _ => vec!["set_curve missing parameter".into()],
+    }
+}
+
+/// Generate get fan curve web method
+pub fn get_curve(sender: Sender<ApiMessage>) -> impl AsyncCallable {
+    let sender = Arc::new(Mutex::new(sender)); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let sender2 = sender.clone();
+        move || {
+            let (tx, rx) = mpsc::channel();
+            let callback =
+                move |fan: FanJson| tx.send(fan).expect("get_curve callback send failed");
+            sender2
+                .lock()
+                .unwrap()
+                .send(ApiMessage::Fan(FanMessage::GetCurve(Box::new(callback))))
+                .expect("get_curve send failed");
+            rx.recv().expect("get_curve callback recv failed")
+        }
+    };
+    super::async_utils::AsyncIshGetter {
+        set_get: getter,
+        trans_getter: |fan: FanJson| vec![Primitive::Json(serde_json::to_string(&fan).unwrap())],
+    }
+}
+
+/// Generate unset fan curve (return to firmware control) web method
+pub fn unset_curve(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move || {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Fan(FanMessage::SetCurve(None)))
+            .expect("unset_curve send failed")
+    };
+    move |_: super::ApiParameterType| {
+        setter();
+        vec![true.into()]
+    }
+}
@@ -1,10 +1,15 @@
 use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use usdpl_back::core::serdes::Primitive;
 use usdpl_back::AsyncCallable;
 
 //use crate::utility::{unwrap_lock, unwrap_maybe_fatal};
-use super::handler::{ApiMessage, GeneralMessage};
+use super::handler::{ApiMessage, GeneralMessage, TelemetryMessage, WatchdogMessage};
+use crate::persist::ValidationMode;
+
+/// Longest a wait_event call is held open before responding without events
+const MAX_EVENT_WAIT: Duration = Duration::from_secs(30);
 
 /// Generate set persistent web method
 pub fn set_persistent(
@@ -19,7 +24,7 @@ pub fn set_persistent(
             .expect("set_persistent send failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::Bool(new_val)) = params_in.get(0) {
+        if let Some(&Primitive::Bool(new_val)) = params_in.first() {
             setter(new_val);
             //log::debug!("Persistent is now {}", settings_lock.persistent);
             vec![new_val.into()]
@@ -63,14 +68,14 @@ pub fn load_settings(
             .expect("load_settings send failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(Primitive::String(id)) = params_in.get(0) {
-            if let Some(Primitive::String(name)) = params_in.get(1) {
-                setter(id.parse().unwrap_or_default(), name.to_owned());
-                vec![true.into()]
-            } else {
-                log::warn!("load_settings missing name parameter");
-                vec!["load_settings missing name parameter".into()]
-            }
+        if let Some(Primitive::String(id)) = params_in.first() {
+            // the front-end names new profiles after their game, scripts may only know the app id
+            let name = match params_in.get(1) {
+                Some(Primitive::String(name)) => name.to_owned(),
+                _ => id.to_owned(),
+            };
+            setter(id.parse().unwrap_or_default(), name);
+            vec![true.into()]
             //let mut general_lock = unwrap_lock(settings.general.lock(), "general");
         } else {
             log::warn!("load_settings missing id parameter");
@@ -108,6 +113,25 @@ pub fn load_default_settings(
     }
 }
 
+/// Generate restore stock hardware settings web method.
+/// The profile stays loaded, but changes aren't saved to it until a profile is loaded again
+pub fn restore_stock(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move || {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::RestoreStock)
+            .expect("restore_stock send failed")
+    };
+    move |_: super::ApiParameterType| {
+        setter();
+        vec![true.into()]
+    }
+}
+
 /// Generate load system default settings from file web method
 pub fn load_system_settings(
     sender: Sender<ApiMessage>,
@@ -255,7 +279,7 @@ pub fn get_provider(sender: Sender<ApiMessage>) -> impl AsyncCallable {
             if let Some(Primitive::String(name)) = params.pop() {
                 Ok(name.to_owned())
             } else {
-                Err(format!("Invalid/missing single param in get_provider"))
+                Err("Invalid/missing single param in get_provider".to_string())
             }
         },
         set_get: getter,
@@ -275,7 +299,7 @@ pub fn gunter(_: super::ApiParameterType) -> super::ApiParameterType {
 /// API web method to send log messages to the back-end log, callable from the front-end
 pub fn log_it() -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     move |params| {
-        if let Some(Primitive::F64(level)) = params.get(0) {
+        if let Some(Primitive::F64(level)) = params.first() {
             if let Some(Primitive::String(msg)) = params.get(1) {
                 log_msg_by_level(*level as u8, msg);
                 vec![true.into()]
@@ -293,6 +317,31 @@ pub fn log_it() -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     }
 }
 
+/// Generate set log verbosity web method; it lasts until the back-end restarts.
+/// Returns true, or why it failed
+pub fn set_log_level() -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    move |params| {
+        if let Some(Primitive::String(level)) = params.first() {
+            match level.parse::<log::LevelFilter>() {
+                Ok(level) => {
+                    log::set_max_level(level);
+                    log::info!("Log level is now {}", level);
+                    vec![true.into()]
+                }
+                Err(_) => vec![format!("Invalid log level `{}`", level).into()],
+            }
+        } else {
+            log::warn!("Got set_log_level call with wrong/missing parameter");
+            vec![false.into()]
+        }
+    }
+}
+
+/// Generate get log verbosity web method
+pub fn get_log_level() -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    move |_| vec![log::max_level().as_str().to_lowercase().into()]
+}
+
 fn log_msg_by_level(level: u8, msg: &str) {
     match level {
         1 => log::trace!("FRONT-END: {}", msg),
@@ -321,3 +370,676 @@ pub fn force_apply(
         vec![true.into()]
     }
 }
+
+/// Generate set telemetry recording enabled web method
+pub fn set_telemetry(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |enabled: bool| {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Telemetry(TelemetryMessage::SetEnabled(enabled)))
+            .expect("set_telemetry send failed")
+    };
+    move |params_in: super::ApiParameterType| {
+        if let Some(&Primitive::Bool(enabled)) = params_in.first() {
+            setter(enabled);
+            vec![enabled.into()]
+        } else {
+            vec!["set_telemetry missing parameter".into()]
+        }
+    }
+}
+
+/// Generate get telemetry recording enabled web method
+pub fn get_telemetry(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback =
+            move |value: bool| tx.send(value).expect("get_telemetry callback send failed");
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Telemetry(TelemetryMessage::GetEnabled(
+                Box::new(callback),
+            )))
+            .expect("get_telemetry send failed");
+        rx.recv().expect("get_telemetry callback recv failed")
+    };
+    move |_: super::ApiParameterType| vec![getter().into()]
+}
+
+/// Generate export telemetry as CSV web method.
+/// Optional parameters are the start and end of the time range, in seconds since the UNIX epoch
+pub fn export_telemetry(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move |start: Option<u64>, end: Option<u64>| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Result<String, String>| {
+            tx.send(value)
+                .expect("export_telemetry callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Telemetry(TelemetryMessage::Export(
+                start,
+                end,
+                Box::new(callback),
+            )))
+            .expect("export_telemetry send failed");
+        rx.recv().expect("export_telemetry callback recv failed")
+    };
+    move |params_in: super::ApiParameterType| {
+        let start = match params_in.first() {
+            Some(Primitive::F64(x)) => Some(*x as u64),
+            _ => None,
+        };
+        let end = match params_in.get(1) {
+            Some(Primitive::F64(x)) => Some(*x as u64),
+            _ => None,
+        };
+        match getter(start, end) {
+            Ok(csv) => vec![csv.into()],
+            Err(e) => {
+                log::warn!("export_telemetry failed: {}", e);
+                vec![Primitive::Empty]
+            }
+        }
+    }
+}
+
+/// Generate set thermal throttling rules web method
+pub fn set_thermal_rules(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |rules: Vec<crate::persist::ThermalRuleJson>| {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::SetThermalRules(rules))
+            .expect("set_thermal_rules send failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::Json(json)) | Some(Primitive::String(json)) => {
+            match serde_json::from_str(json) {
+                Ok(rules) => {
+                    setter(rules);
+                    vec![true.into()]
+                }
+                Err(e) => {
+                    log::warn!("set_thermal_rules got invalid rules: {}", e);
+                    vec![format!("set_thermal_rules invalid parameter: {}", e).into()]
+                }
+            }
+        }
+        _ => vec!["set_thermal_rules missing parameter".into()],
+    }
+}
+
+/// Generate get thermal throttling rules web method
+pub fn get_thermal_rules(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Vec<crate::persist::ThermalRuleJson>| {
+            tx.send(value)
+                .expect("get_thermal_rules callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetThermalRules(Box::new(callback)))
+            .expect("get_thermal_rules send failed");
+        rx.recv().expect("get_thermal_rules callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate set profile switching rules web method.
+/// Returns true, or the reasons why the rules are not allowed
+pub fn set_rules(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |rules: crate::persist::RulesJson| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Result<(), Vec<String>>| {
+            tx.send(value).expect("set_rules callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::SetRules(rules, Box::new(callback)))
+            .expect("set_rules send failed");
+        rx.recv().expect("set_rules callback recv failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::Json(json)) | Some(Primitive::String(json)) => {
+            match serde_json::from_str(json) {
+                Ok(rules) => match setter(rules) {
+                    Ok(()) => vec![true.into()],
+                    Err(errors) => errors.into_iter().map(|e| e.into()).collect(),
+                },
+                Err(e) => {
+                    log::warn!("set_rules got invalid rules: {}", e);
+                    vec![format!("set_rules invalid parameter: {}", e).into()]
+                }
+            }
+        }
+        _ => vec!["set_rules missing parameter".into()],
+    }
+}
+
+/// Generate get profile switching rules web method
+pub fn get_rules(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: crate::persist::RulesJson| {
+            tx.send(value).expect("get_rules callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetRules(Box::new(callback)))
+            .expect("get_rules send failed");
+        rx.recv().expect("get_rules callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate undo last change of current profile web method.
+/// Returns true, or why it failed
+pub fn undo(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback =
+            move |result: Result<(), String>| tx.send(result).expect("undo callback send failed");
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Undo(Box::new(callback)))
+            .expect("undo send failed");
+        rx.recv().expect("undo callback recv failed")
+    };
+    move |_: super::ApiParameterType| match setter() {
+        Ok(()) => vec![true.into()],
+        Err(e) => vec![e.into()],
+    }
+}
+
+/// Generate redo last undone change of current profile web method.
+/// Returns true, or why it failed
+pub fn redo(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback =
+            move |result: Result<(), String>| tx.send(result).expect("redo callback send failed");
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Redo(Box::new(callback)))
+            .expect("redo send failed");
+        rx.recv().expect("redo callback recv failed")
+    };
+    move |_: super::ApiParameterType| match setter() {
+        Ok(()) => vec![true.into()],
+        Err(e) => vec![e.into()],
+    }
+}
+
+/// Generate get change history of current profile web method
+pub fn get_history(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: crate::persist::HistoryJson| {
+            tx.send(value).expect("get_history callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetHistory(Box::new(callback)))
+            .expect("get_history send failed");
+        rx.recv().expect("get_history callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate compare two saved profiles web method.
+/// Parameters are app ids, or null for the main profile
+pub fn diff_profiles(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move |old: Option<u64>, new: Option<u64>| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Result<Vec<crate::persist::ChangeJson>, String>| {
+            tx.send(value).expect("diff_profiles callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::DiffProfiles(old, new, Box::new(callback)))
+            .expect("diff_profiles send failed");
+        rx.recv().expect("diff_profiles callback recv failed")
+    };
+    move |params_in: super::ApiParameterType| {
+        let old = match params_in.first() {
+            Some(Primitive::F64(x)) => Some(*x as u64),
+            _ => None,
+        };
+        let new = match params_in.get(1) {
+            Some(Primitive::F64(x)) => Some(*x as u64),
+            _ => None,
+        };
+        match getter(old, new) {
+            Ok(changes) => vec![Primitive::Json(serde_json::to_string(&changes).unwrap())],
+            Err(e) => {
+                log::warn!("diff_profiles failed: {}", e);
+                vec![e.into()]
+            }
+        }
+    }
+}
+
+/// Generate export current profile for sharing web method
+pub fn export_profile(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: crate::persist::ProfileExportJson| {
+            tx.send(value).expect("export_profile callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::ExportProfile(Box::new(callback)))
+            .expect("export_profile send failed");
+        rx.recv().expect("export_profile callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate import shared profile web method.
+/// An existing profile of the same app is only replaced when the second parameter is true.
+/// Returns a report of what was changed to fit this device, or the reasons why it can't be imported
+pub fn import_profile(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |export: crate::persist::ProfileExportJson, overwrite: bool| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Result<super::ImportReport, Vec<String>>| {
+            tx.send(value).expect("import_profile callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::ImportProfile(
+                Box::new(export),
+                overwrite,
+                Box::new(callback),
+            ))
+            .expect("import_profile send failed");
+        rx.recv().expect("import_profile callback recv failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::Json(json)) | Some(Primitive::String(json)) => {
+            match serde_json::from_str(json) {
+                Ok(export) => match setter(
+                    export,
+                    matches!(params_in.get(1), Some(Primitive::Bool(true))),
+                ) {
+                    Ok(report) => vec![Primitive::Json(serde_json::to_string(&report).unwrap())],
+                    Err(errors) => errors.into_iter().map(|e| e.into()).collect(),
+                },
+                Err(e) => {
+                    log::warn!("import_profile got invalid profile: {}", e);
+                    vec![format!("import_profile invalid parameter: {}", e).into()]
+                }
+            }
+        }
+        _ => vec!["import_profile missing parameter".into()],
+    }
+}
+
+/// Generate set all settings from a settings JSON document web method.
+/// Returns true, or the reasons why the settings are not allowed
+pub fn set_settings_json(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |json: crate::persist::SettingsJson| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Result<(), Vec<String>>| {
+            tx.send(value)
+                .expect("set_settings_json callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::SetSettingsJson(
+                Box::new(json),
+                Box::new(callback),
+            ))
+            .expect("set_settings_json send failed");
+        rx.recv().expect("set_settings_json callback recv failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::Json(json)) | Some(Primitive::String(json)) => {
+            match serde_json::from_str(json) {
+                Ok(settings) => match setter(settings) {
+                    Ok(()) => vec![true.into()],
+                    Err(errors) => errors.into_iter().map(|e| e.into()).collect(),
+                },
+                Err(e) => {
+                    log::warn!("set_settings_json got invalid settings: {}", e);
+                    vec![format!("set_settings_json invalid parameter: {}", e).into()]
+                }
+            }
+        }
+        _ => vec!["set_settings_json missing parameter".into()],
+    }
+}
+
+/// Generate get all settings as a settings JSON document web method
+pub fn get_settings_json(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: crate::persist::SettingsJson| {
+            tx.send(value)
+                .expect("get_settings_json callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetSettingsJson(Box::new(callback)))
+            .expect("get_settings_json send failed");
+        rx.recv().expect("get_settings_json callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate get apply report web method
+pub fn get_apply_report(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: super::ApplyReport| {
+            tx.send(value)
+                .expect("get_apply_report callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetApplyReport(Box::new(callback)))
+            .expect("get_apply_report send failed");
+        rx.recv().expect("get_apply_report callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate get latest setting errors web method
+pub fn get_errors(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Vec<super::SettingErrorInfo>| {
+            tx.send(value).expect("get_errors callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetErrors(Box::new(callback)))
+            .expect("get_errors send failed");
+        rx.recv().expect("get_errors callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate set drift watchdog enabled web method
+pub fn set_watchdog(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |enabled: bool| {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Watchdog(WatchdogMessage::SetEnabled(enabled)))
+            .expect("set_watchdog send failed")
+    };
+    move |params_in: super::ApiParameterType| {
+        if let Some(&Primitive::Bool(enabled)) = params_in.first() {
+            setter(enabled);
+            vec![enabled.into()]
+        } else {
+            vec!["set_watchdog missing parameter".into()]
+        }
+    }
+}
+
+/// Generate get drift watchdog enabled web method
+pub fn get_watchdog(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback =
+            move |value: bool| tx.send(value).expect("get_watchdog callback send failed");
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Watchdog(WatchdogMessage::GetEnabled(Box::new(
+                callback,
+            ))))
+            .expect("get_watchdog send failed");
+        rx.recv().expect("get_watchdog callback recv failed")
+    };
+    move |_: super::ApiParameterType| vec![getter().into()]
+}
+
+/// Generate set yield to system web method.
+/// Parameters are the setting (battery, cpu, gpu or fan) and whether to leave it alone when another process changes it
+pub fn set_yield_to_system(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |setting: String, val: bool| {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Watchdog(WatchdogMessage::SetYield(
+                setting, val,
+            )))
+            .expect("set_yield_to_system send failed")
+    };
+    move |params_in: super::ApiParameterType| match (params_in.first(), params_in.get(1)) {
+        (Some(Primitive::String(setting)), Some(&Primitive::Bool(val))) => {
+            setter(setting.to_owned(), val);
+            vec![val.into()]
+        }
+        _ => vec!["set_yield_to_system missing parameter".into()],
+    }
+}
+
+/// Generate get settings which yield to system web method
+pub fn get_yield_to_system(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Vec<String>| {
+            tx.send(value)
+                .expect("get_yield_to_system callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Watchdog(WatchdogMessage::GetYield(Box::new(
+                callback,
+            ))))
+            .expect("get_yield_to_system send failed");
+        rx.recv().expect("get_yield_to_system callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate set validation mode web method.
+/// The parameter is "reject" (refuse values outside of the limits) or "clamp" (adjust them to fit)
+pub fn set_validation_mode(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |mode: ValidationMode| {
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::SetValidationMode(mode))
+            .expect("set_validation_mode send failed")
+    };
+    move |params_in: super::ApiParameterType| match params_in.first() {
+        Some(Primitive::String(mode)) => {
+            match serde_json::from_value(serde_json::Value::String(mode.to_owned())) {
+                Ok(mode) => {
+                    setter(mode);
+                    vec![mode_to_primitive(mode)]
+                }
+                Err(_) => vec![format!("set_validation_mode invalid mode `{}`", mode).into()],
+            }
+        }
+        _ => vec!["set_validation_mode missing parameter".into()],
+    }
+}
+
+/// Generate get validation mode web method
+pub fn get_validation_mode(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: ValidationMode| {
+            tx.send(value)
+                .expect("get_validation_mode callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetValidationMode(Box::new(callback)))
+            .expect("get_validation_mode send failed");
+        rx.recv().expect("get_validation_mode callback recv failed")
+    };
+    move |_: super::ApiParameterType| vec![mode_to_primitive(getter())]
+}
+
+fn mode_to_primitive(mode: ValidationMode) -> Primitive {
+    match serde_json::to_value(mode) {
+        Ok(serde_json::Value::String(s)) => s.into(),
+        _ => Primitive::Empty,
+    }
+}
+
+/// Generate get power source whose CPU and GPU values are in use web method.
+/// Returns `ac`, `battery`, or nothing when the profile's own values are in use
+pub fn get_power_source(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |value: Option<crate::persist::PowerSourceJson>| {
+            tx.send(value)
+                .expect("get_power_source callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::GetPowerSource(Box::new(callback)))
+            .expect("get_power_source send failed");
+        rx.recv().expect("get_power_source callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![match serde_json::to_value(getter()) {
+            Ok(serde_json::Value::String(s)) => s.into(),
+            _ => Primitive::Empty,
+        }]
+    }
+}
+
+/// Generate wait for events web method.
+/// Parameters are the latest sequence number the client has seen (0 for none)
+/// and optionally how many seconds to wait for new events
+pub fn wait_event(events: crate::events::EventBus) -> impl AsyncCallable {
+    let getter = move || {
+        let events2 = events.clone();
+        move |(since, timeout): (u64, Duration)| events2.wait(since, timeout)
+    };
+    super::async_utils::AsyncIsh {
+        trans_setter: |params| {
+            let since = match params.first() {
+                Some(&Primitive::F64(since)) => since as u64,
+                Some(Primitive::Empty) | None => 0,
+                _ => return Err("wait_event invalid parameter 0".to_owned()),
+            };
+            let timeout = match params.get(1) {
+                Some(&Primitive::F64(secs)) => {
+                    Duration::from_secs_f64(secs.clamp(0.0, MAX_EVENT_WAIT.as_secs_f64()))
+                }
+                _ => MAX_EVENT_WAIT,
+            };
+            Ok((since, timeout))
+        },
+        set_get: getter,
+        trans_getter: |batch| vec![Primitive::Json(serde_json::to_string(&batch).unwrap())],
+    }
+}
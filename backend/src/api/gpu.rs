@@ -12,17 +12,16 @@ pub fn set_ppt(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |fast: u64, slow: u64| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Gpu(GpuMessage::SetPpt(Some(fast), Some(slow))))
-            .expect("set_ppt send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Gpu(GpuMessage::SetPpt(Some(fast), Some(slow))),
+            "set_ppt",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(fast_ppt)) = params_in.get(0) {
+        if let Some(&Primitive::F64(fast_ppt)) = params_in.first() {
             if let Some(&Primitive::F64(slow_ppt)) = params_in.get(1) {
-                setter(fast_ppt as u64, slow_ppt as u64);
-                vec![(fast_ppt as u64).into(), (slow_ppt as u64).into()]
+                super::utility::map_checked(setter(fast_ppt as u64, slow_ppt as u64))
             } else {
                 vec!["set_ppt missing parameter 1".into()]
             }
@@ -82,22 +81,20 @@ pub fn set_clock_limits(
 ) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
     let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
     let setter = move |value: MinMax<u64>| {
-        sender
-            .lock()
-            .unwrap()
-            .send(ApiMessage::Gpu(GpuMessage::SetClockLimits(Some(value))))
-            .expect("set_clock_limits send failed")
+        super::utility::send_checked(
+            &sender,
+            ApiMessage::Gpu(GpuMessage::SetClockLimits(Some(value))),
+            "set_clock_limits",
+        )
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::F64(min)) = params_in.get(0) {
+        if let Some(&Primitive::F64(min)) = params_in.first() {
             if let Some(&Primitive::F64(max)) = params_in.get(1) {
-                let safe_max = if max < min { min } else { max };
-                let safe_min = if min > max { max } else { min };
-                setter(MinMax {
-                    min: safe_min as _,
-                    max: safe_max as _,
+                let result = setter(MinMax {
+                    min: min as _,
+                    max: max as _,
                 });
-                vec![(safe_min as u64).into(), (safe_max as u64).into()]
+                super::utility::map_checked(result)
             } else {
                 vec!["set_clock_limits missing parameter 1".into()]
             }
@@ -166,7 +163,7 @@ pub fn set_slow_memory(
             .expect("unset_clock_limits send failed")
     };
     move |params_in: super::ApiParameterType| {
-        if let Some(&Primitive::Bool(memory_is_slow)) = params_in.get(0) {
+        if let Some(&Primitive::Bool(memory_is_slow)) = params_in.first() {
             setter(memory_is_slow);
             vec![memory_is_slow.into()]
         } else {
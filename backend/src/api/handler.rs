@@ -1,19 +1,56 @@
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::sync::mpsc::{self, Receiver, Sender};
+use usdpl_back::core::serdes::Primitive;
 
-use crate::persist::SettingsJson;
+use crate::events::{Event, EventBus};
+use crate::persist::{
+    ChangeJson, ChangeSourceJson, HistoryJson, RuleTargetJson, RulesJson, SettingsJson,
+    ValidationMode,
+};
+use crate::rules::{Conditions, Rules};
+use crate::save_worker::SaveMessage;
+use crate::settings::history::History;
+use crate::settings::presets;
+use crate::settings::transaction::Outcome;
+use crate::settings::validate::Checks;
 use crate::settings::{
-    MinMax, OnPowerEvent, OnResume, OnSet, PowerMode, Settings, TBattery, TCpus, TGeneral, TGpu,
+    FanCurve, MinMax, OnPowerEvent, OnResume, OnSet, OnShutdown, OnVerify, PowerMode, SettingError,
+    SettingVariant, Settings, TBattery, TCpus, TFan, TGeneral, TGpu, TriggerEvent, Triggers,
 };
-use crate::utility::unwrap_maybe_fatal;
+use crate::telemetry::Telemetry;
+use crate::watchdog::Watchdog;
 
 type Callback<T> = Box<dyn FnOnce(T) + Send>;
 
+/// Settings file of the profile of app `id`, or the main profile without one
+fn profile_file(id: Option<u64>) -> std::path::PathBuf {
+    match id {
+        Some(id) => format!("{}.json", id).into(),
+        None => crate::consts::DEFAULT_SETTINGS_FILE.into(),
+    }
+}
+
+/// Settings which have drifted values
+fn drifted_variants(values: &[super::AppliedValue]) -> Vec<SettingVariant> {
+    SettingVariant::APPLIED
+        .into_iter()
+        .filter(|variant| {
+            let name = variant.to_string();
+            values.iter().any(|v| v.is_drifted() && v.setting == name)
+        })
+        .collect()
+}
+
 pub enum ApiMessage {
     Battery(BatteryMessage),
     Cpu(CpuMessage),
     Gpu(GpuMessage),
+    Fan(FanMessage),
     General(GeneralMessage),
+    Telemetry(TelemetryMessage),
+    Watchdog(WatchdogMessage),
+    Preset(PresetMessage),
     OnResume,
     #[allow(dead_code)]
     OnPluggedIn,
@@ -22,12 +59,75 @@ pub enum ApiMessage {
     #[allow(dead_code)]
     OnChargeChange(f64), // battery fill amount: 0 = empty, 1 = full
     PowerVibeCheck,
+    ThermalCheck,
+    SetThermalRules(Vec<crate::persist::ThermalRuleJson>),
+    GetThermalRules(Callback<Vec<crate::persist::ThermalRuleJson>>),
     WaitForEmptyQueue(Callback<()>),
     LoadSettings(u64, String), // (path, name)
     LoadMainSettings,
     LoadSystemSettings,
     GetLimits(Callback<super::SettingsLimits>),
+    SetSettingsJson(Box<SettingsJson>, Callback<Result<(), Vec<String>>>),
+    GetSettingsJson(Callback<SettingsJson>),
     GetProvider(String, Callback<crate::persist::DriverJson>),
+    Shutdown(Callback<()>),
+    RestoreStock,
+    GetApplyReport(Callback<super::ApplyReport>),
+    GetErrors(Callback<Vec<super::SettingErrorInfo>>),
+    /// Process a message, and tell the caller if its new values were rejected
+    Checked(
+        Box<ApiMessage>,
+        Callback<Result<super::ApiParameterType, Vec<String>>>,
+    ),
+    SetValidationMode(ValidationMode),
+    GetValidationMode(Callback<ValidationMode>),
+    GetPowerSource(Callback<Option<crate::persist::PowerSourceJson>>),
+    SetRules(RulesJson, Callback<Result<(), Vec<String>>>),
+    GetRules(Callback<RulesJson>),
+    ExportProfile(Callback<crate::persist::ProfileExportJson>),
+    ImportProfile(
+        Box<crate::persist::ProfileExportJson>,
+        bool, // overwrite an existing profile of the app
+        Callback<Result<super::ImportReport, Vec<String>>>,
+    ),
+    Undo(Callback<Result<(), String>>),
+    Redo(Callback<Result<(), String>>),
+    GetHistory(Callback<HistoryJson>),
+    DiffProfiles(
+        Option<u64>,
+        Option<u64>,
+        Callback<Result<Vec<ChangeJson>, String>>,
+    ), // (old, new) app ids, none for the main profile
+}
+
+impl ApiMessage {
+    /// Message changes the values of the settings, so the profile no longer matches its preset.
+    /// Trigger actions, thermal throttling and power source values aren't saved over the preset's values.
+    fn changes_values(&self) -> bool {
+        match self {
+            Self::Battery(x) => x.is_modify(),
+            Self::Cpu(x) => x.is_modify(),
+            Self::Gpu(x) => x.is_modify(),
+            Self::Fan(x) => x.is_modify(),
+            Self::SetThermalRules(_) | Self::LoadSystemSettings => true,
+            _ => false,
+        }
+    }
+
+    /// New values of a setter message, to send back once they have been checked
+    fn checked_values(&self) -> super::ApiParameterType {
+        match self {
+            Self::Battery(x) => x.checked_values(),
+            Self::Cpu(x) => x.checked_values(),
+            Self::Gpu(x) => x.checked_values(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Optional value as API parameter, empty when not set
+fn optional_value<T: Into<Primitive>>(value: Option<T>) -> Primitive {
+    value.map(|x| x.into()).unwrap_or(Primitive::Empty)
 }
 
 pub enum BatteryMessage {
@@ -44,7 +144,7 @@ pub enum BatteryMessage {
 }
 
 impl BatteryMessage {
-    fn process(self, settings: &mut dyn TBattery) -> bool {
+    fn process(self, settings: &mut dyn TBattery, triggers: &mut Triggers) -> bool {
         let dirty = self.is_modify();
         match self {
             Self::SetChargeRate(rate) => settings.charge_rate(rate),
@@ -55,15 +155,49 @@ impl BatteryMessage {
             Self::ReadChargeNow(cb) => cb(settings.read_charge_now()),
             Self::ReadChargeDesign(cb) => cb(settings.read_charge_design()),
             Self::ReadCurrentNow(cb) => cb(settings.read_current_now()),
-            Self::SetChargeLimit(limit) => settings.charge_limit(limit),
-            Self::GetChargeLimit(cb) => cb(settings.get_charge_limit()),
+            // the charge limit is done with the charge mode, so drivers without a limit can't do it
+            Self::SetChargeLimit(limit) => {
+                if settings.limits().charge_limit.is_some() {
+                    triggers.charge_limit(limit)
+                } else {
+                    log::warn!("Ignoring charge limit, since the battery driver has none");
+                }
+            }
+            Self::GetChargeLimit(cb) => cb(triggers.get_charge_limit()),
         }
         dirty
     }
 
     /// Message instructs the driver to modify settings
     fn is_modify(&self) -> bool {
-        matches!(self, Self::SetChargeRate(_) | Self::SetChargeMode(_))
+        matches!(
+            self,
+            Self::SetChargeRate(_) | Self::SetChargeMode(_) | Self::SetChargeLimit(_)
+        )
+    }
+
+    /// Check new values against the driver's limits, adjusting them in clamp mode
+    fn validate(&mut self, limits: &super::BatteryLimits, checks: &mut Checks) {
+        match self {
+            Self::SetChargeRate(rate) => checks.optional(
+                "Charge rate",
+                rate,
+                &limits.charge_current,
+                limits.charge_current_step,
+            ),
+            Self::SetChargeMode(Some(mode)) => {
+                checks.option("Charge mode", mode, &limits.charge_modes)
+            }
+            _ => {}
+        }
+    }
+
+    fn checked_values(&self) -> super::ApiParameterType {
+        match self {
+            Self::SetChargeRate(rate) => vec![optional_value(*rate)],
+            Self::SetChargeMode(mode) => vec![optional_value(mode.clone())],
+            _ => Vec::new(),
+        }
     }
 }
 
@@ -87,11 +221,13 @@ impl CpuMessage {
         // not to be confused with a CPU chip, which usually has multiple hardware threads (cpu cores/threads) in the chip
         match self {
             Self::SetCpuOnline(index, status) => {
-                settings.cpus().get_mut(index).map(|c| *c.online() = status);
+                if let Some(c) = settings.cpus().get_mut(index) {
+                    *c.online() = status;
+                }
             }
             Self::SetCpusOnline(cpus) => {
-                for i in 0..cpus.len() {
-                    settings.cpus().get_mut(i).map(|c| *c.online() = cpus[i]);
+                for (c, status) in settings.cpus().iter_mut().zip(cpus) {
+                    *c.online() = status;
                 }
             }
             Self::SetSmt(status, cb) => {
@@ -143,26 +279,23 @@ impl CpuMessage {
                 cb(result);
             }
             Self::SetClockLimits(index, clocks) => {
-                settings
-                    .cpus()
-                    .get_mut(index)
-                    .map(|c| c.clock_limits(clocks));
+                if let Some(c) = settings.cpus().get_mut(index) {
+                    c.clock_limits(clocks)
+                }
             }
             Self::GetClockLimits(index, cb) => {
-                settings
-                    .cpus()
-                    .get(index)
-                    .map(|c| cb(c.get_clock_limits().map(|x| x.to_owned())));
+                if let Some(c) = settings.cpus().get(index) {
+                    cb(c.get_clock_limits().map(|x| x.to_owned()))
+                }
             }
             Self::SetCpuGovernor(index, gov) => {
-                settings.cpus().get_mut(index).map(|c| c.governor(gov));
+                if let Some(c) = settings.cpus().get_mut(index) {
+                    c.governor(gov)
+                }
             }
             Self::SetCpusGovernor(govs) => {
-                for i in 0..govs.len() {
-                    settings
-                        .cpus()
-                        .get_mut(i)
-                        .map(|c| c.governor(govs[i].clone()));
+                for (c, gov) in settings.cpus().iter_mut().zip(govs) {
+                    c.governor(gov)
                 }
             }
             Self::GetCpusGovernor(cb) => {
@@ -188,6 +321,61 @@ impl CpuMessage {
                 | Self::SetCpusGovernor(_)
         )
     }
+
+    /// Check new values against the driver's limits, adjusting them in clamp mode
+    fn validate(&mut self, limits: &super::CpusLimits, checks: &mut Checks) {
+        match self {
+            Self::SetCpuOnline(index, _) => {
+                checks.index("CPU", &limits.cpus, *index);
+            }
+            Self::SetCpusOnline(cpus) => {
+                // only the last one can be missing
+                checks.index("CPU", &limits.cpus, cpus.len().saturating_sub(1));
+            }
+            Self::SetClockLimits(index, Some(clocks)) => {
+                if let Some(cpu) = checks.index("CPU", &limits.cpus, *index) {
+                    checks.min_max(
+                        &format!("CPU {} clock limits", index),
+                        &mut clocks.min,
+                        &mut clocks.max,
+                        (&cpu.clock_min_limits, &cpu.clock_max_limits),
+                        cpu.clock_step,
+                    );
+                }
+            }
+            Self::SetCpuGovernor(index, gov) => {
+                if let Some(cpu) = checks.index("CPU", &limits.cpus, *index) {
+                    let name = format!("CPU {} governor", index);
+                    checks.option(&name, gov, &cpu.governors);
+                }
+            }
+            Self::SetCpusGovernor(govs) => {
+                for (i, gov) in govs.iter().enumerate() {
+                    if let Some(cpu) = checks.index("CPU", &limits.cpus, i) {
+                        let name = format!("CPU {} governor", i);
+                        checks.option(&name, gov, &cpu.governors);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn checked_values(&self) -> super::ApiParameterType {
+        match self {
+            Self::SetCpuOnline(_, online) => vec![(*online).into()],
+            Self::SetCpusOnline(cpus) => cpus.iter().map(|x| (*x).into()).collect(),
+            Self::SetClockLimits(_, clocks) => vec![
+                optional_value(clocks.as_ref().map(|x| x.min)),
+                optional_value(clocks.as_ref().map(|x| x.max)),
+            ],
+            Self::SetCpuGovernor(_, governor) => vec![governor.clone().into()],
+            Self::SetCpusGovernor(governors) => {
+                governors.iter().map(|x| x.clone().into()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 pub enum GpuMessage {
@@ -219,6 +407,65 @@ impl GpuMessage {
             Self::SetPpt(_, _) | Self::SetClockLimits(_) | Self::SetSlowMemory(_)
         )
     }
+
+    /// Check new values against the driver's limits, adjusting them in clamp mode
+    fn validate(&mut self, limits: &super::GpuLimits, checks: &mut Checks) {
+        match self {
+            Self::SetPpt(fast, slow) => {
+                checks.optional(
+                    "GPU fast PPT",
+                    fast,
+                    &limits.fast_ppt_limits,
+                    limits.ppt_step,
+                );
+                checks.optional(
+                    "GPU slow PPT",
+                    slow,
+                    &limits.slow_ppt_limits,
+                    limits.ppt_step,
+                );
+            }
+            Self::SetClockLimits(Some(clocks)) => checks.min_max(
+                "GPU clock limits",
+                &mut clocks.min,
+                &mut clocks.max,
+                (&limits.clock_min_limits, &limits.clock_max_limits),
+                limits.clock_step,
+            ),
+            _ => {}
+        }
+    }
+
+    fn checked_values(&self) -> super::ApiParameterType {
+        match self {
+            Self::SetPpt(fast, slow) => vec![optional_value(*fast), optional_value(*slow)],
+            Self::SetClockLimits(clocks) => vec![
+                optional_value(clocks.as_ref().map(|x| x.min)),
+                optional_value(clocks.as_ref().map(|x| x.max)),
+            ],
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub enum FanMessage {
+    SetCurve(Option<FanCurve>),
+    GetCurve(Callback<crate::persist::FanJson>),
+}
+
+impl FanMessage {
+    fn process(self, settings: &mut dyn TFan) -> bool {
+        let dirty = self.is_modify();
+        match self {
+            Self::SetCurve(curve) => settings.curve(curve),
+            Self::GetCurve(cb) => cb(settings.json()),
+        }
+        dirty
+    }
+
+    fn is_modify(&self) -> bool {
+        matches!(self, Self::SetCurve(_))
+    }
 }
 
 pub enum GeneralMessage {
@@ -247,65 +494,218 @@ impl GeneralMessage {
     }
 }
 
+pub enum TelemetryMessage {
+    Record,
+    SetEnabled(bool),
+    GetEnabled(Callback<bool>),
+    Export(Option<u64>, Option<u64>, Callback<Result<String, String>>), // (start, end)
+}
+
+impl TelemetryMessage {
+    fn process(self, telemetry: &mut Telemetry, settings: &Settings) -> bool {
+        match self {
+            Self::Record => telemetry.record(settings),
+            Self::SetEnabled(val) => telemetry.set_enabled(val),
+            Self::GetEnabled(cb) => cb(telemetry.enabled()),
+            Self::Export(start, end, cb) => cb(telemetry.export_csv(start, end)),
+        }
+        false // telemetry is not part of the settings
+    }
+}
+
+pub enum WatchdogMessage {
+    Check,
+    SetEnabled(bool),
+    GetEnabled(Callback<bool>),
+    SetYield(String, bool), // (setting, yield to system)
+    GetYield(Callback<Vec<String>>),
+}
+
+impl WatchdogMessage {
+    fn process(self, watchdog: &mut Watchdog) -> bool {
+        match self {
+            Self::Check => {} // needs to re-apply settings, so ApiMessageHandler does this
+            Self::SetEnabled(val) => watchdog.set_enabled(val),
+            Self::GetEnabled(cb) => cb(watchdog.enabled()),
+            Self::SetYield(setting, val) => watchdog.set_yield(setting, val),
+            Self::GetYield(cb) => cb(watchdog.yield_to_system()),
+        }
+        false // watchdog is not part of the settings
+    }
+}
+
+pub enum PresetMessage {
+    /// Save the current settings as a new preset
+    Create(String, Callback<Result<(), String>>),
+    /// Replace a preset with the current settings
+    Update(String, Callback<Result<(), String>>),
+    /// Load the values of a preset, and make the current profile follow it
+    Apply(String, Callback<Result<(), String>>),
+    Delete(String, Callback<Result<(), String>>),
+    List(Callback<Vec<String>>),
+    /// Preset the current profile follows, if any
+    GetCurrent(Callback<Option<String>>),
+}
+
+/// Why the settings in use aren't the profile's own, so they aren't saved
+enum Overlay {
+    /// Preset a rule applied on top of the loaded profile
+    Preset(String),
+    /// Hardware was restored to how it was at startup, while the profile stays loaded
+    Stock,
+}
+
+impl std::fmt::Display for Overlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Preset(name) => write!(f, "preset `{}` of a rule is applied", name),
+            Self::Stock => write!(f, "stock settings were restored"),
+        }
+    }
+}
+
 pub struct ApiMessageHandler {
     intake: Receiver<ApiMessage>,
     on_empty: Vec<Callback<()>>,
-}
-
-fn print_errors(call_name: &str, errors: Vec<crate::settings::SettingError>) {
-    let mut err_list = String::new();
-    errors
-        .iter()
-        .for_each(|e| write!(err_list, "\t{},\n", e).unwrap_or(()));
-    log::error!("Settings {}() err:\n{}", call_name, err_list);
+    telemetry: Telemetry,
+    watchdog: Watchdog,
+    rules: Rules,
+    history: History,
+    /// Profiles are written by save_worker once they stop changing
+    saves: Sender<SaveMessage>,
+    /// What made the changes which are saved next
+    change_source: Option<ChangeSourceJson>,
+    /// Latest plugged in state from power events, if there were any
+    plugged_in: Option<bool>,
+    /// Preset or stock settings in use instead of the loaded profile's, until another profile is loaded.
+    /// Nothing is saved meanwhile, since the values aren't the profile's own.
+    overlay: Option<Overlay>,
+    apply_report: super::ApplyReport,
+    /// Settings file and values of the latest settings which applied, to go back to on rollback
+    committed: Option<(std::path::PathBuf, SettingsJson)>,
+    /// Latest errors of each setting and call
+    errors: BTreeMap<(String, String), Vec<super::SettingErrorInfo>>,
+    /// Whether out-of-range values are rejected or clamped
+    validation: ValidationMode,
+    events: EventBus,
+    /// Latest battery readings event, to only publish changes
+    battery_event: Option<Event>,
+    is_shutdown: bool,
 }
 
 impl ApiMessageHandler {
+    /// Log errors, and keep them as the latest errors of their settings for GENERAL_get_errors
+    fn print_errors(&mut self, call_name: &str, errors: Vec<crate::settings::SettingError>) {
+        let mut err_list = String::new();
+        errors
+            .iter()
+            .for_each(|e| writeln!(err_list, "\t{},", e).unwrap_or(()));
+        log::error!("Settings {}() err:\n{}", call_name, err_list);
+        let mut latest: BTreeMap<(String, String), Vec<super::SettingErrorInfo>> = BTreeMap::new();
+        for e in &errors {
+            let info = super::SettingErrorInfo::new(call_name, e);
+            self.events.publish(Event::Error(info.clone()));
+            latest
+                .entry((info.setting.clone(), info.source.clone()))
+                .or_default()
+                .push(info);
+        }
+        self.errors.extend(latest);
+    }
+
+    /// Handle for waiting on events from this handler
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    fn publish_applied(&self) {
+        self.events.publish(Event::Applied {
+            outcome: self.apply_report.outcome.clone(),
+            drifted: self.apply_report.drifted,
+        });
+    }
+
+    fn publish_profile_loaded(&self, settings: &Settings) {
+        self.events.publish(Event::ProfileLoaded {
+            name: settings.general.get_name().to_owned(),
+            path: settings.general.get_path().display().to_string(),
+        });
+    }
+
+    fn publish_battery(&mut self, settings: &Settings) {
+        let event = Event::Battery {
+            charge_now: settings.battery.read_charge_now(),
+            charge_full: settings.battery.read_charge_full(),
+            current_now: settings.battery.read_current_now(),
+        };
+        // current changes all the time, so only whether it's (dis)charging counts as a change
+        let state = |event: &Event| match event {
+            Event::Battery {
+                charge_now,
+                charge_full,
+                current_now,
+            } => Some((
+                *charge_now,
+                *charge_full,
+                current_now.and_then(|x| x.partial_cmp(&0.0)),
+            )),
+            _ => None,
+        };
+        if self.battery_event.as_ref().map(state) != Some(state(&event)) {
+            self.events.publish(event.clone());
+            self.battery_event = Some(event);
+        }
+    }
+
     pub fn process_forever(&mut self, settings: &mut Settings) {
         //let mut dirty_echo = true; // set everything twice, to make sure PowerTools wins on race conditions
+        self.history
+            .observe(settings.general.get_path(), settings.json(), None);
+        self.committed = Some((settings.general.get_path().to_owned(), settings.json()));
         while let Ok(msg) = self.intake.recv() {
+            self.change_source = None;
             let mut dirty = self.process(settings, msg);
             while let Ok(msg) = self.intake.try_recv() {
                 dirty |= self.process(settings, msg);
             }
+            if self.is_shutdown && dirty {
+                // don't apply anything on top of the restored startup values
+                log::debug!("Ignoring message(s) after shutdown");
+                dirty = false;
+            }
             if dirty /*|| dirty_echo */ {
                 //dirty_echo = dirty; // echo only once
 
                 // run on_set
-                if let Err(e) = settings.on_set() {
-                    print_errors("on_set", e);
-                }
+                self.watchdog.reset();
+                let outcome = self.apply(settings);
                 // do callbacks
                 for func in self.on_empty.drain(..) {
                     func(());
                 }
+                if outcome != Outcome::Committed {
+                    // the rejected values were replaced by the ones from before, which are already saved
+                    continue;
+                }
+                if let Some(overlay) = &self.overlay {
+                    log::debug!("Not saving, {}", overlay);
+                    continue;
+                }
                 // save
                 log::debug!("api_worker is saving...");
                 let is_persistent = *settings.general.persistent();
-                let save_path =
-                    crate::utility::settings_dir().join(settings.general.get_path().clone());
-                if is_persistent {
-                    let settings_clone = settings.json();
-                    let save_json: SettingsJson = settings_clone.into();
-                    unwrap_maybe_fatal(save_json.save(&save_path), "Failed to save settings");
-                    log::debug!("Saved settings to {}", save_path.display());
-                    if let Err(e) = crate::utility::chown_settings_dir() {
-                        log::error!("Failed to change config dir permissions: {}", e);
-                    }
-                } else {
-                    if save_path.exists() {
-                        if let Err(e) = std::fs::remove_file(&save_path) {
-                            log::warn!(
-                                "Failed to delete persistent settings file {}: {}",
-                                save_path.display(),
-                                e
-                            );
-                        } else {
-                            log::debug!("Deleted persistent settings file {}", save_path.display());
-                        }
-                    } else {
-                        log::debug!("Ignored save request for non-persistent settings");
-                    }
+                let save_path = crate::utility::settings_dir().join(settings.general.get_path());
+                let save_json = settings.json();
+                // only changes to saved profiles are recorded, since other profiles start over when loaded
+                self.history.observe(
+                    settings.general.get_path(),
+                    save_json.clone(),
+                    self.change_source.filter(|_| is_persistent),
+                );
+                let message =
+                    SaveMessage::Save(save_path, is_persistent.then(|| Box::new(save_json)));
+                if self.saves.send(message).is_err() {
+                    log::error!("Failed to save settings, save_worker is gone");
                 }
             } else {
                 log::debug!("Skipping callbacks for non-modify handled message(s)");
@@ -313,84 +713,538 @@ impl ApiMessageHandler {
         }
     }
 
+    /// Apply settings and read them back, applying them once more if something else overrode them.
+    /// When applying fails as a whole, the settings go back to the latest ones which applied.
+    fn apply(&mut self, settings: &mut Settings) -> Outcome {
+        self.apply_only(settings, &SettingVariant::APPLIED)
+    }
+
+    /// Like apply(), for only some of the settings (e.g. the ones another process changed)
+    fn apply_only(&mut self, settings: &mut Settings, variants: &[SettingVariant]) -> Outcome {
+        // these settings are written again, so older errors of writing them no longer apply;
+        // errors of anything else (e.g. on_power_event) stay until the same call has errors again
+        self.errors.retain(|(setting, call), _| {
+            !call.starts_with("on_set") || !variants.iter().any(|v| v.to_string() == *setting)
+        });
+        let (outcome, errors) = settings.apply_only(variants);
+        if !errors.is_empty() {
+            self.print_errors("on_set", errors);
+        }
+        if outcome != Outcome::Committed {
+            // the hardware has the old values, so they are supposed to differ from the settings
+            log::error!("Applying settings failed as a whole ({})", outcome);
+            let mut report = super::ApplyReport::new(Vec::new());
+            report.outcome = outcome.to_string();
+            self.apply_report = report;
+            self.publish_applied();
+            self.restore_committed(settings);
+            return outcome;
+        }
+        let mut report = super::ApplyReport::new(settings.verify());
+        if report.has_drift() {
+            log::warn!(
+                "{} value(s) did not apply, applying settings again",
+                report.drifted
+            );
+            let (_, errors) = settings.apply_only(&drifted_variants(&report.values));
+            if !errors.is_empty() {
+                self.print_errors("on_set([drift])", errors);
+            }
+            report = super::ApplyReport::new(settings.verify());
+            report.reapplied = true;
+            if report.has_drift() {
+                for value in report.values.iter().filter(|v| v.is_drifted()) {
+                    log::error!(
+                        "{} value `{}` is `{}` instead of `{}`",
+                        value.setting,
+                        value.name,
+                        value.actual.as_deref().unwrap_or_default(),
+                        value.requested
+                    );
+                }
+            }
+        }
+        self.apply_report = report;
+        self.publish_applied();
+        self.committed = Some((settings.general.get_path().to_owned(), settings.json()));
+        outcome
+    }
+
+    /// Replace the settings with the latest ones which applied, which the hardware was rolled back to
+    fn restore_committed(&mut self, settings: &mut Settings) {
+        let (path, json) = match self.committed.clone() {
+            Some(x) => x,
+            None => return,
+        };
+        log::info!(
+            "Going back to settings `{}` ({})",
+            json.name,
+            path.display()
+        );
+        settings.general.path(path);
+        if let Err(e) = settings.load_json(json) {
+            self.print_errors("restore", vec![e]);
+        }
+    }
+
+    /// Restore startup hardware values; settings are no longer applied after this
+    pub fn shutdown(&mut self, settings: &mut Settings) {
+        self.is_shutdown = true;
+        crate::save_worker::flush(&self.saves);
+        if let Err(e) = settings.on_shutdown() {
+            self.print_errors("on_shutdown", e);
+        }
+    }
+
+    /// Check the new values of a setter message against the limits of the current drivers
+    fn validate(
+        &self,
+        settings: &Settings,
+        message: &mut ApiMessage,
+    ) -> Result<(), Vec<SettingError>> {
+        let checks = match message {
+            ApiMessage::Battery(x) if x.is_modify() => {
+                let mut checks = Checks::new(SettingVariant::Battery, self.validation);
+                x.validate(&settings.battery.limits(), &mut checks);
+                checks
+            }
+            ApiMessage::Cpu(x) if x.is_modify() => {
+                let mut checks = Checks::new(SettingVariant::Cpu, self.validation);
+                x.validate(&settings.cpus.limits(), &mut checks);
+                checks
+            }
+            ApiMessage::Gpu(x) if x.is_modify() => {
+                let mut checks = Checks::new(SettingVariant::Gpu, self.validation);
+                x.validate(&settings.gpu.limits(), &mut checks);
+                checks
+            }
+            _ => return Ok(()),
+        };
+        checks.finish()
+    }
+
+    /// Load the profile of app `id`, or the main profile without one
+    fn load_profile(&mut self, settings: &mut Settings, id: Option<u64>, name: String) {
+        // the profile may still be waiting to be written
+        crate::save_worker::flush(&self.saves);
+        self.overlay = None;
+        let result = match id {
+            Some(id) => settings.load_file(format!("{}.json", id).into(), name, false),
+            None => settings.load_file(
+                crate::consts::DEFAULT_SETTINGS_FILE.into(),
+                crate::consts::DEFAULT_SETTINGS_NAME.to_owned(),
+                true,
+            ),
+        };
+        match result {
+            Ok(success) => log::info!("Loaded settings file? {}", success),
+            Err(e) => log::warn!("Load file err: {}", e),
+        }
+        self.publish_profile_loaded(settings);
+    }
+
+    /// Load the values of preset `name`, and make the current profile follow it
+    fn apply_preset(&mut self, settings: &mut Settings, name: &str) -> Result<(), String> {
+        Self::load_preset(settings, name, self.validation, true)?;
+        self.overlay = None;
+        log::info!("Profile now follows preset `{}`", name);
+        Ok(())
+    }
+
+    /// Load the values of preset `name` on top of the current profile, without saving them
+    fn overlay_preset(&mut self, settings: &mut Settings, name: &str) -> Result<(), String> {
+        Self::load_preset(settings, name, self.validation, false)?;
+        self.overlay = Some(Overlay::Preset(name.to_owned()));
+        log::info!(
+            "Preset `{}` applies on top of profile `{}` until another profile is loaded",
+            name,
+            settings.general.get_name()
+        );
+        Ok(())
+    }
+
+    /// Replace the values of the current profile with the ones of preset `name`.
+    /// With `follow`, the profile follows the preset from now on.
+    fn load_preset(
+        settings: &mut Settings,
+        name: &str,
+        validation: ValidationMode,
+        follow: bool,
+    ) -> Result<(), String> {
+        let mut json = settings.json();
+        if follow {
+            json.persistent = true;
+        }
+        json.preset = Some(name.to_owned());
+        let mut json = presets::resolve(json);
+        if json.preset.is_none() {
+            return presets::open(name).map(|_| ()).map_err(|e| e.msg);
+        }
+        if !follow {
+            json.preset = None;
+        }
+        crate::settings::validate::settings_json(&mut json, &settings.limits(), validation)
+            .and_then(|_| settings.load_json(json).map_err(|e| vec![e]))
+            .map_err(|errors| {
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+    }
+
+    /// Load the target of the rule which starts matching, if any; returns true if it did
+    fn check_rules(&mut self, settings: &mut Settings) -> bool {
+        let conditions = Conditions::read(self.plugged_in, settings.battery.as_ref());
+        let rule = match self.rules.evaluate(&conditions) {
+            Some(rule) => rule.clone(),
+            None => return false,
+        };
+        log::info!("Rule `{}` matches, loading {:?}", rule.name, rule.target);
+        self.events.publish(Event::RuleMatched {
+            name: rule.name.clone(),
+        });
+        match rule.target {
+            RuleTargetJson::Profile { id, name } => {
+                self.load_profile(settings, id, name);
+                self.change_source = Some(ChangeSourceJson::Rule);
+                true
+            }
+            RuleTargetJson::Preset(name) => match self.overlay_preset(settings, &name) {
+                Ok(()) => {
+                    self.change_source = Some(ChangeSourceJson::Rule);
+                    true
+                }
+                Err(e) => {
+                    log::error!("Rule `{}` failed to apply preset: {}", rule.name, e);
+                    false
+                }
+            },
+        }
+    }
+
+    /// Save a shared profile as the profile of its app, and load it if that's the current profile.
+    /// An existing profile of the app is only replaced with `overwrite`.
+    /// Returns the result and whether settings were modified.
+    fn import_profile(
+        settings: &mut Settings,
+        export: crate::persist::ProfileExportJson,
+        overwrite: bool,
+    ) -> (Result<super::ImportReport, Vec<String>>, bool) {
+        let filename = profile_file(export.app_id);
+        let path = crate::utility::settings_dir().join(&filename);
+        if !overwrite && path.exists() {
+            return (
+                Err(vec![format!(
+                    "There is already a profile at {}, import with overwrite to replace it",
+                    path.display()
+                )]),
+                false,
+            );
+        }
+        let (json, mut report) = match crate::settings::sharing::import(export, &settings.limits())
+        {
+            Ok(x) => x,
+            Err(errors) => return (Err(errors.iter().map(|e| e.to_string()).collect()), false),
+        };
+        report.path = filename.display().to_string();
+        if let Err(e) = json.save(&path) {
+            return (
+                Err(vec![format!("Failed to save imported profile: {}", e)]),
+                false,
+            );
+        }
+        log::info!(
+            "Imported profile `{}` to {} with {} adjustments",
+            json.name,
+            report.path,
+            report.adjusted.len()
+        );
+        if settings.general.get_path() != filename.as_path() {
+            return (Ok(report), false);
+        }
+        match settings.load_json(json) {
+            Ok(()) => (Ok(report), true),
+            Err(e) => (Err(vec![e.to_string()]), false),
+        }
+    }
+
+    /// Undo or redo one change of the current profile, checking it against the current limits
+    fn step_history(&mut self, settings: &mut Settings, undo: bool) -> Result<(), String> {
+        let limits = settings.limits();
+        let validation = self.validation;
+        let current = settings.json();
+        self.history.step(current, undo, |mut json| {
+            crate::settings::validate::settings_json(&mut json, &limits, validation)
+                .and_then(|_| settings.load_json(json).map_err(|e| vec![e]))
+                .map_err(|errors| {
+                    errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+        })?;
+        // the profile has its own values again
+        self.overlay = None;
+        Ok(())
+    }
+
+    fn observe_power(&mut self, mode: &PowerMode) {
+        match mode {
+            PowerMode::PluggedIn => self.plugged_in = Some(true),
+            PowerMode::PluggedOut => self.plugged_in = Some(false),
+            PowerMode::BatteryCharge(_) => {}
+        }
+    }
+
+    fn process_preset(&mut self, settings: &mut Settings, message: PresetMessage) -> bool {
+        let to_msg = |result: Result<(), SettingError>| result.map_err(|e| e.msg);
+        match message {
+            PresetMessage::Create(name, cb) => {
+                if presets::exists(&name) {
+                    cb(Err(format!("There is already a preset named `{}`", name)));
+                } else {
+                    cb(to_msg(presets::save(&name, settings.json())));
+                }
+                false
+            }
+            PresetMessage::Update(name, cb) => {
+                cb(to_msg(
+                    presets::open(&name).and_then(|_| presets::save(&name, settings.json())),
+                ));
+                false
+            }
+            PresetMessage::Apply(name, cb) => {
+                let result = self.apply_preset(settings, &name);
+                let is_ok = result.is_ok();
+                cb(result);
+                is_ok
+            }
+            PresetMessage::Delete(name, cb) => {
+                let result = presets::delete(&name);
+                // the profile keeps the values it has, and saves them as its own
+                let detached = result.is_ok() && settings.preset.as_deref() == Some(&name);
+                if detached {
+                    settings.preset = None;
+                }
+                cb(to_msg(result));
+                detached
+            }
+            PresetMessage::List(cb) => {
+                cb(presets::list());
+                false
+            }
+            PresetMessage::GetCurrent(cb) => {
+                cb(settings.preset.clone());
+                false
+            }
+        }
+    }
+
     pub fn process(&mut self, settings: &mut Settings, message: ApiMessage) -> bool {
+        let source = match message {
+            ApiMessage::OnResume
+            | ApiMessage::OnPluggedIn
+            | ApiMessage::OnUnplugged
+            | ApiMessage::OnChargeChange(_)
+            | ApiMessage::PowerVibeCheck
+            | ApiMessage::ThermalCheck
+            | ApiMessage::Watchdog(_) => ChangeSourceJson::Event,
+            _ => ChangeSourceJson::Api,
+        };
+        match self.process_checked(settings, message) {
+            Ok(dirty) => {
+                if dirty {
+                    // a batch is recorded as one change, which is the user's if any message of it is
+                    self.change_source = match (self.change_source, source) {
+                        (_, ChangeSourceJson::Api) => Some(ChangeSourceJson::Api),
+                        (Some(current), _) => Some(current),
+                        (None, source) => Some(source),
+                    };
+                }
+                dirty
+            }
+            Err(e) => {
+                self.print_errors("validate", e);
+                false
+            }
+        }
+    }
+
+    fn process_checked(
+        &mut self,
+        settings: &mut Settings,
+        mut message: ApiMessage,
+    ) -> Result<bool, Vec<SettingError>> {
+        if self.is_shutdown {
+            // already restored, so only let other shutdown requests know that
+            if let ApiMessage::Shutdown(cb) = message {
+                cb(());
+            }
+            return Ok(false);
+        }
+        self.validate(settings, &mut message)?;
+        Ok(self.process_validated(settings, message))
+    }
+
+    /// Like process_checked(), for a message whose new values were already checked
+    fn process_validated(&mut self, settings: &mut Settings, message: ApiMessage) -> bool {
+        if message.changes_values() {
+            if let Some(preset) = settings.preset.take() {
+                log::info!("Profile no longer follows preset `{}`", preset);
+            }
+        }
         match message {
-            ApiMessage::Battery(x) => x.process(settings.battery.as_mut()),
+            ApiMessage::Battery(x) => x.process(settings.battery.as_mut(), &mut settings.triggers),
             ApiMessage::Cpu(x) => x.process(settings.cpus.as_mut()),
             ApiMessage::Gpu(x) => x.process(settings.gpu.as_mut()),
+            ApiMessage::Fan(x) => x.process(settings.fan.as_mut()),
             ApiMessage::General(x) => x.process(settings.general.as_mut()),
+            ApiMessage::Telemetry(x) => x.process(&mut self.telemetry, settings),
+            ApiMessage::Watchdog(WatchdogMessage::Check) => {
+                if self.apply_report.outcome != Outcome::Committed.to_string() {
+                    // re-applying would only fail and roll back again
+                    return false;
+                }
+                let drifted = self
+                    .watchdog
+                    .check(settings, crate::periodic_worker::WATCHDOG_PERIOD);
+                if !drifted.is_empty() {
+                    // settings which stayed put (or yield to the system) are left alone
+                    self.apply_only(settings, &drifted_variants(&drifted));
+                    self.watchdog.log_winners(&drifted, &self.apply_report);
+                }
+                false // nothing changed in the settings themselves
+            }
+            ApiMessage::Watchdog(x) => x.process(&mut self.watchdog),
+            ApiMessage::Preset(x) => self.process_preset(settings, x),
             ApiMessage::OnResume => {
+                self.events.publish(Event::Resume);
                 if let Err(e) = settings.on_resume() {
-                    print_errors("on_resume", e);
+                    self.print_errors("on_resume", e);
                 }
-                false
+                settings.fire_triggers(TriggerEvent::Resume)
             }
             ApiMessage::OnPluggedIn => {
+                self.events.publish(Event::PluggedIn);
+                self.observe_power(&PowerMode::PluggedIn);
                 if let Err(e) = settings.on_power_event(PowerMode::PluggedIn) {
-                    print_errors("on_power_event(PluggedIn)", e);
+                    self.print_errors("on_power_event(PluggedIn)", e);
                 }
+                settings.fire_triggers(TriggerEvent::Power(PowerMode::PluggedIn));
+                self.check_rules(settings);
                 true
             }
             ApiMessage::OnUnplugged => {
+                self.events.publish(Event::PluggedOut);
+                self.observe_power(&PowerMode::PluggedOut);
                 if let Err(e) = settings.on_power_event(PowerMode::PluggedOut) {
-                    print_errors("on_power_event(PluggedOut)", e);
+                    self.print_errors("on_power_event(PluggedOut)", e);
                 }
+                settings.fire_triggers(TriggerEvent::Power(PowerMode::PluggedOut));
+                self.check_rules(settings);
                 true
             }
             ApiMessage::OnChargeChange(charge) => {
+                self.events.publish(Event::BatteryCharge { charge });
                 if let Err(e) = settings.on_power_event(PowerMode::BatteryCharge(charge)) {
-                    print_errors(&format!("on_power_event(BatteryCharge={:#0.5})", charge), e);
+                    self.print_errors(&format!("on_power_event(BatteryCharge={:#0.5})", charge), e);
                 }
+                settings.fire_triggers(TriggerEvent::Power(PowerMode::BatteryCharge(charge)));
+                self.check_rules(settings);
                 true
             }
             ApiMessage::PowerVibeCheck => {
+                let mut fired = false;
                 match settings.battery.check_power() {
-                    Err(e) => print_errors("check_power()", e),
+                    Err(e) => self.print_errors("check_power()", e),
                     Ok(events) => {
                         for ev in events {
+                            self.events.publish(Event::from(&ev));
+                            self.observe_power(&ev);
                             let name = format!("on_power_event([vibe]{:?})", ev);
+                            let source = settings.power_source();
                             if let Err(e) = settings.on_power_event(ev) {
-                                print_errors(&name, e);
+                                self.print_errors(&name, e);
                             }
+                            // switching to the values of another power source isn't applied by on_power_event()
+                            fired |= settings.power_source() != source;
+                            fired |= settings.fire_triggers(TriggerEvent::Power(ev));
                         }
                     }
                 }
-                false // on_power_event() should apply everything
+                self.publish_battery(settings);
+                // on_power_event() should apply everything, except changed values and a newly loaded profile
+                self.check_rules(settings) || fired
+            }
+            ApiMessage::ThermalCheck => {
+                if let Err(e) = settings.fan.update() {
+                    self.print_errors("fan.update()", e);
+                }
+                if settings
+                    .thermal
+                    .check(settings.gpu.as_mut(), settings.cpus.as_mut())
+                {
+                    if let Err(e) = settings.on_set() {
+                        self.print_errors("on_set([thermal])", e);
+                    }
+                }
+                false // throttling is temporary, so don't save
+            }
+            ApiMessage::SetThermalRules(rules) => {
+                // undo throttling from the old rules before replacing them
+                settings
+                    .thermal
+                    .restore(settings.gpu.as_mut(), settings.cpus.as_mut());
+                settings.thermal = crate::settings::Thermal::from_json(rules);
+                true
+            }
+            ApiMessage::GetThermalRules(cb) => {
+                cb(settings.thermal.json());
+                false
             }
             ApiMessage::WaitForEmptyQueue(callback) => {
                 self.on_empty.push(callback);
                 false
             }
             ApiMessage::LoadSettings(id, name) => {
-                let path = format!("{}.json", id);
-                match settings.load_file(path.into(), name, false) {
-                    Ok(success) => log::info!("Loaded settings file? {}", success),
-                    Err(e) => log::warn!("Load file err: {}", e),
-                }
+                self.load_profile(settings, Some(id), name);
                 true
             }
             ApiMessage::LoadMainSettings => {
-                match settings.load_file(
-                    crate::consts::DEFAULT_SETTINGS_FILE.into(),
-                    crate::consts::DEFAULT_SETTINGS_NAME.to_owned(),
-                    true,
-                ) {
-                    Ok(success) => log::info!("Loaded main settings file? {}", success),
-                    Err(e) => log::warn!("Load file err: {}", e),
-                }
+                self.load_profile(settings, None, String::new());
                 true
             }
             ApiMessage::LoadSystemSettings => {
+                self.overlay = None;
                 settings.load_system_default(settings.general.get_name().to_owned());
+                self.publish_profile_loaded(settings);
                 true
             }
             ApiMessage::GetLimits(cb) => {
-                cb(super::SettingsLimits {
-                    battery: settings.battery.limits(),
-                    cpu: settings.cpus.limits(),
-                    gpu: settings.gpu.limits(),
-                    general: settings.general.limits(),
-                });
+                cb(settings.limits());
+                false
+            }
+            ApiMessage::SetSettingsJson(json, cb) => {
+                let mut json = presets::resolve(*json);
+                let result = crate::settings::validate::settings_json(
+                    &mut json,
+                    &settings.limits(),
+                    self.validation,
+                )
+                .and_then(|_| settings.load_json(json).map_err(|e| vec![e]));
+                let is_ok = result.is_ok();
+                if is_ok {
+                    self.overlay = None;
+                    self.publish_profile_loaded(settings);
+                }
+                cb(result.map_err(|errors| errors.iter().map(|e| e.to_string()).collect()));
+                is_ok // everything is applied with one on_set()
+            }
+            ApiMessage::GetSettingsJson(cb) => {
+                cb(settings.json());
                 false
             }
             ApiMessage::GetProvider(name, cb) => {
@@ -398,21 +1252,209 @@ impl ApiMessageHandler {
                     "battery" => settings.battery.provider(),
                     "cpu" | "cpus" => settings.cpus.provider(),
                     "gpu" => settings.gpu.provider(),
+                    "fan" => settings.fan.provider(),
                     _ => settings.general.provider(),
                 });
                 false
             }
+            ApiMessage::Shutdown(cb) => {
+                self.shutdown(settings);
+                cb(());
+                false
+            }
+            ApiMessage::GetApplyReport(cb) => {
+                cb(self.apply_report.clone());
+                false
+            }
+            ApiMessage::GetErrors(cb) => {
+                cb(self.errors.values().flatten().cloned().collect());
+                false
+            }
+            ApiMessage::RestoreStock => {
+                if let Err(e) = settings.on_shutdown() {
+                    self.print_errors("on_shutdown([restore stock])", e);
+                }
+                // hardware is already back to stock, so don't re-apply anything,
+                // and keep the profile as it is on disk
+                self.overlay = Some(Overlay::Stock);
+                false
+            }
+            ApiMessage::Checked(mut message, cb) => match self.validate(settings, &mut message) {
+                Ok(()) => {
+                    // the values which are set, which differ from the requested ones when clamped
+                    let values = message.checked_values();
+                    let dirty = self.process_validated(settings, *message);
+                    cb(Ok(values));
+                    dirty
+                }
+                Err(e) => {
+                    cb(Err(e.iter().map(|e| e.to_string()).collect()));
+                    self.print_errors("validate", e);
+                    false
+                }
+            },
+            ApiMessage::SetValidationMode(mode) => {
+                if self.validation != mode {
+                    self.validation = mode;
+                    crate::settings::validate::save_mode(mode);
+                }
+                false
+            }
+            ApiMessage::GetValidationMode(cb) => {
+                cb(self.validation);
+                false
+            }
+            ApiMessage::GetPowerSource(cb) => {
+                cb(settings.power_source());
+                false
+            }
+            ApiMessage::SetRules(rules, cb) => {
+                let result = self.rules.set(rules);
+                let is_ok = result.is_ok();
+                cb(result);
+                // switch right away if a new rule matches
+                is_ok && self.check_rules(settings)
+            }
+            ApiMessage::GetRules(cb) => {
+                cb(self.rules.json());
+                false
+            }
+            ApiMessage::Undo(cb) => {
+                let result = self.step_history(settings, true);
+                let is_ok = result.is_ok();
+                cb(result);
+                is_ok
+            }
+            ApiMessage::Redo(cb) => {
+                let result = self.step_history(settings, false);
+                let is_ok = result.is_ok();
+                cb(result);
+                is_ok
+            }
+            ApiMessage::GetHistory(cb) => {
+                cb(self.history.json());
+                false
+            }
+            ApiMessage::DiffProfiles(old, new, cb) => {
+                crate::save_worker::flush(&self.saves);
+                let open = |id| {
+                    let path = crate::utility::settings_dir().join(profile_file(id));
+                    SettingsJson::open(&path).map_err(|e| format!("{}: {}", path.display(), e))
+                };
+                cb(open(old).and_then(|old| Ok(crate::settings::history::diff(&old, &open(new)?))));
+                false
+            }
+            ApiMessage::ExportProfile(cb) => {
+                cb(crate::settings::sharing::export(settings));
+                false
+            }
+            ApiMessage::ImportProfile(export, overwrite, cb) => {
+                // a pending save of the same profile would overwrite the import
+                crate::save_worker::flush(&self.saves);
+                let (result, dirty) = Self::import_profile(settings, *export, overwrite);
+                if dirty {
+                    // the imported profile replaced the current one
+                    self.overlay = None;
+                }
+                cb(result);
+                dirty
+            }
         }
     }
 
-    pub fn new() -> (Self, Sender<ApiMessage>) {
+    pub fn new(saves: Sender<SaveMessage>) -> (Self, Sender<ApiMessage>) {
         let (tx, rx) = mpsc::channel();
         (
             Self {
                 intake: rx,
                 on_empty: Vec::with_capacity(4),
+                telemetry: Telemetry::load(),
+                watchdog: Watchdog::load(),
+                rules: Rules::load(),
+                history: History::new(saves.clone()),
+                saves,
+                change_source: None,
+                plugged_in: None,
+                overlay: None,
+                apply_report: super::ApplyReport::default(),
+                committed: None,
+                errors: BTreeMap::new(),
+                validation: crate::settings::validate::load_mode(),
+                events: EventBus::default(),
+                battery_event: None,
+                is_shutdown: false,
             },
             tx,
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Settings with drivers which don't touch the hardware
+    fn unknown_settings(path: &std::path::Path) -> Settings {
+        let mut settings = Settings::system_default(path.to_owned(), "Test".to_owned());
+        settings.general = Box::new(crate::settings::General {
+            persistent: true,
+            path: path.to_owned(),
+            name: "Test".to_owned(),
+            driver: crate::persist::DriverJson::Unknown,
+        });
+        settings.cpus = Box::new(crate::settings::unknown::Cpus {
+            cpus: Vec::new(),
+            smt: false,
+            smt_capable: false,
+        });
+        settings.gpu = Box::new(crate::settings::unknown::Gpu::system_default());
+        settings.battery = Box::new(crate::settings::unknown::Battery);
+        settings.fan = Box::new(crate::settings::unknown::Fan);
+        settings
+    }
+
+    #[test]
+    fn restore_stock_keeps_profile_test() {
+        let path = std::env::temp_dir().join(format!(
+            "powertools_handler_test_{}.json",
+            std::process::id()
+        ));
+        let mut settings = unknown_settings(&path);
+        settings.json().save(&path).unwrap();
+
+        let (worker, saves) = crate::save_worker::spawn();
+        let (mut handler, sender) = ApiMessageHandler::new(saves);
+        sender.send(ApiMessage::RestoreStock).unwrap();
+        sender
+            .send(ApiMessage::Gpu(GpuMessage::SetSlowMemory(true)))
+            .unwrap();
+        drop(sender);
+        handler.process_forever(&mut settings);
+        drop(handler);
+        worker.join().unwrap();
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn apply_keeps_other_errors_test() {
+        let mut settings = unknown_settings(std::path::Path::new("test.json"));
+        let (saves, _) = mpsc::channel();
+        let (mut handler, _sender) = ApiMessageHandler::new(saves);
+        let error = |msg: &str| crate::settings::SettingError {
+            msg: msg.to_owned(),
+            setting: SettingVariant::Battery,
+            kind: crate::settings::ErrorKind::Io,
+        };
+        handler.print_errors("on_power_event(PluggedIn)", vec![error("plug")]);
+        handler.print_errors("on_set", vec![error("write")]);
+        handler.apply_only(&mut settings, &[SettingVariant::Battery]);
+        let msgs: Vec<String> = handler
+            .errors
+            .values()
+            .flatten()
+            .map(|e| e.msg.clone())
+            .collect();
+        assert_eq!(msgs, vec!["plug"]);
+    }
+}
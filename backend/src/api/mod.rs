@@ -2,9 +2,15 @@ mod api_types;
 mod async_utils;
 pub mod battery;
 pub mod cpu;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod fan;
 pub mod general;
 pub mod gpu;
 pub mod handler;
+#[cfg(feature = "dbus")]
+pub mod power_profiles;
+pub mod preset;
 mod utility;
 
 pub(super) type ApiParameterType = Vec<usdpl_back::core::serdes::Primitive>;
@@ -0,0 +1,415 @@
+//! power-profiles-daemon compatible D-Bus interface, so the desktop's power profile slider
+//! loads PowerTools profiles instead of fighting over the same hardware
+
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use zbus::fdo;
+use zbus::names::BusName;
+use zbus::object_server::SignalContext;
+use zbus::zvariant::{OwnedValue, Str, Value};
+
+use super::handler::ApiMessage;
+use crate::persist::{PowerProfilesJson, ProfileTargetJson};
+
+pub const BUS_NAME: &str = "net.hadess.PowerProfiles";
+pub const OBJECT_PATH: &str = "/net/hadess/PowerProfiles";
+/// Newer desktops use power-profiles-daemon's new name, with the same interface
+pub const UPOWER_BUS_NAME: &str = "org.freedesktop.UPower.PowerProfiles";
+pub const UPOWER_OBJECT_PATH: &str = "/org/freedesktop/UPower/PowerProfiles";
+
+const PROFILES: [&str; 3] = ["power-saver", "balanced", "performance"];
+const DRIVER: &str = "powertools";
+
+fn config_path() -> std::path::PathBuf {
+    crate::utility::settings_dir().join(crate::consts::POWER_PROFILES_CONFIG_FILE)
+}
+
+fn target_path(target: &ProfileTargetJson) -> String {
+    match target.id {
+        Some(id) => format!("{}.json", id),
+        None => crate::consts::DEFAULT_SETTINGS_FILE.to_owned(),
+    }
+}
+
+fn dict(entries: &[(&str, &str)]) -> HashMap<String, OwnedValue> {
+    entries
+        .iter()
+        .map(|(k, v)| (k.to_string(), Str::from(*v).to_owned().into()))
+        .collect()
+}
+
+struct Hold {
+    cookie: u32,
+    profile: String,
+    reason: String,
+    application_id: String,
+}
+
+#[derive(Default)]
+struct ProfilesState {
+    config: PowerProfilesJson,
+    holds: Vec<Hold>,
+    next_cookie: u32,
+}
+
+impl ProfilesState {
+    /// Profile in effect; a power-saver hold wins over a performance one, like power-profiles-daemon
+    fn effective(&self) -> &str {
+        if self.holds.iter().any(|h| h.profile == "power-saver") {
+            "power-saver"
+        } else if let Some(hold) = self.holds.last() {
+            &hold.profile
+        } else {
+            &self.config.active
+        }
+    }
+
+    fn target(&self, profile: &str) -> Option<&ProfileTargetJson> {
+        match profile {
+            "power-saver" => self.config.power_saver.as_ref(),
+            "balanced" => self.config.balanced.as_ref(),
+            "performance" => self.config.performance.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Choose a profile, which releases all holds; returns the released cookies
+    fn select(&mut self, profile: String) -> Vec<u32> {
+        self.config.active = profile;
+        self.holds.drain(..).map(|h| h.cookie).collect()
+    }
+
+    fn hold(&mut self, profile: String, reason: String, application_id: String) -> u32 {
+        self.next_cookie += 1;
+        self.holds.push(Hold {
+            cookie: self.next_cookie,
+            profile,
+            reason,
+            application_id,
+        });
+        self.next_cookie
+    }
+
+    fn release(&mut self, cookie: u32) -> bool {
+        let count = self.holds.len();
+        self.holds.retain(|h| h.cookie != cookie);
+        self.holds.len() != count
+    }
+
+    /// Another profile was loaded (e.g. by the Decky UI); follow it if it's one of ours.
+    /// Returns the released cookies if the active profile changed.
+    fn follow(&mut self, path: &str) -> Option<Vec<u32>> {
+        let current = self.effective();
+        if self.target(current).map(target_path).as_deref() == Some(path) {
+            return None;
+        }
+        let profile = PROFILES
+            .iter()
+            .find(|p| self.target(p).map(target_path).as_deref() == Some(path))?;
+        Some(self.select(profile.to_string()))
+    }
+}
+
+/// State shared by the interfaces served under both bus names
+struct Shared {
+    sender: Mutex<Sender<ApiMessage>>, // Sender is not Sync; this is required for safety
+    state: Mutex<ProfilesState>,
+    /// Interface name and object of each served interface, for signals about changes from anywhere
+    contexts: Mutex<Vec<(&'static str, SignalContext<'static>)>>,
+}
+
+impl Shared {
+    /// Load the PowerTools profile mapped to `profile`, if there is one
+    fn load(&self, state: &ProfilesState, profile: &str) {
+        let message = match state.target(profile) {
+            Some(ProfileTargetJson { id: Some(id), name }) => {
+                ApiMessage::LoadSettings(*id, name.to_owned())
+            }
+            Some(ProfileTargetJson { id: None, .. }) => ApiMessage::LoadMainSettings,
+            None => return,
+        };
+        log::info!(
+            "Power profile {} loads {:?}",
+            profile,
+            state.target(profile)
+        );
+        self.sender
+            .lock()
+            .unwrap()
+            .send(message)
+            .expect("power_profiles send failed")
+    }
+
+    fn save(state: &ProfilesState) {
+        if let Err(e) = crate::persist::save_json(&state.config, config_path()) {
+            log::error!("Failed to save power profiles config: {}", e);
+        }
+    }
+
+    fn active_profile(&self) -> String {
+        self.state.lock().unwrap().effective().to_owned()
+    }
+
+    fn active_profile_holds(&self) -> Vec<HashMap<String, OwnedValue>> {
+        self.state
+            .lock()
+            .unwrap()
+            .holds
+            .iter()
+            .map(|h| {
+                dict(&[
+                    ("ApplicationId", &h.application_id),
+                    ("Profile", &h.profile),
+                    ("Reason", &h.reason),
+                ])
+            })
+            .collect()
+    }
+
+    /// Tell clients of every interface about released holds and the new profile and holds
+    async fn notify(&self, released: &[u32]) -> zbus::Result<()> {
+        let contexts = self.contexts.lock().unwrap().clone();
+        let active = Value::from(self.active_profile());
+        let holds = Value::from(self.active_profile_holds());
+        let changed = HashMap::from([("ActiveProfile", &active), ("ActiveProfileHolds", &holds)]);
+        for (interface, ctxt) in contexts {
+            for cookie in released {
+                ctxt.connection()
+                    .emit_signal(
+                        None::<BusName<'_>>,
+                        ctxt.path(),
+                        interface,
+                        "ProfileReleased",
+                        &(cookie,),
+                    )
+                    .await?;
+            }
+            fdo::Properties::properties_changed(&ctxt, interface.try_into()?, &changed, &[])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn set_active_profile(&self, profile: String) -> zbus::Result<()> {
+        if !PROFILES.contains(&profile.as_str()) {
+            return Err(fdo::Error::InvalidArgs(format!("Invalid profile `{}`", profile)).into());
+        }
+        let released = {
+            let mut state = self.state.lock().unwrap();
+            let released = state.select(profile);
+            self.load(&state, state.effective());
+            Self::save(&state);
+            released
+        };
+        self.notify(&released).await
+    }
+
+    async fn hold_profile(
+        &self,
+        profile: String,
+        reason: String,
+        application_id: String,
+    ) -> fdo::Result<u32> {
+        if profile != "performance" && profile != "power-saver" {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Only performance and power-saver can be held, not `{}`",
+                profile
+            )));
+        }
+        let cookie = {
+            let mut state = self.state.lock().unwrap();
+            let before = state.effective().to_owned();
+            let cookie = state.hold(profile, reason, application_id);
+            if before != state.effective() {
+                self.load(&state, state.effective());
+            }
+            cookie
+        };
+        self.notify(&[]).await?;
+        Ok(cookie)
+    }
+
+    async fn release_profile(&self, cookie: u32) -> fdo::Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let before = state.effective().to_owned();
+            if !state.release(cookie) {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "No hold with cookie {}",
+                    cookie
+                )));
+            }
+            if before != state.effective() {
+                self.load(&state, state.effective());
+            }
+        }
+        self.notify(&[]).await?;
+        Ok(())
+    }
+}
+
+/// The power-profiles-daemon interface under its old name, `net.hadess.PowerProfiles`
+pub struct PowerProfiles(Arc<Shared>);
+
+/// The power-profiles-daemon interface under its new name, `org.freedesktop.UPower.PowerProfiles`
+pub struct UPowerPowerProfiles(Arc<Shared>);
+
+impl PowerProfiles {
+    pub fn new(sender: Sender<ApiMessage>) -> Self {
+        Self(Arc::new(Shared {
+            sender: Mutex::new(sender),
+            state: Mutex::new(ProfilesState {
+                config: crate::persist::open_json(config_path()).unwrap_or_default(),
+                ..Default::default()
+            }),
+            contexts: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// The same profiles and holds, served under the new name
+    pub fn upower(&self) -> UPowerPowerProfiles {
+        UPowerPowerProfiles(self.0.clone())
+    }
+
+    /// Follow a profile loaded by something else, and tell the desktop about it
+    pub async fn profile_loaded(&self, path: &str) -> zbus::Result<()> {
+        let released = {
+            let mut state = self.0.state.lock().unwrap();
+            let released = state.follow(path);
+            if released.is_some() {
+                Shared::save(&state);
+            }
+            released
+        };
+        match released {
+            Some(released) => self.0.notify(&released).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Implement the power-profiles-daemon interface for one of its names
+macro_rules! power_profiles_interface {
+    ($ty:ident, $name:literal) => {
+        impl $ty {
+            pub fn set_signal_context(&self, ctxt: SignalContext<'static>) {
+                self.0.contexts.lock().unwrap().push(($name, ctxt));
+            }
+        }
+
+        #[zbus::interface(name = $name)]
+        impl $ty {
+            #[zbus(property(emits_changed_signal = "false"))]
+            fn active_profile(&self) -> String {
+                self.0.active_profile()
+            }
+
+            #[zbus(property)]
+            async fn set_active_profile(&self, profile: String) -> zbus::Result<()> {
+                self.0.set_active_profile(profile).await
+            }
+
+            #[zbus(property)]
+            fn performance_inhibited(&self) -> String {
+                String::new()
+            }
+
+            #[zbus(property)]
+            fn performance_degraded(&self) -> String {
+                String::new()
+            }
+
+            #[zbus(property)]
+            fn profiles(&self) -> Vec<HashMap<String, OwnedValue>> {
+                PROFILES
+                    .iter()
+                    .map(|p| {
+                        dict(&[
+                            ("Profile", p),
+                            ("Driver", DRIVER),
+                            ("PlatformDriver", DRIVER),
+                        ])
+                    })
+                    .collect()
+            }
+
+            #[zbus(property)]
+            fn actions(&self) -> Vec<String> {
+                Vec::new()
+            }
+
+            #[zbus(property(emits_changed_signal = "false"))]
+            fn active_profile_holds(&self) -> Vec<HashMap<String, OwnedValue>> {
+                self.0.active_profile_holds()
+            }
+
+            #[zbus(property)]
+            fn version(&self) -> String {
+                crate::consts::PACKAGE_VERSION.to_owned()
+            }
+
+            /// Switch to performance or power-saver until released
+            async fn hold_profile(
+                &self,
+                profile: String,
+                reason: String,
+                application_id: String,
+            ) -> fdo::Result<u32> {
+                self.0.hold_profile(profile, reason, application_id).await
+            }
+
+            async fn release_profile(&self, cookie: u32) -> fdo::Result<()> {
+                self.0.release_profile(cookie).await
+            }
+
+            /// Declared for introspection; Shared::notify() emits it for every interface
+            #[allow(dead_code)]
+            #[zbus(signal)]
+            async fn profile_released(ctxt: &SignalContext<'_>, cookie: u32) -> zbus::Result<()>;
+        }
+    };
+}
+
+power_profiles_interface!(PowerProfiles, "net.hadess.PowerProfiles");
+power_profiles_interface!(UPowerPowerProfiles, "org.freedesktop.UPower.PowerProfiles");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target(id: Option<u64>) -> Option<ProfileTargetJson> {
+        Some(ProfileTargetJson {
+            id,
+            name: "Test".to_owned(),
+        })
+    }
+
+    #[test]
+    fn holds_and_follow_test() {
+        let mut state = ProfilesState {
+            config: PowerProfilesJson {
+                power_saver: target(Some(1)),
+                balanced: target(None),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(state.effective(), "balanced");
+        let perf = state.hold("performance".into(), "game".into(), "app".into());
+        assert_eq!(state.effective(), "performance");
+        let saver = state.hold("power-saver".into(), "low battery".into(), "app".into());
+        assert_eq!(state.effective(), "power-saver");
+        assert!(state.release(saver));
+        assert!(!state.release(saver));
+        assert_eq!(state.effective(), "performance");
+        assert_eq!(state.select("balanced".into()), vec![perf]);
+        // loading the main profile is already balanced, but app 1 is power-saver
+        assert_eq!(state.follow(crate::consts::DEFAULT_SETTINGS_FILE), None);
+        let held = state.hold("performance".into(), "game".into(), "app".into());
+        assert_eq!(state.follow("1.json"), Some(vec![held]));
+        assert_eq!(state.effective(), "power-saver");
+        assert_eq!(state.follow("2.json"), None);
+    }
+}
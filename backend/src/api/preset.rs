@@ -0,0 +1,112 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use usdpl_back::core::serdes::Primitive;
+
+use super::handler::{ApiMessage, PresetMessage};
+
+type PresetCallback = Box<dyn FnOnce(Result<(), String>) + Send>;
+
+/// Generate a web method which does something with the preset named by its parameter.
+/// Returns true, or why it failed
+fn preset_action(
+    sender: Sender<ApiMessage>,
+    name: &'static str,
+    message: fn(String, PresetCallback) -> PresetMessage,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let setter = move |preset: String| {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |result: Result<(), String>| tx.send(result).unwrap_or(());
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Preset(message(preset, Box::new(callback))))
+            .unwrap_or_else(|_| panic!("{} send failed", name));
+        rx.recv()
+            .unwrap_or_else(|_| panic!("{} callback recv failed", name))
+    };
+    move |params_in: super::ApiParameterType| {
+        if let Some(Primitive::String(preset)) = params_in.first() {
+            match setter(preset.to_owned()) {
+                Ok(()) => vec![true.into()],
+                Err(e) => vec![e.into()],
+            }
+        } else {
+            vec![format!("{} missing parameter", name).into()]
+        }
+    }
+}
+
+/// Generate save current settings as a new preset web method
+pub fn create(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    preset_action(sender, "create_preset", PresetMessage::Create)
+}
+
+/// Generate replace preset with current settings web method
+pub fn update(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    preset_action(sender, "update_preset", PresetMessage::Update)
+}
+
+/// Generate load preset into the current profile web method
+pub fn apply(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    preset_action(sender, "apply_preset", PresetMessage::Apply)
+}
+
+/// Generate delete preset web method
+pub fn delete(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    preset_action(sender, "delete_preset", PresetMessage::Delete)
+}
+
+/// Generate get preset names web method
+pub fn list(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback =
+            move |names: Vec<String>| tx.send(names).expect("list_presets callback send failed");
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Preset(PresetMessage::List(Box::new(callback))))
+            .expect("list_presets send failed");
+        rx.recv().expect("list_presets callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![Primitive::Json(serde_json::to_string(&getter()).unwrap())]
+    }
+}
+
+/// Generate get preset of the current profile web method
+pub fn get_current(
+    sender: Sender<ApiMessage>,
+) -> impl Fn(super::ApiParameterType) -> super::ApiParameterType {
+    let sender = Mutex::new(sender); // Sender is not Sync; this is required for safety
+    let getter = move || {
+        let (tx, rx) = mpsc::channel();
+        let callback = move |name: Option<String>| {
+            tx.send(name)
+                .expect("get_current_preset callback send failed")
+        };
+        sender
+            .lock()
+            .unwrap()
+            .send(ApiMessage::Preset(PresetMessage::GetCurrent(Box::new(
+                callback,
+            ))))
+            .expect("get_current_preset send failed");
+        rx.recv().expect("get_current_preset callback recv failed")
+    };
+    move |_: super::ApiParameterType| {
+        vec![getter().map(|name| name.into()).unwrap_or(Primitive::Empty)]
+    }
+}
@@ -1,6 +1,9 @@
 use std::convert::Into;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
 use usdpl_back::core::serdes::Primitive;
 
+use super::handler::ApiMessage;
 use crate::settings::SettingError;
 
 #[inline]
@@ -30,6 +33,39 @@ pub fn map_optional_result<T: Into<Primitive>>(
     }
 }
 
+/// Send a setter message, then wait until its new values have been checked.
+/// Returns the values which are set, which are adjusted ones in clamp mode.
+pub fn send_checked(
+    sender: &Mutex<Sender<ApiMessage>>,
+    message: ApiMessage,
+    name: &str,
+) -> Result<super::ApiParameterType, Vec<String>> {
+    let (tx, rx) = mpsc::channel();
+    let callback =
+        move |result: Result<super::ApiParameterType, Vec<String>>| tx.send(result).unwrap_or(());
+    sender
+        .lock()
+        .unwrap()
+        .send(ApiMessage::Checked(Box::new(message), Box::new(callback)))
+        .unwrap_or_else(|_| panic!("{} send failed", name));
+    rx.recv()
+        .unwrap_or_else(|_| panic!("{} callback recv failed", name))
+}
+
+/// Map rejected values to their error messages, otherwise respond with the values which are set
+#[inline]
+pub fn map_checked(
+    result: Result<super::ApiParameterType, Vec<String>>,
+) -> super::ApiParameterType {
+    match result {
+        Ok(values) => values,
+        Err(errors) => {
+            log::debug!("Mapping rejected values to primitive: {:?}", errors);
+            errors.into_iter().map(|e| e.into()).collect()
+        }
+    }
+}
+
 /*#[inline]
 pub fn map_empty_result<T: Into<Primitive>>(
     result: Result<(), SettingError>,
@@ -1,13 +1,24 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::thread::{self, JoinHandle};
 
 use crate::settings::Settings;
 //use crate::utility::{unwrap_lock, unwrap_maybe_fatal};
 use crate::api::handler::ApiMessageHandler;
 
+pub const THREAD_NAME: &str = "api_worker";
+
 pub fn spawn(mut settings: Settings, mut handler: ApiMessageHandler) -> JoinHandle<()> {
-    thread::spawn(move || {
-        log::info!("api_worker starting...");
-        handler.process_forever(&mut settings);
-        log::warn!("api_worker completed!");
-    })
+    thread::Builder::new()
+        .name(THREAD_NAME.to_owned())
+        .spawn(move || {
+            log::info!("api_worker starting...");
+            let result = catch_unwind(AssertUnwindSafe(|| handler.process_forever(&mut settings)));
+            if result.is_err() {
+                log::error!("api_worker panicked, restoring startup hardware settings");
+                handler.shutdown(&mut settings);
+                std::process::exit(crate::shutdown_worker::PANIC_EXIT_CODE);
+            }
+            log::warn!("api_worker completed!");
+        })
+        .expect("api_worker thread spawn failed")
 }
@@ -0,0 +1,104 @@
+//! Minimal USDPL client, speaking the same protocol as the front-end over plain HTTP
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use usdpl_back::core::serdes::{Dumpable, Loadable, Primitive};
+use usdpl_back::core::socket::Packet;
+use usdpl_back::core::RemoteCall;
+
+/// Long enough for GENERAL_wait_event to respond
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// USDPL rejects calls (in release builds) whose id is far from the latest one it saw,
+/// and any other low id would become the latest one, cutting off the front-end's calls.
+/// Id 0 leaves it without a latest id, like a back-end which was just started,
+/// so the front-end's next call is accepted whatever its id is.
+const CALL_ID: u64 = 0;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(std::io::Error),
+    Http(String),
+    Packet(String),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Connection error: {} (is the back-end running?)", e),
+            Self::Http(msg) => write!(f, "HTTP error: {}", msg),
+            Self::Packet(msg) => write!(f, "USDPL error: {}", msg),
+        }
+    }
+}
+
+pub struct Client {
+    port: u16,
+}
+
+impl Client {
+    pub fn new(port: u16) -> Self {
+        Self { port }
+    }
+
+    /// Call `function` on the back-end and return its response
+    pub fn call(
+        &self,
+        function: &str,
+        parameters: Vec<Primitive>,
+    ) -> Result<Vec<Primitive>, ClientError> {
+        let id = CALL_ID;
+        let packet = Packet::Call(RemoteCall {
+            id,
+            function: function.to_owned(),
+            parameters,
+        });
+        let mut body = String::new();
+        packet
+            .dump_base64(&mut body)
+            .map_err(|e| ClientError::Packet(e.to_string()))?;
+        let response = self.post(&body)?;
+        match Packet::load_base64(response.trim().as_bytes()) {
+            Ok((Packet::CallResponse(resp), _)) if resp.id == id => Ok(resp.response),
+            Ok((Packet::CallResponse(resp), _)) => Err(ClientError::Packet(format!(
+                "response id {} does not match call id {}",
+                resp.id, id
+            ))),
+            Ok((Packet::Invalid, _)) => Err(ClientError::Packet(format!(
+                "back-end rejected call to `{}` (unknown function?)",
+                function
+            ))),
+            Ok(_) => Err(ClientError::Packet("unexpected response packet".to_owned())),
+            Err(e) => Err(ClientError::Packet(e.to_string())),
+        }
+    }
+
+    fn post(&self, body: &str) -> Result<String, ClientError> {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).map_err(ClientError::Io)?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .map_err(ClientError::Io)?;
+        write!(
+            stream,
+            "POST /usdpl/call HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.port,
+            body.len(),
+            body
+        )
+        .map_err(ClientError::Io)?;
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(ClientError::Io)?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| ClientError::Http("malformed response".to_owned()))?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(ClientError::Http(format!("{} {}", status, body)));
+        }
+        Ok(body.to_owned())
+    }
+}
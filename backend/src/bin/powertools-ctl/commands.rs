@@ -0,0 +1,457 @@
+//! Subcommands, and the back-end functions they call
+
+use usdpl_back::core::serdes::Primitive;
+
+/// Kind of a positional argument, for converting it to what the back-end expects
+#[derive(Clone, Copy)]
+pub enum Arg {
+    Number(&'static str),
+    Bool(&'static str),
+    Text(&'static str),
+    /// Path to a JSON file, or `-` for stdin
+    JsonFile(&'static str),
+    /// Steam app id of a profile, or `main` for the main profile
+    Profile(&'static str),
+}
+
+impl Arg {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Number(n)
+            | Self::Bool(n)
+            | Self::Text(n)
+            | Self::JsonFile(n)
+            | Self::Profile(n) => n,
+        }
+    }
+
+    fn parse(&self, value: &str) -> Result<Primitive, String> {
+        match self {
+            Self::Number(name) => value
+                .parse::<f64>()
+                .map(Primitive::F64)
+                .map_err(|_| format!("<{}> must be a number, got `{}`", name, value)),
+            Self::Bool(name) => match value {
+                "true" | "on" | "1" => Ok(Primitive::Bool(true)),
+                "false" | "off" | "0" => Ok(Primitive::Bool(false)),
+                _ => Err(format!("<{}> must be true or false, got `{}`", name, value)),
+            },
+            Self::Text(_) => Ok(Primitive::String(value.to_owned())),
+            Self::JsonFile(name) => {
+                let json = if value == "-" {
+                    std::io::read_to_string(std::io::stdin())
+                } else {
+                    std::fs::read_to_string(value)
+                }
+                .map_err(|e| format!("Failed to read <{}> `{}`: {}", name, value, e))?;
+                Ok(Primitive::Json(json))
+            }
+            Self::Profile(_) if value == "main" => Ok(Primitive::Empty),
+            Self::Profile(name) => value
+                .parse::<u64>()
+                .map(|id| Primitive::F64(id as f64))
+                .map_err(|_| format!("<{}> must be an app id or `main`, got `{}`", name, value)),
+        }
+    }
+}
+
+pub struct Command {
+    pub words: &'static [&'static str],
+    pub args: &'static [Arg],
+    pub function: &'static str,
+    pub help: &'static str,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        let mut usage = self.words.join(" ");
+        for arg in self.args {
+            usage.push_str(&format!(" <{}>", arg.name()));
+        }
+        usage
+    }
+
+    /// Convert the arguments after the command's words to call parameters
+    pub fn parameters(&self, args: &[String]) -> Result<Vec<Primitive>, String> {
+        if args.len() != self.args.len() {
+            return Err(format!("Usage: {}", self.usage()));
+        }
+        self.args
+            .iter()
+            .zip(args)
+            .map(|(kind, value)| kind.parse(value))
+            .collect()
+    }
+}
+
+macro_rules! cmd {
+    ([$($word:literal),+], [$($arg:expr),*], $function:literal, $help:literal) => {
+        Command {
+            words: &[$($word),+],
+            args: &[$($arg),*],
+            function: $function,
+            help: $help,
+        }
+    };
+}
+
+use Arg::*;
+
+pub const COMMANDS: &[Command] = &[
+    cmd!(
+        ["get", "limits"],
+        [],
+        "GENERAL_get_limits",
+        "Limits of the current drivers, as JSON"
+    ),
+    cmd!(
+        ["get", "settings"],
+        [],
+        "GENERAL_get_settings_json",
+        "All settings, as JSON"
+    ),
+    cmd!(
+        ["set", "settings"],
+        [JsonFile("file")],
+        "GENERAL_set_settings_json",
+        "Replace all settings with a JSON file (- for stdin)"
+    ),
+    cmd!(
+        ["get", "power-source"],
+        [],
+        "GENERAL_get_power_source",
+        "Power source whose CPU and GPU values are in use (ac, battery or null)"
+    ),
+    cmd!(
+        ["get", "rules"],
+        [],
+        "GENERAL_get_rules",
+        "Profile switching rules, as JSON"
+    ),
+    cmd!(
+        ["set", "rules"],
+        [JsonFile("file")],
+        "GENERAL_set_rules",
+        "Replace profile switching rules with a JSON file (- for stdin)"
+    ),
+    cmd!(
+        ["undo"],
+        [],
+        "GENERAL_undo",
+        "Undo the last change of the current profile"
+    ),
+    cmd!(
+        ["redo"],
+        [],
+        "GENERAL_redo",
+        "Redo the last undone change of the current profile"
+    ),
+    cmd!(
+        ["get", "history"],
+        [],
+        "GENERAL_get_history",
+        "Changes made to the current profile, as JSON"
+    ),
+    cmd!(
+        ["diff", "profiles"],
+        [Profile("old"), Profile("new")],
+        "GENERAL_diff_profiles",
+        "Differences between two saved profiles (app ids, or main)"
+    ),
+    cmd!(
+        ["export", "profile"],
+        [],
+        "GENERAL_export_profile",
+        "Current profile with device info, as JSON for sharing"
+    ),
+    cmd!(
+        ["import", "profile"],
+        [JsonFile("file"), Bool("overwrite")],
+        "GENERAL_import_profile",
+        "Import a shared profile from a JSON file (- for stdin), fitting it to this device; overwrite replaces the app's profile"
+    ),
+    cmd!(
+        ["get", "log-level"],
+        [],
+        "GENERAL_get_log_level",
+        "Back-end log verbosity"
+    ),
+    cmd!(
+        ["set", "log-level"],
+        [Text("level")],
+        "GENERAL_set_log_level",
+        "Set back-end log verbosity until it restarts (off, error, warn, info, debug or trace)"
+    ),
+    cmd!(
+        ["get", "gpu", "ppt"],
+        [],
+        "GPU_get_ppt",
+        "GPU fast and slow PPT"
+    ),
+    cmd!(
+        ["set", "gpu", "ppt"],
+        [Number("fast"), Number("slow")],
+        "GPU_set_ppt",
+        "Set GPU fast and slow PPT"
+    ),
+    cmd!(
+        ["unset", "gpu", "ppt"],
+        [],
+        "GPU_unset_ppt",
+        "Stop setting GPU PPT"
+    ),
+    cmd!(
+        ["get", "gpu", "clocks"],
+        [],
+        "GPU_get_clock_limits",
+        "GPU clock limits"
+    ),
+    cmd!(
+        ["set", "gpu", "clocks"],
+        [Number("min"), Number("max")],
+        "GPU_set_clock_limits",
+        "Set GPU clock limits"
+    ),
+    cmd!(
+        ["unset", "gpu", "clocks"],
+        [],
+        "GPU_unset_clock_limits",
+        "Stop setting GPU clock limits"
+    ),
+    cmd!(
+        ["get", "cpu", "online"],
+        [],
+        "CPU_get_onlines",
+        "Whether each CPU is online"
+    ),
+    cmd!(
+        ["set", "cpu", "online"],
+        [Number("index"), Bool("online")],
+        "CPU_set_online",
+        "Set a CPU online or offline"
+    ),
+    cmd!(
+        ["get", "cpu", "smt"],
+        [],
+        "CPU_get_smt",
+        "Whether SMT is enabled"
+    ),
+    cmd!(
+        ["set", "cpu", "smt"],
+        [Bool("smt")],
+        "CPU_set_smt",
+        "Enable or disable SMT"
+    ),
+    cmd!(
+        ["get", "cpu", "clocks"],
+        [Number("index")],
+        "CPU_get_clock_limits",
+        "Clock limits of a CPU"
+    ),
+    cmd!(
+        ["set", "cpu", "clocks"],
+        [Number("index"), Number("min"), Number("max")],
+        "CPU_set_clock_limits",
+        "Set clock limits of a CPU"
+    ),
+    cmd!(
+        ["unset", "cpu", "clocks"],
+        [Number("index")],
+        "CPU_unset_clock_limits",
+        "Stop setting clock limits of a CPU"
+    ),
+    cmd!(
+        ["get", "cpu", "governors"],
+        [],
+        "CPU_get_governors",
+        "Governor of each CPU"
+    ),
+    cmd!(
+        ["set", "cpu", "governor"],
+        [Number("index"), Text("governor")],
+        "CPU_set_governor",
+        "Set the governor of a CPU"
+    ),
+    cmd!(
+        ["get", "battery", "charge-now"],
+        [],
+        "BATTERY_charge_now",
+        "Battery charge (Wh)"
+    ),
+    cmd!(
+        ["get", "battery", "charge-full"],
+        [],
+        "BATTERY_charge_full",
+        "Battery capacity (Wh)"
+    ),
+    cmd!(
+        ["get", "battery", "current-now"],
+        [],
+        "BATTERY_current_now",
+        "Battery current (mA)"
+    ),
+    cmd!(
+        ["get", "battery", "charge-rate"],
+        [],
+        "BATTERY_get_charge_rate",
+        "Charge rate limit (mA)"
+    ),
+    cmd!(
+        ["set", "battery", "charge-rate"],
+        [Number("rate")],
+        "BATTERY_set_charge_rate",
+        "Set charge rate limit (mA)"
+    ),
+    cmd!(
+        ["unset", "battery", "charge-rate"],
+        [],
+        "BATTERY_unset_charge_rate",
+        "Stop setting the charge rate limit"
+    ),
+    cmd!(
+        ["get", "battery", "charge-mode"],
+        [],
+        "BATTERY_get_charge_mode",
+        "Charge mode"
+    ),
+    cmd!(
+        ["set", "battery", "charge-mode"],
+        [Text("mode")],
+        "BATTERY_set_charge_mode",
+        "Set charge mode"
+    ),
+    cmd!(
+        ["unset", "battery", "charge-mode"],
+        [],
+        "BATTERY_unset_charge_mode",
+        "Stop setting the charge mode"
+    ),
+    cmd!(
+        ["profile", "name"],
+        [],
+        "GENERAL_get_name",
+        "Name of the loaded profile"
+    ),
+    cmd!(
+        ["profile", "load"],
+        [Text("id")],
+        "GENERAL_load_settings",
+        "Load the profile of an app id"
+    ),
+    cmd!(
+        ["profile", "load-main"],
+        [],
+        "GENERAL_load_default_settings",
+        "Load the main profile"
+    ),
+    cmd!(
+        ["profile", "load-system"],
+        [],
+        "GENERAL_load_system_settings",
+        "Load system defaults into the current profile"
+    ),
+    cmd!(
+        ["preset", "list"],
+        [],
+        "PRESET_list",
+        "Names of all presets, as JSON"
+    ),
+    cmd!(
+        ["preset", "current"],
+        [],
+        "PRESET_get_current",
+        "Preset the loaded profile follows"
+    ),
+    cmd!(
+        ["preset", "create"],
+        [Text("name")],
+        "PRESET_create",
+        "Save the current settings as a new preset"
+    ),
+    cmd!(
+        ["preset", "update"],
+        [Text("name")],
+        "PRESET_update",
+        "Replace a preset with the current settings"
+    ),
+    cmd!(
+        ["preset", "apply"],
+        [Text("name")],
+        "PRESET_apply",
+        "Make the loaded profile follow a preset"
+    ),
+    cmd!(
+        ["preset", "delete"],
+        [Text("name")],
+        "PRESET_delete",
+        "Delete a preset"
+    ),
+    cmd!(
+        ["apply"],
+        [],
+        "GENERAL_apply_now",
+        "Apply all settings again"
+    ),
+    cmd!(
+        ["restore-stock"],
+        [],
+        "GENERAL_restore_stock",
+        "Put the hardware back to how it was at start-up"
+    ),
+    cmd!(
+        ["events"],
+        [Number("since")],
+        "GENERAL_wait_event",
+        "Wait for events after a sequence number, as JSON"
+    ),
+];
+
+/// Find the command whose words start `args`, and the arguments after them
+pub fn find(args: &[String]) -> Option<(&'static Command, &[String])> {
+    COMMANDS
+        .iter()
+        .filter(|c| args.len() >= c.words.len() && c.words.iter().zip(args).all(|(w, a)| w == a))
+        .max_by_key(|c| c.words.len())
+        .map(|c| (c, &args[c.words.len()..]))
+}
+
+/// Guess the type of a raw `call` parameter
+pub fn guess_parameter(value: &str) -> Primitive {
+    if value == "null" {
+        Primitive::Empty
+    } else if let Ok(b) = value.parse::<bool>() {
+        Primitive::Bool(b)
+    } else if let Ok(n) = value.parse::<f64>() {
+        Primitive::F64(n)
+    } else if value.starts_with('{') || value.starts_with('[') {
+        Primitive::Json(value.to_owned())
+    } else {
+        Primitive::String(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn find_command_test() {
+        let args = strings(&["set", "cpu", "clocks", "0", "1000", "3500"]);
+        let (cmd, rest) = find(&args).unwrap();
+        assert_eq!(cmd.function, "CPU_set_clock_limits");
+        let params = cmd.parameters(rest).unwrap();
+        assert!(matches!(params[2], Primitive::F64(x) if x == 3500.0));
+        // app ids are sent as strings
+        let args = strings(&["profile", "load", "1234"]);
+        let (cmd, rest) = find(&args).unwrap();
+        assert!(matches!(&cmd.parameters(rest).unwrap()[0], Primitive::String(id) if id == "1234"));
+        assert!(find(&strings(&["set", "cpu", "smt", "maybe"]))
+            .map(|(cmd, rest)| cmd.parameters(rest).is_err())
+            .unwrap());
+        assert!(find(&strings(&["frobnicate"])).is_none());
+    }
+}
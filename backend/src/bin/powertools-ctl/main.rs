@@ -0,0 +1,178 @@
+//! Command-line client for scripting a running PowerTools back-end
+
+mod client;
+mod commands;
+
+use usdpl_back::core::serdes::Primitive;
+
+use client::{Client, ClientError};
+
+// only the port and the config file are needed here, so the rest is left as the back-end has it
+#[allow(dead_code, clippy::redundant_static_lifetimes)]
+#[path = "../../consts.rs"]
+mod consts;
+
+const EXIT_USAGE: i32 = 1;
+const EXIT_CLIENT: i32 = 2;
+
+fn usage() -> String {
+    let mut usage = format!(
+        "Usage: {} [--port <port> | --config <file>] <command>\n\nCommands:\n",
+        env!("CARGO_BIN_NAME")
+    );
+    let mut lines: Vec<(String, &str)> = commands::COMMANDS
+        .iter()
+        .map(|c| (c.usage(), c.help))
+        .collect();
+    lines.push((
+        "status [--json]".to_owned(),
+        "Loaded profile, last apply and latest errors",
+    ));
+    lines.push((
+        "call <function> [params...]".to_owned(),
+        "Call any back-end function, e.g. GENERAL_get_limits",
+    ));
+    let width = lines.iter().map(|(u, _)| u.len()).max().unwrap_or(0);
+    for (u, help) in lines {
+        usage.push_str(&format!("  {:width$}  {}\n", u, help, width = width));
+    }
+    usage
+}
+
+/// Port the back-end listens on, from its config file when that sets one
+fn config_port(config_file: &str) -> u16 {
+    std::fs::read_to_string(config_file)
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .and_then(|json| json["port"].as_u64())
+        .and_then(|port| u16::try_from(port).ok())
+        .unwrap_or(consts::PORT)
+}
+
+fn primitive_to_string(primitive: &Primitive) -> String {
+    match primitive {
+        Primitive::Empty => "null".to_owned(),
+        Primitive::String(s) | Primitive::Json(s) => s.to_owned(),
+        Primitive::F32(x) => x.to_string(),
+        Primitive::F64(x) => x.to_string(),
+        Primitive::U32(x) => x.to_string(),
+        Primitive::U64(x) => x.to_string(),
+        Primitive::I32(x) => x.to_string(),
+        Primitive::I64(x) => x.to_string(),
+        Primitive::Bool(x) => x.to_string(),
+    }
+}
+
+fn call_json(client: &Client, function: &str) -> Result<serde_json::Value, ClientError> {
+    let response = client.call(function, Vec::new())?;
+    Ok(match response.first() {
+        Some(Primitive::Json(json)) => serde_json::from_str(json).unwrap_or_default(),
+        Some(Primitive::String(s)) => serde_json::Value::String(s.to_owned()),
+        _ => serde_json::Value::Null,
+    })
+}
+
+fn status(client: &Client, as_json: bool) -> Result<(), ClientError> {
+    let profile = call_json(client, "GENERAL_get_name")?;
+    let report = call_json(client, "GENERAL_get_apply_report")?;
+    let errors = call_json(client, "GENERAL_get_errors")?;
+    if as_json {
+        let status = serde_json::json!({
+            "profile": profile,
+            "apply_report": report,
+            "errors": errors,
+        });
+        println!("{}", status);
+        return Ok(());
+    }
+    println!("Profile: {}", profile.as_str().unwrap_or("?"));
+    println!(
+        "Last apply: {}, {} drifted value(s)",
+        report["outcome"]
+            .as_str()
+            .filter(|o| !o.is_empty())
+            .unwrap_or("nothing yet"),
+        report["drifted"]
+    );
+    match errors.as_array() {
+        Some(errors) if !errors.is_empty() => {
+            println!("Errors:");
+            for e in errors {
+                println!(
+                    "  [{}] {}: {}",
+                    e["setting"].as_str().unwrap_or("?"),
+                    e["kind"].as_str().unwrap_or("?"),
+                    e["msg"].as_str().unwrap_or("?")
+                );
+            }
+        }
+        _ => println!("Errors: none"),
+    }
+    Ok(())
+}
+
+fn run(args: Vec<String>) -> Result<(), (i32, String)> {
+    let mut port = None;
+    let mut config_file = consts::DEFAULT_CONFIG_FILE.to_owned();
+    let mut rest = args.as_slice();
+    while let Some(first) = rest.first() {
+        match first.as_str() {
+            "-h" | "--help" | "help" => {
+                println!("{}", usage());
+                return Ok(());
+            }
+            "--port" => {
+                port = Some(
+                    rest.get(1)
+                        .and_then(|p| p.parse().ok())
+                        .ok_or((EXIT_USAGE, "--port needs a number".to_owned()))?,
+                );
+                rest = &rest[2..];
+            }
+            "--config" => {
+                config_file = rest
+                    .get(1)
+                    .ok_or((EXIT_USAGE, "--config needs a file".to_owned()))?
+                    .to_owned();
+                rest = &rest[2..];
+            }
+            _ => break,
+        }
+    }
+    let client = Client::new(port.unwrap_or_else(|| config_port(&config_file)));
+    let client_err = |e: ClientError| (EXIT_CLIENT, e.to_string());
+    let (function, parameters) = match rest.first().map(|s| s.as_str()) {
+        None => return Err((EXIT_USAGE, usage())),
+        Some("status") => {
+            let as_json = rest.get(1).map(|a| a == "--json").unwrap_or(false);
+            return status(&client, as_json).map_err(client_err);
+        }
+        Some("call") => {
+            let function = rest
+                .get(1)
+                .ok_or((EXIT_USAGE, "call needs a function".to_owned()))?;
+            let parameters = rest[2..]
+                .iter()
+                .map(|p| commands::guess_parameter(p))
+                .collect();
+            (function.as_str(), parameters)
+        }
+        Some(_) => {
+            let (command, args) = commands::find(rest)
+                .ok_or_else(|| (EXIT_USAGE, format!("Unknown command\n\n{}", usage())))?;
+            let parameters = command.parameters(args).map_err(|e| (EXIT_USAGE, e))?;
+            (command.function, parameters)
+        }
+    };
+    for primitive in client.call(function, parameters).map_err(client_err)? {
+        println!("{}", primitive_to_string(&primitive));
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err((code, msg)) = run(std::env::args().skip(1).collect()) {
+        eprintln!("{}", msg);
+        std::process::exit(code);
+    }
+}
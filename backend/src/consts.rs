@@ -1,9 +1,32 @@
 pub const PORT: u16 = 44443;
 
-pub const PACKAGE_NAME: &'static str = env!("CARGO_PKG_NAME");
-pub const PACKAGE_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+/// Config file used by `--headless` when `--config` isn't given
+pub const DEFAULT_CONFIG_FILE: &str = "/etc/powertools/config.json";
+
+pub const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
+pub const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const DEFAULT_SETTINGS_FILE: &str = "default_settings.json";
 pub const DEFAULT_SETTINGS_NAME: &str = "Main";
 
 pub const LIMITS_FILE: &str = "limits_cache.json";
+
+pub const TELEMETRY_FILE: &str = "telemetry.bin";
+pub const TELEMETRY_CONFIG_FILE: &str = "telemetry.json";
+
+pub const HARDWARE_SNAPSHOT_FILE: &str = "hardware_snapshot.json";
+
+pub const WATCHDOG_CONFIG_FILE: &str = "watchdog.json";
+
+pub const VALIDATION_CONFIG_FILE: &str = "validation.json";
+
+pub const PRESETS_DIR: &str = "presets";
+
+pub const RULES_CONFIG_FILE: &str = "rules.json";
+
+pub const HISTORY_DIR: &str = "history";
+/// Most changes kept in the history of each profile
+pub const HISTORY_LIMIT: usize = 100;
+
+#[cfg(feature = "dbus")]
+pub const POWER_PROFILES_CONFIG_FILE: &str = "power_profiles.json";
@@ -0,0 +1,231 @@
+//! Start-up options from command-line arguments and a config file, for running without Decky
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use log::LevelFilter;
+
+use crate::logging::{LogDestination, LogFormat};
+use crate::persist::DaemonJson;
+
+const HEADLESS_SETTINGS_DIR: &str = "/var/lib/powertools/";
+const HEADLESS_LOG_FILE: &str = "/var/log/powertools.log";
+
+static CONFIG: OnceLock<DaemonConfig> = OnceLock::new();
+
+const USAGE: &str = "Options:
+  --headless              Run without Decky (defaults to the config file /etc/powertools/config.json)
+  --config <file>         Read options from a JSON config file
+  --port <port>           Port to listen on
+  --settings-dir <dir>    Directory for settings files
+  --log-path <file>       File to log to
+  --log-level <level>     off, error, warn, info, debug or trace
+  --log-to <sink>         file, journald or stderr (defaults to file)
+  --log-format <format>   text or json (one object per line)
+  --log-max-size <bytes>  Rotate the log file when it gets bigger than this (0 only rotates on start)
+  --log-keep <count>      Rotated log files to keep
+  --user <name>           User which owns the settings dir
+  --dbus <bus>            Serve org.powertools.Manager on system, session or a bus address (needs the dbus feature)
+  --power-profiles        Also serve net.hadess.PowerProfiles (on the system bus if --dbus isn't given)
+  -h, --help              Show this help";
+
+/// Why the command-line arguments didn't make a config
+#[derive(Debug)]
+pub enum ArgsError {
+    /// `--help` was given, so the caller should print usage and stop
+    Help,
+    /// Unknown, missing or invalid arguments
+    Invalid(String),
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Help => write!(f, "{}", USAGE),
+            Self::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<String> for ArgsError {
+    fn from(msg: String) -> Self {
+        Self::Invalid(msg)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DaemonConfig {
+    pub headless: bool,
+    pub port: Option<u16>,
+    pub settings_dir: Option<PathBuf>,
+    pub log_path: Option<PathBuf>,
+    pub log_level: Option<LevelFilter>,
+    pub log_to: Option<LogDestination>,
+    pub log_format: Option<LogFormat>,
+    pub log_max_size: Option<u64>,
+    pub log_keep: Option<usize>,
+    pub user: Option<String>,
+    pub dbus: Option<String>,
+    pub power_profiles: bool,
+}
+
+impl DaemonConfig {
+    /// Parse command-line arguments (without the program name).
+    /// Options given as arguments take priority over the config file.
+    /// This never exits the process; what to do with an error is up to the caller.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgsError> {
+        let mut config = Self::default();
+        let mut config_file = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{} needs a value\n\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--headless" => config.headless = true,
+                "--config" => config_file = Some(PathBuf::from(value()?)),
+                "--port" => {
+                    let port = value()?;
+                    config.port = Some(
+                        port.parse()
+                            .map_err(|_| format!("Invalid port `{}`", port))?,
+                    );
+                }
+                "--settings-dir" => config.settings_dir = Some(value()?.into()),
+                "--log-path" => config.log_path = Some(value()?.into()),
+                "--log-level" => config.log_level = Some(parse_level(&value()?)?),
+                "--log-to" => config.log_to = Some(value()?.parse()?),
+                "--log-format" => config.log_format = Some(value()?.parse()?),
+                "--log-max-size" => config.log_max_size = Some(parse_number(&arg, &value()?)?),
+                "--log-keep" => config.log_keep = Some(parse_number(&arg, &value()?)?),
+                "--user" => config.user = Some(value()?),
+                "--dbus" => config.dbus = Some(value()?),
+                "--power-profiles" => config.power_profiles = true,
+                "-h" | "--help" => return Err(ArgsError::Help),
+                _ => {
+                    return Err(ArgsError::Invalid(format!(
+                        "Unknown argument `{}`\n\n{}",
+                        arg, USAGE
+                    )))
+                }
+            }
+        }
+        if config_file.is_none() && config.headless {
+            config_file = Some(crate::consts::DEFAULT_CONFIG_FILE.into());
+        }
+        if let Some(path) = config_file {
+            match crate::persist::open_json::<DaemonJson, _>(&path) {
+                Ok(json) => config.fill_from(json)?,
+                Err(e) => eprintln!("Ignoring config file {}: {}", path.display(), e),
+            }
+        }
+        if config.headless {
+            config
+                .settings_dir
+                .get_or_insert_with(|| HEADLESS_SETTINGS_DIR.into());
+            config
+                .log_path
+                .get_or_insert_with(|| HEADLESS_LOG_FILE.into());
+        }
+        Ok(config)
+    }
+
+    fn fill_from(&mut self, json: DaemonJson) -> Result<(), String> {
+        self.port = self.port.or(json.port);
+        self.settings_dir = self.settings_dir.take().or(json.settings_dir);
+        self.log_path = self.log_path.take().or(json.log_path);
+        if self.log_level.is_none() {
+            self.log_level = json.log_level.as_deref().map(parse_level).transpose()?;
+        }
+        if self.log_to.is_none() {
+            self.log_to = json.log_to.as_deref().map(str::parse).transpose()?;
+        }
+        if self.log_format.is_none() {
+            self.log_format = json.log_format.as_deref().map(str::parse).transpose()?;
+        }
+        self.log_max_size = self.log_max_size.or(json.log_max_size);
+        self.log_keep = self.log_keep.or(json.log_keep);
+        self.user = self.user.take().or(json.user);
+        self.dbus = self.dbus.take().or(json.dbus);
+        self.power_profiles |= json.power_profiles.unwrap_or(false);
+        Ok(())
+    }
+
+    /// Make this the config for the rest of the process; only the first call does anything
+    pub fn install(self) -> &'static Self {
+        CONFIG.get_or_init(|| self)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("Invalid log level `{}`", level))
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, number: &str) -> Result<T, String> {
+    number
+        .parse()
+        .map_err(|_| format!("Invalid {} `{}`", arg, number))
+}
+
+/// Options the back-end was started with (all defaults if none were installed)
+pub fn config() -> &'static DaemonConfig {
+    CONFIG.get_or_init(DaemonConfig::default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn args_over_config_file_test() {
+        let path = std::env::temp_dir().join(format!(
+            "powertools_daemon_test_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{"port": 1234, "log_level": "warn", "log_to": "journald", "log_keep": 5, "user": "gamer", "dbus": "system"}"#,
+        )
+        .unwrap();
+        let config = DaemonConfig::from_args(args(&[
+            "--headless",
+            "--config",
+            path.to_str().unwrap(),
+            "--port",
+            "5678",
+            "--dbus",
+            "session",
+            "--log-to",
+            "stderr",
+        ]))
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.port, Some(5678));
+        assert_eq!(config.log_level, Some(LevelFilter::Warn));
+        assert_eq!(config.log_to, Some(LogDestination::Stderr));
+        assert_eq!(config.log_keep, Some(5));
+        assert_eq!(config.user.as_deref(), Some("gamer"));
+        assert_eq!(config.dbus.as_deref(), Some("session"));
+        assert_eq!(config.settings_dir, Some(HEADLESS_SETTINGS_DIR.into()));
+        assert!(DaemonConfig::from_args(args(&["--port"])).is_err());
+        assert!(DaemonConfig::from_args(args(&["--log-level", "loud"])).is_err());
+        assert!(DaemonConfig::from_args(args(&["--log-format", "xml"])).is_err());
+        assert!(matches!(
+            DaemonConfig::from_args(args(&["--help"])),
+            Err(ArgsError::Help)
+        ));
+        assert!(matches!(
+            DaemonConfig::from_args(args(&["--loud"])),
+            Err(ArgsError::Invalid(_))
+        ));
+        let decky = DaemonConfig::from_args(Vec::new()).unwrap();
+        assert!(!decky.headless && decky.settings_dir.is_none());
+    }
+}
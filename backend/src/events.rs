@@ -0,0 +1,192 @@
+//! Events pushed to the front-end, which waits for them with a long-poll
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::api::SettingErrorInfo;
+use crate::settings::PowerMode;
+
+/// Most events kept around for clients which are catching up
+const CAPACITY: usize = 256;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    PluggedIn,
+    PluggedOut,
+    /// Battery fill amount crossed into a new value: 0 = empty, 1 = full
+    BatteryCharge {
+        charge: f64,
+    },
+    /// Battery charge changed since the last event, or it started or stopped (dis)charging
+    Battery {
+        charge_now: Option<f64>,
+        charge_full: Option<f64>,
+        current_now: Option<f64>,
+    },
+    Resume,
+    ProfileLoaded {
+        name: String,
+        path: String,
+    },
+    Applied {
+        outcome: String,
+        drifted: usize,
+    },
+    Error(SettingErrorInfo),
+    /// A profile switching rule started matching, and its profile or preset was loaded
+    RuleMatched {
+        name: String,
+    },
+}
+
+impl From<&PowerMode> for Event {
+    fn from(mode: &PowerMode) -> Self {
+        match mode {
+            PowerMode::PluggedIn => Self::PluggedIn,
+            PowerMode::PluggedOut => Self::PluggedOut,
+            PowerMode::BatteryCharge(charge) => Self::BatteryCharge { charge: *charge },
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EventRecord {
+    pub seq: u64,
+    /// Seconds since UNIX epoch
+    pub time: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Events after the sequence number a client asked for
+#[derive(Serialize, Debug)]
+pub struct EventBatch {
+    /// Latest sequence number; pass this to the next wait to resume from here
+    pub seq: u64,
+    /// Some events were dropped (or the back-end restarted) since the requested sequence number
+    pub missed: bool,
+    pub events: Vec<EventRecord>,
+}
+
+#[derive(Default)]
+struct EventLog {
+    last_seq: u64,
+    events: VecDeque<EventRecord>,
+}
+
+impl EventLog {
+    fn batch(&self, since: u64) -> EventBatch {
+        let restarted = since > self.last_seq;
+        let since = if restarted { 0 } else { since };
+        let dropped = self
+            .events
+            .front()
+            .map(|e| e.seq > since + 1)
+            .unwrap_or(false);
+        EventBatch {
+            seq: self.last_seq,
+            missed: restarted || dropped,
+            events: self
+                .events
+                .iter()
+                .filter(|e| e.seq > since)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Shared handle for publishing and waiting on events
+#[derive(Clone, Default)]
+pub struct EventBus {
+    inner: Arc<(Mutex<EventLog>, Condvar)>,
+}
+
+impl EventBus {
+    pub fn publish(&self, event: Event) {
+        let (log, waiters) = &*self.inner;
+        let mut log = log.lock().unwrap();
+        log.last_seq += 1;
+        let record = EventRecord {
+            seq: log.last_seq,
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            event,
+        };
+        log::debug!("Publishing event {:?}", record);
+        if log.events.len() == CAPACITY {
+            log.events.pop_front();
+        }
+        log.events.push_back(record);
+        waiters.notify_all();
+    }
+
+    /// Sequence number of the latest event, for only waiting on events published after now
+    #[cfg(any(feature = "dbus", test))]
+    pub fn last_seq(&self) -> u64 {
+        self.inner.0.lock().unwrap().last_seq
+    }
+
+    /// Wait until there are events after `since`, or `timeout` runs out
+    pub fn wait(&self, since: u64, timeout: Duration) -> EventBatch {
+        let (log, waiters) = &*self.inner;
+        let deadline = Instant::now() + timeout;
+        let mut log = log.lock().unwrap();
+        loop {
+            let batch = log.batch(since);
+            let now = Instant::now();
+            if !batch.events.is_empty() || now >= deadline {
+                return batch;
+            }
+            log = waiters.wait_timeout(log, deadline - now).unwrap().0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resume_from_seq_test() {
+        let bus = EventBus::default();
+        let empty = bus.wait(0, Duration::ZERO);
+        assert_eq!((empty.seq, empty.missed, empty.events.len()), (0, false, 0));
+        bus.publish(Event::PluggedIn);
+        bus.publish(Event::Resume);
+        assert_eq!(bus.last_seq(), 2);
+        let batch = bus.wait(1, Duration::ZERO);
+        assert_eq!(batch.seq, 2);
+        assert!(!batch.missed);
+        assert_eq!(batch.events[0].event, Event::Resume);
+        // a client from before a restart gets everything
+        let batch = bus.wait(10, Duration::ZERO);
+        assert!(batch.missed);
+        assert_eq!(batch.events.len(), 2);
+        for _ in 0..CAPACITY {
+            bus.publish(Event::PluggedOut);
+        }
+        let batch = bus.wait(1, Duration::ZERO);
+        assert!(batch.missed);
+        assert_eq!(batch.events.len(), CAPACITY);
+    }
+
+    #[test]
+    fn wake_on_publish_test() {
+        let bus = EventBus::default();
+        let publisher = bus.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            publisher.publish(Event::PluggedOut);
+        });
+        let batch = bus.wait(0, Duration::from_secs(10));
+        handle.join().unwrap();
+        assert_eq!(batch.events[0].event, Event::PluggedOut);
+    }
+}
@@ -0,0 +1,318 @@
+//! Logging to a file, the systemd journal or stderr, as text or JSON lines.
+//! The level can be changed while running (see GENERAL_set_log_level), since it's the `log` crate's max level.
+
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+/// Size a log file may grow to before it's rotated
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// Rotated log files to keep, besides the one being written
+pub const DEFAULT_KEEP: usize = 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogDestination {
+    #[default]
+    File,
+    Journald,
+    Stderr,
+}
+
+impl std::str::FromStr for LogDestination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Self::File),
+            "journald" => Ok(Self::Journald),
+            "stderr" => Ok(Self::Stderr),
+            _ => Err(format!("Invalid log destination `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for tooling
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid log format `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    pub destination: LogDestination,
+    pub format: LogFormat,
+    pub path: PathBuf,
+    /// Rotate the log file once it's bigger than this; 0 never rotates while running
+    pub max_size: u64,
+    /// Rotated log files to keep, as `<path>.1` (newest) to `<path>.<keep>`
+    pub keep: usize,
+}
+
+enum Sink {
+    File { file: File, size: u64 },
+    Journald(UnixDatagram),
+    Stderr,
+}
+
+struct Logger {
+    options: LogOptions,
+    sink: Mutex<Sink>,
+}
+
+/// Move `path` to `path.1`, `path.1` to `path.2`, and so on, dropping the oldest
+fn rotate(path: &Path, keep: usize) -> std::io::Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    let numbered = |i: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", i));
+        PathBuf::from(name)
+    };
+    for i in (1..keep).rev() {
+        let from = numbered(i);
+        if from.exists() {
+            std::fs::rename(from, numbered(i + 1))?;
+        }
+    }
+    std::fs::rename(path, numbered(1))
+}
+
+/// Journal native protocol: `KEY=value` lines, or a length-prefixed value when it has line breaks
+fn journald_field(datagram: &mut Vec<u8>, key: &str, value: &str) {
+    datagram.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        datagram.push(b'\n');
+        datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        datagram.push(b'=');
+    }
+    datagram.extend_from_slice(value.as_bytes());
+    datagram.push(b'\n');
+}
+
+fn syslog_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+impl Logger {
+    fn line(&self, record: &Record) -> String {
+        match self.options.format {
+            LogFormat::Text => {
+                let time = crate::utility::local_time()
+                    .map(|tm| format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec))
+                    .unwrap_or_default();
+                match std::thread::current().name() {
+                    Some(thread) => format!(
+                        "{} [{}] ({}) {}: {}\n",
+                        time,
+                        record.level(),
+                        thread,
+                        record.target(),
+                        record.args()
+                    ),
+                    None => format!(
+                        "{} [{}] {}: {}\n",
+                        time,
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    ),
+                }
+            }
+            LogFormat::Json => {
+                let time = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                let json = serde_json::json!({
+                    "time_ms": time,
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "thread": std::thread::current().name(),
+                    "file": record.file(),
+                    "line": record.line(),
+                    "message": record.args().to_string(),
+                });
+                format!("{}\n", json)
+            }
+        }
+    }
+
+    fn journald_datagram(&self, record: &Record) -> Vec<u8> {
+        let mut datagram = Vec::new();
+        journald_field(
+            &mut datagram,
+            "PRIORITY",
+            &syslog_priority(record.level()).to_string(),
+        );
+        journald_field(
+            &mut datagram,
+            "SYSLOG_IDENTIFIER",
+            crate::consts::PACKAGE_NAME,
+        );
+        journald_field(&mut datagram, "TARGET", record.target());
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            journald_field(&mut datagram, "CODE_FILE", file);
+            journald_field(&mut datagram, "CODE_LINE", &line.to_string());
+        }
+        journald_field(&mut datagram, "MESSAGE", &record.args().to_string());
+        datagram
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut sink = match self.sink.lock() {
+            Ok(sink) => sink,
+            Err(_) => return,
+        };
+        match &mut *sink {
+            Sink::File { file, size } => {
+                let line = self.line(record);
+                if file.write_all(line.as_bytes()).is_err() {
+                    return;
+                }
+                *size += line.len() as u64;
+                if self.options.max_size != 0 && *size > self.options.max_size {
+                    let path = &self.options.path;
+                    match rotate(path, self.options.keep).and_then(|_| File::create(path)) {
+                        Ok(new) => {
+                            *file = new;
+                            *size = 0;
+                        }
+                        Err(e) => eprintln!("Failed to rotate log {}: {}", path.display(), e),
+                    }
+                }
+            }
+            Sink::Journald(socket) => {
+                if let Err(e) = socket.send(&self.journald_datagram(record)) {
+                    eprintln!("Failed to log to journald ({}): {}", e, record.args());
+                }
+            }
+            Sink::Stderr => eprint!("{}", self.line(record)),
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            if let Sink::File { file, .. } = &mut *sink {
+                file.flush().unwrap_or(());
+            }
+        }
+    }
+}
+
+fn open_file(options: &LogOptions) -> Result<File, String> {
+    if let Some(parent) = options.path.parent() {
+        std::fs::create_dir_all(parent).unwrap_or(());
+    }
+    // the log of the previous run is kept, instead of being truncated
+    rotate(&options.path, options.keep)
+        .map_err(|e| format!("can't rotate {}: {}", options.path.display(), e))?;
+    File::create(&options.path)
+        .map_err(|e| format!("can't create {}: {}", options.path.display(), e))
+}
+
+/// Start logging; only the first call does anything.
+/// When the log file or the journal aren't available, logs go to stderr instead.
+/// Returns where logs go.
+pub fn init(options: LogOptions, level: LevelFilter) -> Result<LogDestination, String> {
+    let sink = match options.destination {
+        LogDestination::File => match open_file(&options) {
+            Ok(file) => Sink::File { file, size: 0 },
+            Err(e) => {
+                eprintln!("Logging to stderr, {}", e);
+                Sink::Stderr
+            }
+        },
+        LogDestination::Journald => {
+            match UnixDatagram::unbound().and_then(|socket| {
+                socket.connect(JOURNALD_SOCKET)?;
+                Ok(socket)
+            }) {
+                Ok(socket) => Sink::Journald(socket),
+                Err(e) => {
+                    eprintln!("Logging to stderr, can't connect to journald: {}", e);
+                    Sink::Stderr
+                }
+            }
+        }
+        LogDestination::Stderr => Sink::Stderr,
+    };
+    let destination = match sink {
+        Sink::File { .. } => LogDestination::File,
+        Sink::Journald(_) => LogDestination::Journald,
+        Sink::Stderr => LogDestination::Stderr,
+    };
+    log::set_boxed_logger(Box::new(Logger {
+        options,
+        sink: Mutex::new(sink),
+    }))
+    .map_err(|e| e.to_string())?;
+    log::set_max_level(level);
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotate_and_journald_test() {
+        let dir =
+            std::env::temp_dir().join(format!("powertools_logging_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.log");
+        for run in ["first", "second", "third"] {
+            rotate(&path, 2).unwrap();
+            std::fs::write(&path, run).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("test.log"), "third");
+        assert_eq!(read("test.log.1"), "second");
+        assert_eq!(read("test.log.2"), "first");
+        rotate(&path, 2).unwrap();
+        assert_eq!(read("test.log.2"), "second");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut datagram = Vec::new();
+        journald_field(&mut datagram, "PRIORITY", "6");
+        journald_field(&mut datagram, "MESSAGE", "a\nb");
+        let mut expected = b"PRIORITY=6\nMESSAGE\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(datagram, expected);
+    }
+}
@@ -1,57 +1,82 @@
 mod api;
+mod daemon;
+mod events;
+mod logging;
 mod persist;
+mod rules;
 mod settings;
 mod state;
+mod telemetry;
+mod watchdog;
 
 mod consts;
 use consts::*;
+mod api_worker;
+mod periodic_worker;
 mod power_worker;
 mod resume_worker;
-//mod save_worker;
-mod api_worker;
+mod save_worker;
+mod shutdown_worker;
 mod utility;
 
-use settings::OnSet;
-
-use simplelog::{LevelFilter, WriteLogger};
+use settings::{OnSet, OnShutdown};
 
 use usdpl_back::core::serdes::Primitive;
 use usdpl_back::Instance;
 
 fn main() -> Result<(), ()> {
+    let daemon_config = match daemon::DaemonConfig::from_args(std::env::args().skip(1)) {
+        Ok(config) => config.install(),
+        Err(daemon::ArgsError::Help) => {
+            println!("{}", daemon::ArgsError::Help);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     #[cfg(debug_assertions)]
-    let log_filepath = usdpl_back::api::dirs::home()
+    let default_log_filepath = usdpl_back::api::dirs::home()
         .unwrap_or_else(|| "/tmp/".into())
         .join(PACKAGE_NAME.to_owned() + ".log");
     #[cfg(not(debug_assertions))]
-    let log_filepath = std::path::Path::new("/tmp").join(format!("{}.log", PACKAGE_NAME));
-    #[cfg(debug_assertions)]
-    let old_log_filepath = usdpl_back::api::dirs::home()
-        .unwrap_or_else(|| "/tmp/".into())
-        .join(PACKAGE_NAME.to_owned() + ".log.old");
-    #[cfg(debug_assertions)]
-    {
-        if std::path::Path::new(&log_filepath).exists() {
-            std::fs::copy(&log_filepath, &old_log_filepath)
-                .expect("Unable to increment logs. Do you have write permissions?");
-        }
-    }
-    WriteLogger::init(
+    let default_log_filepath = std::path::Path::new("/tmp").join(format!("{}.log", PACKAGE_NAME));
+    let log_filepath = daemon_config
+        .log_path
+        .clone()
+        .unwrap_or(default_log_filepath);
+    let log_destination = daemon_config.log_to.unwrap_or_default();
+    let log_options = logging::LogOptions {
+        destination: log_destination,
+        format: daemon_config.log_format.unwrap_or_default(),
+        path: log_filepath.clone(),
+        max_size: daemon_config
+            .log_max_size
+            .unwrap_or(logging::DEFAULT_MAX_SIZE),
+        keep: daemon_config.log_keep.unwrap_or(logging::DEFAULT_KEEP),
+    };
+    let log_level = daemon_config.log_level.unwrap_or({
         #[cfg(debug_assertions)]
         {
-            LevelFilter::Debug
-        },
+            log::LevelFilter::Debug
+        }
         #[cfg(not(debug_assertions))]
         {
-            LevelFilter::Info
-        },
-        Default::default(),
-        std::fs::File::create(&log_filepath).unwrap(),
-        //std::fs::File::create("/home/deck/powertools-rs.log").unwrap(),
-    )
-    .unwrap();
-    log::debug!("Logging to: {:?}.", log_filepath);
-    println!("Logging to: {:?}", log_filepath);
+            log::LevelFilter::Info
+        }
+    });
+    let log_destination = logging::init(log_options, log_level).unwrap_or_else(|e| {
+        // only when there's a logger already, which is where logs go then
+        eprintln!("Unable to start logging: {}", e);
+        log_destination
+    });
+    if log_destination == logging::LogDestination::File {
+        log::debug!("Logging to: {:?}.", log_filepath);
+        println!("Logging to: {:?}", log_filepath);
+    } else {
+        println!("Logging to: {:?}", log_destination);
+    }
     log::info!("Starting back-end ({} v{})", PACKAGE_NAME, PACKAGE_VERSION);
     println!("Starting back-end ({} v{})", PACKAGE_NAME, PACKAGE_VERSION);
     log::info!(
@@ -64,6 +89,13 @@ fn main() -> Result<(), ()> {
     );
 
     log::info!("home dir: {:?}", usdpl_back::api::dirs::home());
+    if daemon_config.headless {
+        log::info!("Running headless with {:?}", daemon_config);
+    }
+    log::info!("settings dir: {}", utility::settings_dir().display());
+    if let Err(e) = utility::create_settings_dir(&utility::settings_dir()) {
+        log::error!("Failed to create settings dir: {}", e);
+    }
 
     log::info!("Last version file: {}", utility::read_version_file());
     if let Err(e) = utility::save_version_file() {
@@ -90,13 +122,56 @@ fn main() -> Result<(), ()> {
 
     log::debug!("Settings: {:?}", loaded_settings);
 
-    let (api_handler, api_sender) = crate::api::handler::ApiMessageHandler::new();
+    let (_save_handle, save_sender) = save_worker::spawn();
+    let (api_handler, api_sender) = crate::api::handler::ApiMessageHandler::new(save_sender);
+    let events = api_handler.events();
 
-    //let (_save_handle, save_sender) = save_worker::spawn(loaded_settings.clone());
     let _resume_handle = resume_worker::spawn(api_sender.clone());
     let _power_handle = power_worker::spawn(api_sender.clone());
+    // recording is skipped by the handler when telemetry is disabled
+    let _telemetry_handle = periodic_worker::spawn(
+        "telemetry_worker",
+        periodic_worker::TELEMETRY_PERIOD,
+        api_sender.clone(),
+        || api::handler::ApiMessage::Telemetry(api::handler::TelemetryMessage::Record),
+    );
+    // nothing happens in the handler when there are no thermal rules
+    let _thermal_handle = periodic_worker::spawn(
+        "thermal_worker",
+        periodic_worker::THERMAL_PERIOD,
+        api_sender.clone(),
+        || api::handler::ApiMessage::ThermalCheck,
+    );
+    // the watchdog is off by default, and backs off on its own, so checks may be skipped in the handler
+    let _watchdog_handle = periodic_worker::spawn(
+        "watchdog_worker",
+        periodic_worker::WATCHDOG_PERIOD,
+        api_sender.clone(),
+        || api::handler::ApiMessage::Watchdog(api::handler::WatchdogMessage::Check),
+    );
+    let _shutdown_handle = shutdown_worker::spawn(api_sender.clone());
+    shutdown_worker::set_panic_hook(api_sender.clone());
+    #[cfg(feature = "dbus")]
+    let _dbus_handle = daemon_config
+        .dbus
+        .as_deref()
+        .or(daemon_config.power_profiles.then_some("system"))
+        .and_then(|bus| {
+            api::dbus::spawn(
+                bus,
+                daemon_config.power_profiles,
+                api_sender.clone(),
+                events.clone(),
+            )
+            .map_err(|e| log::error!("Failed to start D-Bus service on {}: {}", bus, e))
+            .ok()
+        });
+    #[cfg(not(feature = "dbus"))]
+    if daemon_config.dbus.is_some() || daemon_config.power_profiles {
+        log::warn!("Built without the dbus feature, ignoring D-Bus option");
+    }
 
-    let instance = Instance::new(PORT)
+    let instance = Instance::new(daemon_config.port.unwrap_or(PORT))
         .register("V_INFO", |_: Vec<Primitive>| {
             #[cfg(debug_assertions)]
             {
@@ -109,6 +184,8 @@ fn main() -> Result<(), ()> {
         })
         .register("NAME", |_: Vec<Primitive>| vec![PACKAGE_NAME.into()])
         .register("LOG", api::general::log_it())
+        .register("GENERAL_set_log_level", api::general::set_log_level())
+        .register("GENERAL_get_log_level", api::general::get_log_level())
         // battery API functions
         .register_async(
             "BATTERY_current_now",
@@ -226,6 +303,20 @@ fn main() -> Result<(), ()> {
             "GPU_get_slow_memory",
             api::gpu::get_slow_memory(api_sender.clone()),
         )
+        // fan API functions
+        .register("FAN_set_curve", api::fan::set_curve(api_sender.clone()))
+        .register_async("FAN_get_curve", api::fan::get_curve(api_sender.clone()))
+        .register("FAN_unset_curve", api::fan::unset_curve(api_sender.clone()))
+        // preset API functions
+        .register("PRESET_create", api::preset::create(api_sender.clone()))
+        .register("PRESET_update", api::preset::update(api_sender.clone()))
+        .register("PRESET_apply", api::preset::apply(api_sender.clone()))
+        .register("PRESET_delete", api::preset::delete(api_sender.clone()))
+        .register("PRESET_list", api::preset::list(api_sender.clone()))
+        .register(
+            "PRESET_get_current",
+            api::preset::get_current(api_sender.clone()),
+        )
         // general API functions
         .register(
             "GENERAL_set_persistent",
@@ -247,6 +338,54 @@ fn main() -> Result<(), ()> {
             "GENERAL_load_system_settings",
             api::general::load_system_settings(api_sender.clone()),
         )
+        .register(
+            "GENERAL_set_settings_json",
+            api::general::set_settings_json(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_settings_json",
+            api::general::get_settings_json(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_apply_report",
+            api::general::get_apply_report(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_errors",
+            api::general::get_errors(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_set_watchdog",
+            api::general::set_watchdog(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_watchdog",
+            api::general::get_watchdog(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_set_yield_to_system",
+            api::general::set_yield_to_system(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_yield_to_system",
+            api::general::get_yield_to_system(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_restore_stock",
+            api::general::restore_stock(api_sender.clone()),
+        )
+        .register_async(
+            "GENERAL_wait_event",
+            api::general::wait_event(events.clone()),
+        )
+        .register(
+            "GENERAL_set_validation_mode",
+            api::general::set_validation_mode(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_validation_mode",
+            api::general::get_validation_mode(api_sender.clone()),
+        )
         .register_async(
             "GENERAL_get_name",
             api::general::get_name(api_sender.clone()),
@@ -272,6 +411,56 @@ fn main() -> Result<(), ()> {
             "GENERAL_apply_now",
             api::general::force_apply(api_sender.clone()),
         )
+        .register(
+            "GENERAL_set_telemetry",
+            api::general::set_telemetry(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_telemetry",
+            api::general::get_telemetry(api_sender.clone()),
+        )
+        .register_blocking(
+            "GENERAL_export_telemetry",
+            api::general::export_telemetry(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_set_thermal_rules",
+            api::general::set_thermal_rules(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_thermal_rules",
+            api::general::get_thermal_rules(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_set_rules",
+            api::general::set_rules(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_rules",
+            api::general::get_rules(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_export_profile",
+            api::general::export_profile(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_import_profile",
+            api::general::import_profile(api_sender.clone()),
+        )
+        .register("GENERAL_undo", api::general::undo(api_sender.clone()))
+        .register("GENERAL_redo", api::general::redo(api_sender.clone()))
+        .register(
+            "GENERAL_get_history",
+            api::general::get_history(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_diff_profiles",
+            api::general::diff_profiles(api_sender.clone()),
+        )
+        .register(
+            "GENERAL_get_power_source",
+            api::general::get_power_source(api_sender.clone()),
+        )
         .register(
             "GENERAL_on_pluggedin",
             api::battery::on_plugged(api_sender.clone()),
@@ -281,6 +470,11 @@ fn main() -> Result<(), ()> {
             api::battery::on_unplugged(api_sender.clone()),
         );
 
+    let snapshot_path = utility::settings_dir().join(HARDWARE_SNAPSHOT_FILE);
+    settings::startup::load(&snapshot_path);
+    loaded_settings.snapshot();
+    settings::startup::save(&snapshot_path);
+
     if let Err(e) = loaded_settings.on_set() {
         e.iter()
             .for_each(|e| log::error!("Startup Settings.on_set() error: {}", e));
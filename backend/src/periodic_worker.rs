@@ -0,0 +1,27 @@
+//! Workers which ask api_worker to do something at a fixed period
+
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::api::handler::ApiMessage;
+
+pub const TELEMETRY_PERIOD: Duration = Duration::from_secs(10);
+pub const THERMAL_PERIOD: Duration = Duration::from_secs(2);
+pub const WATCHDOG_PERIOD: Duration = Duration::from_secs(5);
+
+/// Send `message()` to api_worker now, and every `period` after that
+pub fn spawn<F: Fn() -> ApiMessage + Send + 'static>(
+    name: &'static str,
+    period: Duration,
+    sender: Sender<ApiMessage>,
+    message: F,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        log::info!("{} starting...", name);
+        while sender.send(message()).is_ok() {
+            thread::sleep(period);
+        }
+        log::warn!("{} completed, api_worker is gone", name);
+    })
+}
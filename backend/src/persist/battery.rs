@@ -3,27 +3,18 @@ use std::default::Default;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct BatteryJson {
     pub charge_rate: Option<u64>,
     pub charge_mode: Option<String>,
+    /// Steam Deck battery events of older profiles, which are moved to the profile's triggers when it's loaded
     #[serde(default)]
     pub events: Vec<BatteryEventJson>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BatteryEventJson {
     pub trigger: String,
     pub charge_rate: Option<u64>,
     pub charge_mode: Option<String>,
 }
-
-impl Default for BatteryJson {
-    fn default() -> Self {
-        Self {
-            charge_rate: None,
-            charge_mode: None,
-            events: Vec::new(),
-        }
-    }
-}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Start-up options for running without Decky; anything missing keeps its default
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DaemonJson {
+    pub port: Option<u16>,
+    pub settings_dir: Option<PathBuf>,
+    pub log_path: Option<PathBuf>,
+    /// off, error, warn, info, debug or trace
+    pub log_level: Option<String>,
+    /// file, journald or stderr
+    pub log_to: Option<String>,
+    /// text or json
+    pub log_format: Option<String>,
+    /// Size in bytes the log file is rotated at; 0 only rotates on start
+    pub log_max_size: Option<u64>,
+    /// Rotated log files to keep
+    pub log_keep: Option<usize>,
+    /// User which owns the settings dir
+    pub user: Option<String>,
+    /// D-Bus to serve org.powertools.Manager on: system, session or a bus address
+    pub dbus: Option<String>,
+    /// Also serve net.hadess.PowerProfiles, for the desktop's power profile slider
+    pub power_profiles: Option<bool>,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use super::{DriverJson, SettingsJson};
+
+/// Drivers which were in use when a profile was exported
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProvidersJson {
+    pub general: DriverJson,
+    pub cpus: DriverJson,
+    pub gpu: DriverJson,
+    pub battery: DriverJson,
+    pub fan: DriverJson,
+}
+
+/// Profile shared with other devices, with what it was made on
+#[derive(Serialize, Deserialize)]
+pub struct ProfileExportJson {
+    /// Version of this document's format
+    pub format: u64,
+    pub powertools_version: String,
+    /// Name of the limits config which matched the device the profile was made on
+    pub device: Option<String>,
+    pub providers: ProvidersJson,
+    /// Steam app id of the game the profile is for, or none for the main profile
+    pub app_id: Option<u64>,
+    pub settings: SettingsJson,
+}
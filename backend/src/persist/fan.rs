@@ -0,0 +1,29 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+use super::ThermalSensorJson;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FanPointJson {
+    /// Degrees Celsius
+    pub temperature: f64,
+    /// Fan speed in driver units (RPM on the Steam Deck, PWM duty for generic hwmon fans)
+    pub speed: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FanJson {
+    /// Fan curve; firmware fan control is used when this is not set
+    pub curve: Option<Vec<FanPointJson>>,
+    pub sensor: ThermalSensorJson,
+}
+
+impl Default for FanJson {
+    fn default() -> Self {
+        Self {
+            curve: None,
+            sensor: ThermalSensorJson::Cpu,
+        }
+    }
+}
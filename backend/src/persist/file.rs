@@ -0,0 +1,16 @@
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::JsonError;
+
+/// Write `value` to a settings file, which the settings user owns if it's new
+pub fn save_json<T: Serialize, P: AsRef<Path>>(value: &T, path: P) -> Result<(), JsonError> {
+    let mut file = crate::utility::create_settings_file(path).map_err(JsonError::Io)?;
+    serde_json::to_writer_pretty(&mut file, value).map_err(JsonError::Serde)
+}
+
+pub fn open_json<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, JsonError> {
+    let mut file = std::fs::File::open(path).map_err(JsonError::Io)?;
+    serde_json::from_reader(&mut file).map_err(JsonError::Serde)
+}
@@ -3,9 +3,12 @@ use std::default::Default;
 use serde::{Deserialize, Serialize};
 
 use super::JsonError;
-use super::{BatteryJson, CpuJson, DriverJson, GpuJson};
+use super::{
+    BatteryJson, CpuJson, DriverJson, FanJson, GpuJson, PowerSourcesJson, ThermalRuleJson,
+    TriggerJson,
+};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SettingsJson {
     pub version: u64,
     pub name: String,
@@ -13,7 +16,20 @@ pub struct SettingsJson {
     pub cpus: Vec<CpuJson>,
     pub gpu: GpuJson,
     pub battery: BatteryJson,
+    #[serde(default)]
+    pub fan: FanJson,
     pub provider: Option<DriverJson>,
+    #[serde(default)]
+    pub thermal: Vec<ThermalRuleJson>,
+    /// Preset whose values are used instead of the ones in this file
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Setting changes made on power events and resume
+    #[serde(default)]
+    pub triggers: Vec<TriggerJson>,
+    /// CPU and GPU values for when plugged in or on battery, instead of `cpus` and `gpu`
+    #[serde(default)]
+    pub power_sources: PowerSourcesJson,
 }
 
 impl Default for SettingsJson {
@@ -25,7 +41,12 @@ impl Default for SettingsJson {
             cpus: Vec::with_capacity(8),
             gpu: GpuJson::default(),
             battery: BatteryJson::default(),
+            fan: FanJson::default(),
             provider: None,
+            thermal: Vec::new(),
+            preset: None,
+            triggers: Vec::new(),
+            power_sources: PowerSourcesJson::default(),
         }
     }
 }
@@ -35,11 +56,7 @@ impl SettingsJson {
         let path = path.as_ref();
 
         if self.persistent {
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent).map_err(JsonError::Io)?;
-            }
-            let mut file = std::fs::File::create(path).map_err(JsonError::Io)?;
-            serde_json::to_writer_pretty(&mut file, &self).map_err(JsonError::Serde)
+            super::save_json(self, path)
         } else {
             if path.exists() {
                 // remove settings file when persistence is turned off, to prevent it from be loaded next time.
@@ -51,12 +68,11 @@ impl SettingsJson {
     }
 
     pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, JsonError> {
-        let mut file = std::fs::File::open(path).map_err(JsonError::Io)?;
-        serde_json::from_reader(&mut file).map_err(JsonError::Serde)
+        super::open_json(path)
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct MinMaxJson<T> {
     pub max: T,
     pub min: T,
@@ -4,21 +4,10 @@ use std::default::Default;
 use super::MinMaxJson;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct GpuJson {
     pub fast_ppt: Option<u64>,
     pub slow_ppt: Option<u64>,
     pub clock_limits: Option<MinMaxJson<u64>>,
     pub slow_memory: bool,
 }
-
-impl Default for GpuJson {
-    fn default() -> Self {
-        Self {
-            fast_ppt: None,
-            slow_ppt: None,
-            clock_limits: None,
-            slow_memory: false,
-        }
-    }
-}
@@ -0,0 +1,39 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+/// What made a change to a profile
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSourceJson {
+    /// Front-end or other API client
+    Api,
+    /// Power events, resume or triggers
+    Event,
+    /// Profile switching rule
+    Rule,
+}
+
+/// One changed value; `field` is a JSON pointer into the settings document, like `/gpu/fast_ppt`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChangeJson {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntryJson {
+    /// Seconds since UNIX epoch
+    pub time: u64,
+    pub source: ChangeSourceJson,
+    pub changes: Vec<ChangeJson>,
+}
+
+/// Changes made to one profile, oldest first
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HistoryJson {
+    pub entries: Vec<HistoryEntryJson>,
+    /// Number of entries in effect; the ones after it were undone and can be redone
+    pub position: usize,
+}
@@ -1,14 +1,44 @@
 mod battery;
 mod cpu;
+mod daemon;
 mod driver;
 mod error;
+mod export;
+mod fan;
+mod file;
 mod general;
 mod gpu;
+mod history;
+#[cfg(feature = "dbus")]
+mod power_profiles;
+mod power_source;
+mod rules;
+mod snapshot;
+mod telemetry;
+mod thermal;
+mod triggers;
+mod validation;
+mod watchdog;
 
-pub use battery::{BatteryEventJson, BatteryJson};
+pub use battery::BatteryJson;
 pub use cpu::CpuJson;
+pub use daemon::DaemonJson;
 pub use driver::DriverJson;
+pub use export::{ProfileExportJson, ProvidersJson};
+pub use fan::{FanJson, FanPointJson};
+pub use file::{open_json, save_json};
 pub use general::{MinMaxJson, SettingsJson};
 pub use gpu::GpuJson;
+pub use history::{ChangeJson, ChangeSourceJson, HistoryEntryJson, HistoryJson};
+#[cfg(feature = "dbus")]
+pub use power_profiles::{PowerProfilesJson, ProfileTargetJson};
+pub use power_source::{PowerSourceJson, PowerSourceValuesJson, PowerSourcesJson};
+pub use rules::{ConditionsJson, RuleJson, RuleTargetJson, RulesJson};
+pub use snapshot::HardwareSnapshotJson;
+pub use telemetry::TelemetryJson;
+pub use thermal::{ThermalActionJson, ThermalRuleJson, ThermalSensorJson};
+pub use triggers::{TriggerActionJson, TriggerJson};
+pub use validation::{ValidationJson, ValidationMode};
+pub use watchdog::WatchdogJson;
 
 pub use error::JsonError;
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// PowerTools profile to load for a power-profiles-daemon profile
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProfileTargetJson {
+    /// App id of the profile, or none for the main profile
+    pub id: Option<u64>,
+    pub name: String,
+}
+
+/// Mapping of the desktop's power-saver, balanced and performance profiles;
+/// a profile with no target only changes what the desktop shows
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct PowerProfilesJson {
+    pub active: String,
+    pub power_saver: Option<ProfileTargetJson>,
+    pub balanced: Option<ProfileTargetJson>,
+    pub performance: Option<ProfileTargetJson>,
+}
+
+impl Default for PowerProfilesJson {
+    fn default() -> Self {
+        Self {
+            active: "balanced".to_owned(),
+            power_saver: None,
+            balanced: None,
+            performance: None,
+        }
+    }
+}
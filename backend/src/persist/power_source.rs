@@ -0,0 +1,44 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+use super::{CpuJson, GpuJson};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSourceJson {
+    /// Plugged in to a charger
+    Ac,
+    Battery,
+}
+
+/// CPU and GPU values used instead of the profile's own while on one power source
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PowerSourceValuesJson {
+    pub cpus: Vec<CpuJson>,
+    pub gpu: GpuJson,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PowerSourcesJson {
+    #[serde(default)]
+    pub ac: Option<PowerSourceValuesJson>,
+    #[serde(default)]
+    pub battery: Option<PowerSourceValuesJson>,
+}
+
+impl PowerSourcesJson {
+    pub fn get(&self, source: PowerSourceJson) -> Option<&PowerSourceValuesJson> {
+        match source {
+            PowerSourceJson::Ac => self.ac.as_ref(),
+            PowerSourceJson::Battery => self.battery.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, source: PowerSourceJson) -> &mut Option<PowerSourceValuesJson> {
+        match source {
+            PowerSourceJson::Ac => &mut self.ac,
+            PowerSourceJson::Battery => &mut self.battery,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+use super::PowerSourceJson;
+
+/// Local time of day range, as "HH:MM"; `to` may be before `from` to wrap past midnight
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TimeRangeJson {
+    pub from: String,
+    pub to: String,
+}
+
+/// Conditions which must all hold for a rule to match; unset conditions always hold
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ConditionsJson {
+    #[serde(default)]
+    pub power: Option<PowerSourceJson>,
+    /// Whether an external display (e.g. a dock) is connected
+    #[serde(default)]
+    pub external_display: Option<bool>,
+    /// Battery charge percentage (0 to 100) below which the rule matches
+    #[serde(default)]
+    pub battery_below: Option<f64>,
+    #[serde(default)]
+    pub time: Option<TimeRangeJson>,
+}
+
+/// What to load when a rule starts matching
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTargetJson {
+    /// Profile of an app id, or the main profile without one
+    Profile { id: Option<u64>, name: String },
+    /// Apply a preset on top of the loaded profile without saving it, until another profile is loaded
+    Preset(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RuleJson {
+    pub name: String,
+    pub when: ConditionsJson,
+    pub target: RuleTargetJson,
+}
+
+/// Rules for switching profiles automatically; the first matching rule wins
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RulesJson {
+    pub enabled: bool,
+    pub rules: Vec<RuleJson>,
+}
@@ -0,0 +1,12 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Hardware values from before PowerTools changed anything
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HardwareSnapshotJson {
+    /// Kernel boot id the snapshot was taken during; hardware goes back to stock on reboot
+    pub boot_id: String,
+    /// sysfs path (or driver-specific key) -> value
+    pub values: BTreeMap<String, String>,
+}
@@ -0,0 +1,19 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TelemetryJson {
+    pub enabled: bool,
+    /// Maximum amount of samples kept on disk before the oldest is overwritten
+    pub capacity: u32,
+}
+
+impl Default for TelemetryJson {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 8640, // 1 day @ 1 sample per 10s
+        }
+    }
+}
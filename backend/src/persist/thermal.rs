@@ -0,0 +1,42 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermalSensorJson {
+    /// CPU package temperature (Tctl on AMD)
+    Cpu,
+    /// GPU edge temperature
+    Gpu,
+    Battery,
+}
+
+/// Limits applied while a thermal rule is active; unset values are left alone
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ThermalActionJson {
+    #[serde(default)]
+    pub fast_ppt: Option<u64>,
+    #[serde(default)]
+    pub slow_ppt: Option<u64>,
+    #[serde(default)]
+    pub cpu_clock_max: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ThermalRuleJson {
+    pub sensor: ThermalSensorJson,
+    /// Temperature (degrees Celsius) at or above which the rule triggers
+    pub threshold: f64,
+    /// Seconds the temperature must stay past the threshold (or below it, minus hysteresis) before the rule changes state
+    #[serde(default)]
+    pub duration: u64,
+    /// Degrees Celsius below the threshold the temperature must fall to before restoring
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f64,
+    pub action: ThermalActionJson,
+}
+
+fn default_hysteresis() -> f64 {
+    5.0
+}
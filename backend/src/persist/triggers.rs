@@ -0,0 +1,34 @@
+use std::default::Default;
+
+use serde::{Deserialize, Serialize};
+
+use super::MinMaxJson;
+
+/// Setting changes made when a trigger fires; unset values are left alone
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TriggerActionJson {
+    #[serde(default)]
+    pub fast_ppt: Option<u64>,
+    #[serde(default)]
+    pub slow_ppt: Option<u64>,
+    #[serde(default)]
+    pub gpu_clock_limits: Option<MinMaxJson<u64>>,
+    #[serde(default)]
+    pub slow_memory: Option<bool>,
+    #[serde(default)]
+    pub smt: Option<bool>,
+    /// Governor of all online CPUs
+    #[serde(default)]
+    pub cpu_governor: Option<String>,
+    #[serde(default)]
+    pub charge_rate: Option<u64>,
+    #[serde(default)]
+    pub charge_mode: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TriggerJson {
+    /// `plug-in`, `plug-out`, `resume`, or a battery percentage like `>80` or `<20`
+    pub trigger: String,
+    pub action: TriggerActionJson,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// What to do with a value outside of the limits of the current drivers
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationMode {
+    /// Refuse the new value and report why to the caller
+    #[default]
+    Reject,
+    /// Adjust the new value to the nearest allowed one
+    Clamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ValidationJson {
+    pub mode: ValidationMode,
+}
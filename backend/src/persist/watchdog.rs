@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// The watchdog is off by default, since it polls the hardware
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WatchdogJson {
+    pub enabled: bool,
+    /// Settings (battery, cpu, gpu or fan) which are left alone when another process changes them
+    pub yield_to_system: Vec<String>,
+}
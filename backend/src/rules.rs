@@ -0,0 +1,269 @@
+//! Automatic profile switching from conditions like AC power, a connected dock or the time of day.
+//! Rules only switch when a different rule starts matching, so a profile loaded on purpose
+//! (e.g. on game launch) stays until the conditions change.
+
+use std::path::PathBuf;
+
+use crate::persist::{ConditionsJson, PowerSourceJson, RuleJson, RulesJson};
+use crate::settings::TBattery;
+
+const DRM_DIR: &str = "/sys/class/drm";
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+/// Connector types of built-in screens
+const INTERNAL_CONNECTORS: &[&str] = &["eDP", "LVDS", "DSI"];
+
+/// State of the device which rules are matched against
+#[derive(Debug, Default)]
+pub struct Conditions {
+    pub plugged_in: Option<bool>,
+    /// Battery fill amount: 0 = empty, 1 = full
+    pub battery: Option<f64>,
+    pub external_display: bool,
+    /// Minutes since local midnight
+    pub minute_of_day: Option<u32>,
+}
+
+impl Conditions {
+    pub fn read(plugged_in: Option<bool>, battery: &dyn TBattery) -> Self {
+        Self {
+            plugged_in: plugged_in.or_else(mains_online),
+            battery: battery
+                .read_charge_now()
+                .zip(battery.read_charge_full())
+                .map(|(now, full)| now / full),
+            external_display: external_display_connected(),
+            minute_of_day: local_minute_of_day(),
+        }
+    }
+}
+
+/// Whether a display is connected to anything other than a built-in screen connector
+pub fn external_display_connected() -> bool {
+    let entries = match std::fs::read_dir(DRM_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.filter_map(|entry| entry.ok()).any(|entry| {
+        // connectors are named like card0-eDP-1 or card1-HDMI-A-1
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let connector = match name.split_once('-') {
+            Some((card, connector)) if card.starts_with("card") => connector.to_owned(),
+            _ => return false,
+        };
+        !INTERNAL_CONNECTORS
+            .iter()
+            .any(|internal| connector.starts_with(internal))
+            && usdpl_back::api::files::read_single::<_, String, _>(entry.path().join("status"))
+                .map(|status| status.trim() == "connected")
+                .unwrap_or(false)
+    })
+}
+
+/// Whether a mains power supply is online, for drivers which don't report plugging in
+pub fn mains_online() -> Option<bool> {
+    let mut online = None;
+    for entry in std::fs::read_dir(POWER_SUPPLY_DIR)
+        .ok()?
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        let kind: String = match usdpl_back::api::files::read_single(path.join("type")) {
+            Ok(kind) => kind,
+            Err(_) => continue,
+        };
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        if let Ok(value) = usdpl_back::api::files::read_single::<_, String, _>(path.join("online"))
+        {
+            online = Some(online.unwrap_or(false) || value.trim() == "1");
+        }
+    }
+    online
+}
+
+fn local_minute_of_day() -> Option<u32> {
+    let tm = crate::utility::local_time()?;
+    Some(tm.tm_hour as u32 * 60 + tm.tm_min as u32)
+}
+
+/// Parse "HH:MM" into minutes since midnight
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+fn matches(when: &ConditionsJson, conditions: &Conditions) -> bool {
+    let power = match when.power {
+        None => true,
+        Some(PowerSourceJson::Ac) => conditions.plugged_in == Some(true),
+        Some(PowerSourceJson::Battery) => conditions.plugged_in == Some(false),
+    };
+    let display = when
+        .external_display
+        .map(|connected| connected == conditions.external_display)
+        .unwrap_or(true);
+    let battery = when
+        .battery_below
+        .map(|percent| {
+            conditions
+                .battery
+                .map(|charge| charge * 100.0 < percent)
+                .unwrap_or(false)
+        })
+        .unwrap_or(true);
+    let time = when
+        .time
+        .as_ref()
+        .map(|range| {
+            match (
+                parse_time(&range.from),
+                parse_time(&range.to),
+                conditions.minute_of_day,
+            ) {
+                (Some(from), Some(to), Some(now)) if from <= to => from <= now && now < to,
+                (Some(from), Some(to), Some(now)) => now >= from || now < to,
+                _ => false,
+            }
+        })
+        .unwrap_or(true);
+    power && display && battery && time
+}
+
+/// Problems with rules which would stop them from ever matching
+fn check(config: &RulesJson) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for rule in &config.rules {
+        if let Some(range) = &rule.when.time {
+            for time in [&range.from, &range.to] {
+                if parse_time(time).is_none() {
+                    errors.push(format!("Rule `{}` time `{}` is not HH:MM", rule.name, time));
+                }
+            }
+        }
+        if let Some(percent) = rule.when.battery_below {
+            if !(0.0..=100.0).contains(&percent) {
+                errors.push(format!(
+                    "Rule `{}` battery percentage {} is not between 0 and 100",
+                    rule.name, percent
+                ));
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub struct Rules {
+    config: RulesJson,
+    /// Index of the rule which matched last time
+    active: Option<usize>,
+}
+
+impl Rules {
+    /// Load rules from the settings dir, falling back to defaults (disabled)
+    pub fn load() -> Self {
+        Self {
+            config: crate::persist::open_json(Self::config_path()).unwrap_or_default(),
+            active: None,
+        }
+    }
+
+    fn config_path() -> PathBuf {
+        crate::utility::settings_dir().join(crate::consts::RULES_CONFIG_FILE)
+    }
+
+    fn save(&self) {
+        if let Err(e) = crate::persist::save_json(&self.config, Self::config_path()) {
+            log::error!("Failed to save rules config: {}", e);
+        }
+    }
+
+    pub fn json(&self) -> RulesJson {
+        self.config.clone()
+    }
+
+    /// Replace all rules; they're checked from scratch next time
+    pub fn set(&mut self, config: RulesJson) -> Result<(), Vec<String>> {
+        check(&config)?;
+        self.config = config;
+        self.active = None;
+        self.save();
+        Ok(())
+    }
+
+    /// The first matching rule, if it isn't the same one as last time
+    pub fn evaluate(&mut self, conditions: &Conditions) -> Option<&RuleJson> {
+        if !self.config.enabled {
+            return None;
+        }
+        let matching = self
+            .config
+            .rules
+            .iter()
+            .position(|rule| matches(&rule.when, conditions));
+        if matching == self.active {
+            return None;
+        }
+        self.active = matching;
+        matching.map(|i| &self.config.rules[i])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RULES: &str = r#"{
+        "enabled": true,
+        "rules": [
+            {
+                "name": "Low",
+                "when": { "power": "battery", "battery_below": 20 },
+                "target": { "preset": "Battery saver" }
+            },
+            {
+                "name": "Night",
+                "when": { "time": { "from": "22:00", "to": "7:30" } },
+                "target": { "profile": { "id": null, "name": "Main" } }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn first_match_wins_once_test() {
+        let mut rules = Rules {
+            config: serde_json::from_str(RULES).unwrap(),
+            active: None,
+        };
+        let mut conditions = Conditions {
+            plugged_in: Some(false),
+            battery: Some(0.5),
+            external_display: false,
+            minute_of_day: Some(23 * 60),
+        };
+        assert_eq!(rules.evaluate(&conditions).unwrap().name, "Night");
+        // still the same rule, so nothing to switch to
+        assert!(rules.evaluate(&conditions).is_none());
+        conditions.battery = Some(0.1);
+        assert_eq!(rules.evaluate(&conditions).unwrap().name, "Low");
+        conditions.plugged_in = Some(true);
+        conditions.minute_of_day = Some(12 * 60);
+        assert!(rules.evaluate(&conditions).is_none());
+        assert!(rules.active.is_none());
+        conditions.minute_of_day = Some(7 * 60);
+        assert_eq!(rules.evaluate(&conditions).unwrap().name, "Night");
+
+        let mut bad = rules.json();
+        bad.rules[1].when.time.as_mut().unwrap().to = "25:00".to_owned();
+        assert_eq!(check(&bad).unwrap_err().len(), 1);
+    }
+}
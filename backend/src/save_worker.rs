@@ -0,0 +1,181 @@
+//! Writes profiles and their change history to disk once they stop changing, instead of on every change.
+//! Changes in quick succession (like dragging a slider) are coalesced into one write per file.
+//! Pending writes are flushed before profiles are read back, on shutdown, and before the system sleeps
+//! (with the dbus feature; without it, they are written once the system wakes up again).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::persist::{HistoryJson, SettingsJson};
+
+/// How long a profile has to stay the same before it is written
+pub const DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub enum SaveMessage {
+    /// Write a profile, or delete its file when it isn't persistent (none)
+    Save(PathBuf, Option<Box<SettingsJson>>),
+    /// Write the change history of a profile
+    SaveHistory(PathBuf, Box<HistoryJson>),
+    /// Write everything pending right away
+    Flush(Box<dyn FnOnce(()) + Send>),
+}
+
+enum Document {
+    Settings(Option<Box<SettingsJson>>),
+    History(Box<HistoryJson>),
+}
+
+struct Pending {
+    due: Instant,
+    document: Document,
+}
+
+/// Write everything pending, and wait for it to be written
+pub fn flush(sender: &Sender<SaveMessage>) {
+    let (tx, rx) = mpsc::channel();
+    let callback = move |_: ()| tx.send(()).unwrap_or(());
+    if sender.send(SaveMessage::Flush(Box::new(callback))).is_err() || rx.recv().is_err() {
+        log::error!("Failed to flush saves, save_worker is gone");
+    }
+}
+
+fn write(path: &Path, document: Document) {
+    let json = match document {
+        Document::Settings(json) => json,
+        Document::History(journal) => {
+            match crate::persist::save_json(&journal, path) {
+                Ok(()) => log::debug!("Saved history to {}", path.display()),
+                Err(e) => log::error!("Failed to save history to {}: {}", path.display(), e),
+            }
+            return;
+        }
+    };
+    match json {
+        Some(json) => match json.save(path) {
+            Ok(()) => log::debug!("Saved settings to {}", path.display()),
+            Err(e) => log::error!("Failed to save settings to {}: {}", path.display(), e),
+        },
+        None if path.exists() => {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::warn!(
+                    "Failed to delete persistent settings file {}: {}",
+                    path.display(),
+                    e
+                );
+            } else {
+                log::debug!("Deleted persistent settings file {}", path.display());
+            }
+        }
+        None => log::debug!("Ignored save request for non-persistent settings"),
+    }
+}
+
+pub fn spawn() -> (JoinHandle<()>, Sender<SaveMessage>) {
+    let (sender, receiver) = mpsc::channel();
+    #[cfg(feature = "dbus")]
+    spawn_sleep_listener(sender.clone());
+    let handle = thread::spawn(move || {
+        log::info!("save_worker starting...");
+        let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+        loop {
+            let message = match pending.values().map(|p| p.due).min() {
+                Some(due) => {
+                    match receiver.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                        Ok(message) => Some(message),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match receiver.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => break,
+                },
+            };
+            match message {
+                Some(SaveMessage::Save(path, json)) => {
+                    // a newer version of the same profile replaces the pending one, and waits again
+                    pending.insert(
+                        path,
+                        Pending {
+                            due: Instant::now() + DEBOUNCE,
+                            document: Document::Settings(json),
+                        },
+                    );
+                }
+                Some(SaveMessage::SaveHistory(path, journal)) => {
+                    pending.insert(
+                        path,
+                        Pending {
+                            due: Instant::now() + DEBOUNCE,
+                            document: Document::History(journal),
+                        },
+                    );
+                }
+                Some(SaveMessage::Flush(callback)) => {
+                    for (path, p) in pending.drain() {
+                        write(&path, p.document);
+                    }
+                    callback(());
+                }
+                None => {
+                    let now = Instant::now();
+                    let due: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, p)| p.due <= now)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in due {
+                        if let Some(p) = pending.remove(&path) {
+                            write(&path, p.document);
+                        }
+                    }
+                }
+            }
+        }
+        for (path, p) in pending.drain() {
+            write(&path, p.document);
+        }
+        log::warn!("save_worker completed!");
+    });
+    (handle, sender)
+}
+
+#[cfg(feature = "dbus")]
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login1Manager {
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Flush pending saves when logind says the system is about to sleep
+#[cfg(feature = "dbus")]
+fn spawn_sleep_listener(sender: Sender<SaveMessage>) {
+    thread::spawn(move || {
+        let signals = zbus::blocking::Connection::system().and_then(|connection| {
+            Login1ManagerProxyBlocking::new(&connection)?.receive_prepare_for_sleep()
+        });
+        let signals = match signals {
+            Ok(signals) => signals,
+            Err(e) => {
+                log::warn!(
+                    "Not flushing saves before sleep, can't listen to logind: {}",
+                    e
+                );
+                return;
+            }
+        };
+        for signal in signals {
+            if signal.args().map(|args| args.start).unwrap_or(false) {
+                log::info!("System is going to sleep, flushing saves");
+                flush(&sender);
+            }
+        }
+    });
+}
@@ -1,11 +1,20 @@
 use std::fs::File;
+use std::sync::Mutex;
 
 use regex::RegexBuilder;
 
-use limits_core::json::{BatteryLimit, CpuLimit, GpuLimit, Limits};
+use limits_core::json::{BatteryLimit, CpuLimit, FanLimit, GpuLimit, Limits};
 
 use crate::persist::{DriverJson, SettingsJson};
-use crate::settings::{Driver, General, TBattery, TCpus, TGeneral, TGpu};
+use crate::settings::{Driver, General, TBattery, TCpus, TFan, TGeneral, TGpu};
+
+/// Name of the last limits config which matched this device
+static DETECTED_CONFIG: Mutex<Option<String>> = Mutex::new(None);
+
+/// Name of the limits config which matched this device (the last one, if several did), once detection ran
+pub fn detected_config() -> Option<String> {
+    DETECTED_CONFIG.lock().unwrap().clone()
+}
 
 fn get_limits() -> limits_core::json::Base {
     let limits_path = super::utility::limits_path();
@@ -109,6 +118,7 @@ pub fn auto_detect0(
             }
         }
         if matches {
+            *DETECTED_CONFIG.lock().unwrap() = Some(conf.name.clone());
             if let Some(settings) = &settings_opt {
                 *builder.general.persistent() = true;
                 builder.general.name(settings.name.clone());
@@ -215,6 +225,31 @@ pub fn auto_detect0(
                             };
                             builder.battery = Some(driver);
                         }
+                        Limits::Fan(fan) => {
+                            let driver: Box<dyn TFan> = match fan {
+                                FanLimit::SteamDeck => {
+                                    Box::new(crate::settings::steam_deck::Fan::from_json(
+                                        settings.fan.clone(),
+                                        settings.version,
+                                    ))
+                                }
+                                FanLimit::SteamDeckAdvance => {
+                                    Box::new(crate::settings::steam_deck::Fan::from_json(
+                                        settings.fan.clone(),
+                                        settings.version,
+                                    ))
+                                }
+                                FanLimit::Generic(x) => Box::new(
+                                    crate::settings::generic::Fan::from_json_and_limits(
+                                        settings.fan.clone(),
+                                        settings.version,
+                                        x,
+                                    ),
+                                ),
+                                FanLimit::Unknown => Box::new(crate::settings::unknown::Fan),
+                            };
+                            builder.fan = Some(driver);
+                        }
                     }
                 }
             } else {
@@ -279,6 +314,21 @@ pub fn auto_detect0(
                             };
                             builder.battery = Some(driver);
                         }
+                        Limits::Fan(fan) => {
+                            let driver: Box<dyn TFan> = match fan {
+                                FanLimit::SteamDeck => {
+                                    Box::new(crate::settings::steam_deck::Fan::system_default())
+                                }
+                                FanLimit::SteamDeckAdvance => {
+                                    Box::new(crate::settings::steam_deck::Fan::system_default())
+                                }
+                                FanLimit::Generic(x) => {
+                                    Box::new(crate::settings::generic::Fan::from_limits(x))
+                                }
+                                FanLimit::Unknown => Box::new(crate::settings::unknown::Fan),
+                            };
+                            builder.fan = Some(driver);
+                        }
                     }
                 }
             }
@@ -293,6 +343,7 @@ struct DriverBuilder {
     cpus: Option<Box<dyn TCpus>>,
     gpu: Option<Box<dyn TGpu>>,
     battery: Option<Box<dyn TBattery>>,
+    fan: Option<Box<dyn TFan>>,
 }
 
 impl DriverBuilder {
@@ -307,6 +358,7 @@ impl DriverBuilder {
             cpus: None,
             gpu: None,
             battery: None,
+            fan: None,
         }
     }
 
@@ -326,6 +378,9 @@ impl DriverBuilder {
             battery: self
                 .battery
                 .unwrap_or_else(|| Box::new(crate::settings::unknown::Battery)),
+            fan: self
+                .fan
+                .unwrap_or_else(|| Box::new(crate::settings::unknown::Fan)),
         }
     }
 }
@@ -92,7 +92,7 @@ pub fn get_limits_cached() -> Base {
 #[cfg(feature = "online")]
 fn save_base(new_base: &Base, path: impl AsRef<std::path::Path>) {
     let limits_path = path.as_ref();
-    match std::fs::File::create(&limits_path) {
+    match crate::utility::create_settings_file(limits_path) {
         Ok(f) => match serde_json::to_writer_pretty(f, &new_base) {
             Ok(_) => log::info!("Successfully saved new limits to {}", limits_path.display()),
             Err(e) => log::error!(
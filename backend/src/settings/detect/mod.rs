@@ -2,4 +2,4 @@ mod auto_detect;
 pub mod limits_worker;
 mod utility;
 
-pub use auto_detect::{auto_detect0, auto_detect_provider};
+pub use auto_detect::{auto_detect0, auto_detect_provider, detected_config};
@@ -1,4 +1,4 @@
-use super::{auto_detect0, General, SettingError, TBattery, TCpus, TGeneral, TGpu};
+use super::{auto_detect0, General, SettingError, TBattery, TCpus, TFan, TGeneral, TGpu};
 use crate::persist::{DriverJson, SettingsJson};
 
 pub struct Driver {
@@ -6,6 +6,7 @@ pub struct Driver {
     pub cpus: Box<dyn TCpus>,
     pub gpu: Box<dyn TGpu>,
     pub battery: Box<dyn TBattery>,
+    pub fan: Box<dyn TFan>,
 }
 
 impl Driver {
@@ -34,6 +35,10 @@ impl Driver {
                     settings.battery,
                     settings.version,
                 )),
+                fan: Box::new(super::steam_deck::Fan::from_json(
+                    settings.fan,
+                    settings.version,
+                )),
             },
         })
     }
@@ -64,6 +69,10 @@ impl Driver {
                         settings.battery,
                         settings.version,
                     )),
+                    fan: Box::new(super::steam_deck::Fan::from_json(
+                        settings.fan,
+                        settings.version,
+                    )),
                 }),
                 // There's nothing special about SteamDeckAdvance, it just appears different
                 DriverJson::SteamDeckAdvance => Ok(Self {
@@ -85,6 +94,10 @@ impl Driver {
                         settings.battery,
                         settings.version,
                     )),
+                    fan: Box::new(super::steam_deck::Fan::from_json(
+                        settings.fan,
+                        settings.version,
+                    )),
                 }),
                 DriverJson::Generic | DriverJson::GenericAMD => {
                     Ok(super::detect::auto_detect0(Some(settings), json_path, name))
@@ -4,6 +4,7 @@ use super::SettingVariant;
 pub struct SettingError {
     pub msg: String,
     pub setting: SettingVariant,
+    pub kind: ErrorKind,
 }
 
 impl std::fmt::Display for SettingError {
@@ -11,3 +12,83 @@ impl std::fmt::Display for SettingError {
         write!(f, "{} setting error: {}", self.setting, self.msg)
     }
 }
+
+/// Machine-readable cause of a SettingError, so the UI can suggest what to do about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Not allowed to change the value (e.g. not running as root)
+    Permission,
+    /// The hardware or kernel doesn't support the setting
+    Unsupported,
+    /// Reading or writing the value failed
+    Io,
+    /// The value is outside of what the hardware or kernel accepts
+    OutOfRange,
+    /// The value is within range, but not a multiple of the step size
+    OffStep,
+    /// The minimum of a min-max pair is greater than its maximum
+    MinOverMax,
+    /// The value is not one of the available options (e.g. an unknown governor)
+    UnknownOption,
+    /// There is no item (e.g. CPU) at the given index
+    BadIndex,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Permission => write!(f, "permission"),
+            Self::Unsupported => write!(f, "unsupported"),
+            Self::Io => write!(f, "io"),
+            Self::OutOfRange => write!(f, "out-of-range"),
+            Self::OffStep => write!(f, "off-step"),
+            Self::MinOverMax => write!(f, "min-over-max"),
+            Self::UnknownOption => write!(f, "unknown-option"),
+            Self::BadIndex => write!(f, "bad-index"),
+        }
+    }
+}
+
+impl From<&std::io::Error> for ErrorKind {
+    fn from(e: &std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::Permission,
+            // missing sysfs files mean the driver doesn't provide the setting
+            std::io::ErrorKind::NotFound => Self::Unsupported,
+            // ...and so does ENODEV, e.g. for SMT control on CPUs without SMT
+            _ if e.raw_os_error() == Some(libc::ENODEV) => Self::Unsupported,
+            // sysfs rejects values it won't accept with EINVAL
+            std::io::ErrorKind::InvalidInput => Self::OutOfRange,
+            _ => Self::Io,
+        }
+    }
+}
+
+impl<E> From<&usdpl_back::api::files::ReadError<E>> for ErrorKind {
+    fn from(e: &usdpl_back::api::files::ReadError<E>) -> Self {
+        match e {
+            usdpl_back::api::files::ReadError::Io(e) => e.into(),
+            usdpl_back::api::files::ReadError::Parse(_) => Self::Io,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn io_error_kind_test() {
+        let kind = |k: std::io::ErrorKind| ErrorKind::from(&std::io::Error::from(k));
+        assert_eq!(
+            kind(std::io::ErrorKind::PermissionDenied),
+            ErrorKind::Permission
+        );
+        assert_eq!(kind(std::io::ErrorKind::NotFound), ErrorKind::Unsupported);
+        assert_eq!(
+            kind(std::io::ErrorKind::InvalidInput),
+            ErrorKind::OutOfRange
+        );
+        assert_eq!(kind(std::io::ErrorKind::Interrupted), ErrorKind::Io);
+    }
+}
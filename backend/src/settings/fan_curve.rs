@@ -0,0 +1,109 @@
+use crate::persist::{FanJson, FanPointJson, ThermalSensorJson};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FanPoint {
+    pub temperature: f64,
+    pub speed: u64,
+}
+
+/// Temperature to fan speed mapping, linearly interpolated between points
+#[derive(Debug, Clone)]
+pub struct FanCurve {
+    points: Vec<FanPoint>,
+    pub sensor: ThermalSensorJson,
+}
+
+impl FanCurve {
+    pub fn new(mut points: Vec<FanPoint>, sensor: ThermalSensorJson) -> Self {
+        points.retain(|p| p.temperature.is_finite());
+        points.sort_by(|a, b| a.temperature.total_cmp(&b.temperature));
+        Self { points, sensor }
+    }
+
+    pub fn from_json(points: Vec<FanPointJson>, sensor: ThermalSensorJson, _version: u64) -> Self {
+        Self::new(
+            points
+                .into_iter()
+                .map(|p| FanPoint {
+                    temperature: p.temperature,
+                    speed: p.speed,
+                })
+                .collect(),
+            sensor,
+        )
+    }
+
+    /// Convert an optional curve into json; no curve means firmware fan control
+    pub fn to_json(curve: Option<&Self>) -> FanJson {
+        match curve {
+            Some(curve) => FanJson {
+                curve: Some(
+                    curve
+                        .points
+                        .iter()
+                        .map(|p| FanPointJson {
+                            temperature: p.temperature,
+                            speed: p.speed,
+                        })
+                        .collect(),
+                ),
+                sensor: curve.sensor,
+            },
+            None => FanJson::default(),
+        }
+    }
+
+    pub fn clamp(&mut self, min: u64, max: u64) {
+        for point in self.points.iter_mut() {
+            point.speed = point.speed.clamp(min, max);
+        }
+    }
+
+    /// Fan speed for the given temperature, or None if the curve has no points
+    pub fn speed_at(&self, temperature: f64) -> Option<u64> {
+        let first = self.points.first()?;
+        if temperature <= first.temperature {
+            return Some(first.speed);
+        }
+        for pair in self.points.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if temperature <= high.temperature {
+                let ratio = (temperature - low.temperature) / (high.temperature - low.temperature);
+                let speed = low.speed as f64 + ratio * (high.speed as f64 - low.speed as f64);
+                return Some(speed.round() as u64);
+            }
+        }
+        self.points.last().map(|p| p.speed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curve_interpolation_test() {
+        let curve = FanCurve::new(
+            vec![
+                FanPoint {
+                    temperature: 80.0,
+                    speed: 6000,
+                },
+                FanPoint {
+                    temperature: 50.0,
+                    speed: 2000,
+                },
+                FanPoint {
+                    temperature: 60.0,
+                    speed: 3000,
+                },
+            ],
+            ThermalSensorJson::Cpu,
+        );
+        assert_eq!(curve.speed_at(20.0), Some(2000));
+        assert_eq!(curve.speed_at(55.0), Some(2500));
+        assert_eq!(curve.speed_at(70.0), Some(4500));
+        assert_eq!(curve.speed_at(95.0), Some(6000));
+        assert_eq!(FanCurve::new(vec![], ThermalSensorJson::Cpu).speed_at(50.0), None);
+    }
+}
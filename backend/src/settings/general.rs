@@ -2,27 +2,41 @@ use std::path::PathBuf;
 //use std::sync::{Arc, Mutex};
 
 //use super::{Battery, Cpus, Gpu};
-use super::{OnResume, OnSet, SettingError};
-use super::{TBattery, TCpus, TGeneral, TGpu};
-use crate::persist::SettingsJson;
+use super::transaction;
+use super::{ErrorKind, OnResume, OnSet, OnShutdown, OnVerify, SettingError};
+use super::{TBattery, TCpus, TFan, TGeneral, TGpu};
+use crate::persist::{PowerSourceJson, PowerSourceValuesJson, PowerSourcesJson, SettingsJson};
 //use crate::utility::unwrap_lock;
 
-const LATEST_VERSION: u64 = 0;
+pub const LATEST_VERSION: u64 = 0;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SettingVariant {
     Battery,
     Cpu,
     Gpu,
+    Fan,
     General,
 }
 
+impl SettingVariant {
+    /// Settings which are applied to hardware, in the order they are applied
+    pub const APPLIED: [Self; 5] = [
+        Self::General,
+        Self::Battery,
+        Self::Cpu,
+        Self::Gpu,
+        Self::Fan,
+    ];
+}
+
 impl std::fmt::Display for SettingVariant {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Battery => write!(f, "Battery"),
             Self::Cpu => write!(f, "CPU"),
             Self::Gpu => write!(f, "GPU"),
+            Self::Fan => write!(f, "Fan"),
             Self::General => write!(f, "General"),
         }
     }
@@ -90,57 +104,106 @@ pub struct Settings {
     pub cpus: Box<dyn TCpus>,
     pub gpu: Box<dyn TGpu>,
     pub battery: Box<dyn TBattery>,
+    pub fan: Box<dyn TFan>,
+    pub thermal: super::Thermal,
+    pub triggers: super::Triggers,
+    pub power_sources: super::PowerSources,
+    /// Latest power source from power events, which stays the same when loading another profile.
+    /// Until there is one, it's read from the mains power supply (see use_power_source()).
+    power_source: Option<PowerSourceJson>,
+    /// Preset this profile follows, until one of its values is changed
+    pub preset: Option<String>,
 }
 
 impl OnSet for Settings {
     fn on_set(&mut self) -> Result<(), Vec<SettingError>> {
+        match self.apply() {
+            (_, errors) if errors.is_empty() => Ok(()),
+            (_, errors) => Err(errors),
+        }
+    }
+}
+
+impl Settings {
+    /// Apply all settings as one transaction, which is rolled back if any of them fail.
+    pub fn apply(&mut self) -> (transaction::Outcome, Vec<SettingError>) {
+        self.apply_only(&SettingVariant::APPLIED)
+    }
+
+    /// Apply the settings of `variants` as one transaction, leaving the others alone.
+    /// The order matters: CPUs go before the GPU, since they share the DPM performance level.
+    pub fn apply_only(
+        &mut self,
+        variants: &[SettingVariant],
+    ) -> (transaction::Outcome, Vec<SettingError>) {
         let mut errors = Vec::new();
 
         log::debug!("Applying settings for on_set");
-        self.general
-            .on_set()
-            .unwrap_or_else(|mut e| errors.append(&mut e));
-        log::debug!("Set general");
-        self.battery
-            .on_set()
-            .unwrap_or_else(|mut e| errors.append(&mut e));
-        log::debug!("Set battery");
-        self.cpus
-            .on_set()
-            .unwrap_or_else(|mut e| errors.append(&mut e));
-        log::debug!("Set CPUs");
-        self.gpu
-            .on_set()
-            .unwrap_or_else(|mut e| errors.append(&mut e));
-        log::debug!("Set GPU");
+        transaction::begin();
+        for variant in SettingVariant::APPLIED {
+            if !variants.contains(&variant) {
+                continue;
+            }
+            let result = match variant {
+                SettingVariant::General => self.general.on_set(),
+                SettingVariant::Battery => self.battery.on_set(),
+                SettingVariant::Cpu => self.cpus.on_set(),
+                SettingVariant::Gpu => self.gpu.on_set(),
+                SettingVariant::Fan => self.fan.on_set(),
+            };
+            result.unwrap_or_else(|mut e| errors.append(&mut e));
+            log::debug!("Set {}", variant);
+        }
 
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        if !transaction::needs_rollback(&errors) {
+            transaction::commit();
+            return (transaction::Outcome::Committed, errors);
+        }
+        log::warn!("Applying settings failed, rolling back to the old values");
+        match transaction::rollback() {
+            Ok(()) => (transaction::Outcome::RolledBack, errors),
+            Err(mut e) => {
+                errors.append(&mut e);
+                (transaction::Outcome::RollbackFailed, errors)
+            }
         }
     }
-}
 
-impl Settings {
     #[inline]
     pub fn from_json(other: SettingsJson, json_path: PathBuf) -> Self {
+        let mut other = super::presets::resolve(other);
+        super::triggers::migrate_battery_events(&mut other);
         let name_bup = other.name.clone();
+        let preset = other.preset.clone();
+        let thermal = super::Thermal::from_json(other.thermal.clone());
+        let triggers = super::Triggers::from_json(other.triggers.clone());
+        let power_sources = super::PowerSources::from_json(other.power_sources.clone());
         match super::Driver::init(other, json_path.clone()) {
             Ok(x) => {
                 log::info!(
-                    "Loaded settings with drivers general:{:?},cpus:{:?},gpu:{:?},battery:{:?}",
+                    "Loaded settings with drivers general:{:?},cpus:{:?},gpu:{:?},battery:{:?},fan:{:?}",
                     x.general.provider(),
                     x.cpus.provider(),
                     x.gpu.provider(),
-                    x.battery.provider()
+                    x.battery.provider(),
+                    x.fan.provider()
                 );
-                Self {
+                let mut settings = Self {
                     general: x.general,
                     cpus: x.cpus,
                     gpu: x.gpu,
                     battery: x.battery,
+                    fan: x.fan,
+                    thermal,
+                    triggers,
+                    power_sources,
+                    power_source: None,
+                    preset,
+                };
+                if let Err(e) = settings.use_power_source() {
+                    log::error!("Failed to use values of the power source: {}", e);
                 }
+                settings
             }
             Err(e) => {
                 log::error!("Driver init error: {}", e);
@@ -156,6 +219,12 @@ impl Settings {
             cpus: driver.cpus,
             gpu: driver.gpu,
             battery: driver.battery,
+            fan: driver.fan,
+            thermal: super::Thermal::default(),
+            triggers: super::Triggers::default(),
+            power_sources: super::PowerSources::default(),
+            power_source: None,
+            preset: None,
         }
     }
 
@@ -164,7 +233,12 @@ impl Settings {
         self.cpus = driver.cpus;
         self.gpu = driver.gpu;
         self.battery = driver.battery;
+        self.fan = driver.fan;
         self.general = driver.general;
+        self.thermal = super::Thermal::default();
+        self.triggers = super::Triggers::default();
+        self.power_sources = super::PowerSources::default();
+        self.preset = None;
     }
 
     pub fn load_file(
@@ -178,7 +252,10 @@ impl Settings {
             let settings_json = SettingsJson::open(&json_path).map_err(|e| SettingError {
                 msg: e.to_string(),
                 setting: SettingVariant::General,
+                kind: ErrorKind::Io,
             })?;
+            let mut settings_json = super::presets::resolve(settings_json);
+            super::triggers::migrate_battery_events(&mut settings_json);
             if !settings_json.persistent {
                 log::warn!(
                     "Loaded persistent config `{}` ({}) with persistent=false",
@@ -188,13 +265,24 @@ impl Settings {
                 *self.general.persistent() = false;
                 self.general.name(name);
             } else {
+                let thermal = super::Thermal::from_json(settings_json.thermal.clone());
+                let triggers = super::Triggers::from_json(settings_json.triggers.clone());
+                let power_sources =
+                    super::PowerSources::from_json(settings_json.power_sources.clone());
+                let preset = settings_json.preset.clone();
                 match super::Driver::init(settings_json, json_path.clone()) {
                     Ok(x) => {
-                        log::info!("Loaded settings with drivers general:{:?},cpus:{:?},gpu:{:?},battery:{:?}", x.general.provider(), x.cpus.provider(), x.gpu.provider(), x.battery.provider());
+                        log::info!("Loaded settings with drivers general:{:?},cpus:{:?},gpu:{:?},battery:{:?},fan:{:?}", x.general.provider(), x.cpus.provider(), x.gpu.provider(), x.battery.provider(), x.fan.provider());
                         self.general = x.general;
                         self.cpus = x.cpus;
                         self.gpu = x.gpu;
                         self.battery = x.battery;
+                        self.fan = x.fan;
+                        self.thermal = thermal;
+                        self.triggers = triggers;
+                        self.power_sources = power_sources;
+                        self.preset = preset;
+                        self.use_power_source()?;
                     }
                     Err(e) => {
                         log::error!("Driver init error: {}", e);
@@ -225,6 +313,7 @@ impl Settings {
             let settings_json = SettingsJson::open(&json_path).map_err(|e| SettingError {
                 msg: e.to_string(),
                 setting: SettingVariant::General,
+                kind: ErrorKind::Io,
             })?;
             if !settings_json.persistent {
                 log::warn!("Loaded persistent config `{}` ({}) with persistent=false", &settings_json.name, json_path.display());
@@ -248,16 +337,150 @@ impl Settings {
         Ok(*self.general.persistent())
     }*/
 
+    /// Replace all settings with `settings_json`, keeping the current settings file and drivers,
+    /// since values are checked against the limits of the current drivers.
+    /// The values are not checked; see validate::settings_json for that.
+    pub fn load_json(&mut self, mut settings_json: SettingsJson) -> Result<(), SettingError> {
+        let provider = self.general.provider();
+        if settings_json
+            .provider
+            .as_ref()
+            .is_some_and(|p| *p != provider)
+        {
+            log::warn!(
+                "Ignoring provider {:?} of settings JSON, keeping {:?}",
+                settings_json.provider,
+                provider
+            );
+        }
+        settings_json.provider = Some(provider);
+        super::triggers::migrate_battery_events(&mut settings_json);
+        let thermal = super::Thermal::from_json(settings_json.thermal.clone());
+        let triggers = super::Triggers::from_json(settings_json.triggers.clone());
+        let power_sources = super::PowerSources::from_json(settings_json.power_sources.clone());
+        let preset = settings_json.preset.clone();
+        let x = super::Driver::init(settings_json, self.general.get_path().to_owned())?;
+        log::info!(
+            "Loaded settings JSON with drivers general:{:?},cpus:{:?},gpu:{:?},battery:{:?},fan:{:?}",
+            x.general.provider(),
+            x.cpus.provider(),
+            x.gpu.provider(),
+            x.battery.provider(),
+            x.fan.provider()
+        );
+        self.general = x.general;
+        self.cpus = x.cpus;
+        self.gpu = x.gpu;
+        self.battery = x.battery;
+        self.fan = x.fan;
+        self.thermal = thermal;
+        self.triggers = triggers;
+        self.power_sources = power_sources;
+        self.preset = preset;
+        self.use_power_source().map(|_| ())
+    }
+
+    /// Power source whose CPU and GPU values are in use, if the profile has values for it
+    pub fn power_source(&self) -> Option<PowerSourceJson> {
+        self.power_sources.in_use()
+    }
+
+    /// Switch to the CPU and GPU values of the latest power source.
+    /// Returns true when settings were modified and need to be applied.
+    fn use_power_source(&mut self) -> Result<bool, SettingError> {
+        if self.power_source.is_none() {
+            // only some drivers send plug events, and not before their first power check
+            self.power_source = crate::rules::mains_online().map(|online| {
+                if online {
+                    PowerSourceJson::Ac
+                } else {
+                    PowerSourceJson::Battery
+                }
+            });
+        }
+        let (thermal, triggers, cpus, gpu) = (
+            &mut self.thermal,
+            &mut self.triggers,
+            &mut self.cpus,
+            &mut self.gpu,
+        );
+        let values = match self.power_sources.switch(self.power_source, || {
+            // keep the user's values, not throttled ones; throttling starts again on the next check
+            thermal.restore(gpu.as_mut(), cpus.as_mut());
+            let mut values = PowerSourceValuesJson {
+                cpus: cpus.json(),
+                gpu: gpu.json(),
+            };
+            // the same goes for values of triggers, which fire again on the next plug event
+            triggers.unfired_values(&mut values);
+            values
+        }) {
+            Some(values) => values,
+            None => return Ok(false),
+        };
+        log::info!(
+            "Using CPU and GPU values for {:?}",
+            self.power_sources.in_use()
+        );
+        let json = SettingsJson {
+            version: LATEST_VERSION,
+            name: self.general.get_name().to_owned(),
+            persistent: self.general.get_persistent(),
+            cpus: values.cpus,
+            gpu: values.gpu,
+            battery: self.battery.json(),
+            fan: self.fan.json(),
+            provider: Some(self.general.provider()),
+            ..Default::default()
+        };
+        let x = super::Driver::init(json, self.general.get_path().to_owned())?;
+        self.cpus = x.cpus;
+        self.gpu = x.gpu;
+        Ok(true)
+    }
+
+    /// Change settings for the triggers `event` fires.
+    /// Returns true when settings were modified and need to be applied.
+    pub fn fire_triggers(&mut self, event: super::TriggerEvent) -> bool {
+        self.triggers.fire(
+            event,
+            self.gpu.as_mut(),
+            self.cpus.as_mut(),
+            self.battery.as_mut(),
+        )
+    }
+
+    pub fn limits(&self) -> crate::api::SettingsLimits {
+        crate::api::SettingsLimits {
+            battery: self.battery.limits(),
+            cpu: self.cpus.limits(),
+            gpu: self.gpu.limits(),
+            fan: self.fan.limits(),
+            general: self.general.limits(),
+        }
+    }
+
     pub fn json(&self) -> SettingsJson {
-        SettingsJson {
+        let mut json = SettingsJson {
             version: LATEST_VERSION,
             name: self.general.get_name().to_owned(),
             persistent: self.general.get_persistent(),
             cpus: self.cpus.json(),
             gpu: self.gpu.json(),
             battery: self.battery.json(),
+            fan: self.fan.json(),
             provider: Some(self.general.provider()),
-        }
+            thermal: self.thermal.json(),
+            preset: self.preset.clone(),
+            triggers: self.triggers.json(),
+            power_sources: PowerSourcesJson::default(), // filled in below
+        };
+        // trigger actions and thermal throttling are temporary, so the user's values are saved instead;
+        // triggers first, since their values may have been captured while throttled
+        self.triggers.unfired_json(&mut json);
+        self.thermal.unthrottled_json(&mut json);
+        self.power_sources.json(&mut json);
+        json
     }
 }
 
@@ -282,6 +505,58 @@ impl OnResume for Settings {
             .on_resume()
             .unwrap_or_else(|mut e| errors.append(&mut e));
         log::debug!("Resumed GPU");
+        self.fan
+            .on_resume()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        log::debug!("Resumed fan");
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl OnVerify for Settings {
+    fn verify(&self) -> Vec<crate::api::AppliedValue> {
+        let mut values = self.battery.verify();
+        values.append(&mut self.cpus.verify());
+        values.append(&mut self.gpu.verify());
+        values.append(&mut self.fan.verify());
+        values
+    }
+}
+
+impl OnShutdown for Settings {
+    fn snapshot(&self) {
+        self.fan.snapshot();
+        self.battery.snapshot();
+        self.gpu.snapshot();
+        self.cpus.snapshot();
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        let mut errors = Vec::new();
+
+        log::info!("Restoring startup hardware settings for on_shutdown");
+        // fan first, so the firmware is cooling again as soon as possible
+        self.fan
+            .on_shutdown()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        log::debug!("Shutdown fan");
+        self.battery
+            .on_shutdown()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        log::debug!("Shutdown battery");
+        self.gpu
+            .on_shutdown()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        log::debug!("Shutdown GPU");
+        self.cpus
+            .on_shutdown()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        log::debug!("Shutdown CPUs");
 
         if errors.is_empty() {
             Ok(())
@@ -295,6 +570,17 @@ impl crate::settings::OnPowerEvent for Settings {
     fn on_power_event(&mut self, new_mode: super::PowerMode) -> Result<(), Vec<SettingError>> {
         let mut errors = Vec::new();
 
+        let source = match new_mode {
+            super::PowerMode::PluggedIn => Some(PowerSourceJson::Ac),
+            super::PowerMode::PluggedOut => Some(PowerSourceJson::Battery),
+            super::PowerMode::BatteryCharge(_) => None,
+        };
+        if let Some(source) = source {
+            self.power_source = Some(source);
+            self.use_power_source()
+                .map(|_| ())
+                .unwrap_or_else(|e| errors.push(e));
+        }
         self.general
             .on_power_event(new_mode)
             .unwrap_or_else(|mut e| errors.append(&mut e));
@@ -307,6 +593,9 @@ impl crate::settings::OnPowerEvent for Settings {
         self.gpu
             .on_power_event(new_mode)
             .unwrap_or_else(|mut e| errors.append(&mut e));
+        self.fan
+            .on_power_event(new_mode)
+            .unwrap_or_else(|mut e| errors.append(&mut e));
 
         if errors.is_empty() {
             Ok(())
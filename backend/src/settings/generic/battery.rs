@@ -12,9 +12,9 @@ pub struct Battery {
     limits: GenericBatteryLimit,
 }
 
-impl Into<BatteryJson> for Battery {
+impl From<Battery> for BatteryJson {
     #[inline]
-    fn into(self) -> BatteryJson {
+    fn from(_val: Battery) -> Self {
         BatteryJson {
             charge_rate: None,
             charge_mode: None,
@@ -30,6 +30,7 @@ impl Battery {
             Err(e) => Err(SettingError {
                 msg: format!("Failed to read from `{}`: {}", path.display(), e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // this value is in uA, while it's set in mA
             // so convert this to mA for consistency
@@ -68,6 +69,10 @@ impl OnResume for Battery {
 
 impl crate::settings::OnPowerEvent for Battery {}
 
+impl crate::settings::OnShutdown for Battery {}
+
+impl crate::settings::OnVerify for Battery {}
+
 impl TBattery for Battery {
     fn limits(&self) -> crate::api::BatteryLimits {
         crate::api::BatteryLimits {
@@ -129,12 +134,6 @@ impl TBattery for Battery {
         None
     }
 
-    fn charge_limit(&mut self, _limit: Option<f64>) {}
-
-    fn get_charge_limit(&self) -> Option<f64> {
-        None
-    }
-
     fn provider(&self) -> crate::persist::DriverJson {
         crate::persist::DriverJson::Generic
     }
@@ -3,9 +3,12 @@ use std::convert::{AsMut, AsRef, Into};
 use limits_core::json::GenericCpuLimit;
 
 use super::FromGenericCpuInfo;
+use crate::api::AppliedValue;
 use crate::persist::CpuJson;
+use crate::settings::startup;
+use crate::settings::verify;
 use crate::settings::{min_max_from_json, MinMax};
-use crate::settings::{OnResume, OnSet, SettingError};
+use crate::settings::{OnResume, OnSet, OnShutdown, OnVerify, SettingError};
 use crate::settings::{TCpu, TCpus};
 
 const CPU_PRESENT_PATH: &str = "/sys/devices/system/cpu/present";
@@ -24,17 +27,19 @@ impl<C: AsMut<Cpu> + AsRef<Cpu> + TCpu + OnSet> OnSet for Cpus<C> {
         if self.smt_capable {
             // toggle SMT
             if self.smt {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "on")
+                startup::write_single(CPU_SMT_PATH, "on")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `on` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
             } else {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "off")
+                startup::write_single(CPU_SMT_PATH, "off")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `off` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
             }
@@ -66,6 +71,34 @@ impl<C: AsMut<Cpu> + AsRef<Cpu> + TCpu + OnResume> OnResume for Cpus<C> {
     }
 }
 
+impl<C: AsMut<Cpu> + AsRef<Cpu> + TCpu> OnVerify for Cpus<C> {
+    fn verify(&self) -> Vec<AppliedValue> {
+        let mut values: Vec<AppliedValue> = verify::smt(self.smt_capable, self.smt)
+            .into_iter()
+            .collect();
+        for cpu in &self.cpus {
+            let cpu = cpu.as_ref();
+            values.append(&mut verify::cpu(
+                cpu.index,
+                cpu.online,
+                cpu.state.do_set_online,
+                &cpu.governor,
+            ));
+        }
+        values
+    }
+}
+
+impl<C: AsMut<Cpu> + AsRef<Cpu> + TCpu> OnShutdown for Cpus<C> {
+    fn snapshot(&self) {
+        startup::remember_cpus(self.cpus.len());
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        startup::restore_cpus(self.cpus.len())
+    }
+}
+
 impl<C: AsMut<Cpu> + AsRef<Cpu> + TCpu + FromGenericCpuInfo> Cpus<C> {
     pub fn cpu_count() -> Option<usize> {
         let mut data: String = usdpl_back::api::files::read_single(CPU_PRESENT_PATH)
@@ -277,10 +310,11 @@ impl Cpu {
         if self.index != 0 && self.state.do_set_online {
             // cpu0 cannot be disabled
             let online_path = cpu_online_path(self.index);
-            usdpl_back::api::files::write_single(&online_path, self.online as u8)
+            startup::write_single(&online_path, self.online as u8)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to write to `{}`: {}", &online_path, e),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -288,13 +322,14 @@ impl Cpu {
         // set governor
         if self.index == 0 || self.online {
             let governor_path = cpu_governor_path(self.index);
-            usdpl_back::api::files::write_single(&governor_path, &self.governor)
+            startup::write_single(&governor_path, &self.governor)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         &self.governor, &governor_path, e
                     ),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -338,13 +373,13 @@ impl Cpu {
     }
 }
 
-impl Into<CpuJson> for Cpu {
+impl From<Cpu> for CpuJson {
     #[inline]
-    fn into(self) -> CpuJson {
+    fn from(other: Cpu) -> Self {
         CpuJson {
-            online: self.online,
-            clock_limits: self.clock_limits.map(|x| x.into()),
-            governor: self.governor,
+            online: other.online,
+            clock_limits: other.clock_limits.map(|x| x.into()),
+            governor: other.governor,
         }
     }
 }
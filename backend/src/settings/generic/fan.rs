@@ -0,0 +1,240 @@
+use std::path::PathBuf;
+
+use limits_core::json::GenericFanLimit;
+
+use crate::persist::FanJson;
+use crate::settings::thermal::{find_hwmon, read_temperature};
+use crate::settings::{transaction, FanCurve, TFan};
+use crate::settings::{ErrorKind, OnResume, OnSet, SettingError, SettingVariant};
+
+const HWMON_DIR: &str = "/sys/class/hwmon";
+const PWM_FILE: &str = "pwm1";
+/// 0 = full speed, 1 = manual (pwm1), 2+ = automatic (driver-specific)
+const PWM_ENABLE_FILE: &str = "pwm1_enable";
+const PWM_ENABLE_MANUAL: u64 = 1;
+const PWM_ENABLE_AUTO: u64 = 2;
+const PWM_MAX: u64 = 255;
+
+#[derive(Debug)]
+pub struct Fan {
+    curve: Option<FanCurve>,
+    limits: GenericFanLimit,
+    state: crate::state::generic::Fan,
+}
+
+impl Fan {
+    pub fn from_limits(limits: GenericFanLimit) -> Self {
+        Self {
+            curve: None,
+            limits,
+            state: crate::state::generic::Fan::default(),
+        }
+    }
+
+    pub fn from_json_and_limits(other: FanJson, version: u64, limits: GenericFanLimit) -> Self {
+        Self {
+            curve: other
+                .curve
+                .map(|points| FanCurve::from_json(points, other.sensor, version)),
+            limits,
+            state: crate::state::generic::Fan::default(),
+        }
+    }
+
+    fn hwmon(&self) -> Option<PathBuf> {
+        if let Some(name) = &self.limits.hwmon {
+            find_hwmon(&[name.as_str()])
+        } else {
+            std::fs::read_dir(HWMON_DIR)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .find(|path| path.join(PWM_ENABLE_FILE).exists())
+        }
+    }
+
+    fn hwmon_file(&self, file: &str) -> Result<PathBuf, SettingError> {
+        self.hwmon()
+            .map(|hwmon| hwmon.join(file))
+            .ok_or_else(|| SettingError {
+                msg: format!(
+                    "Failed to find pwm fan hwmon (name: {:?})",
+                    self.limits.hwmon
+                ),
+                setting: SettingVariant::Fan,
+                kind: ErrorKind::Unsupported,
+            })
+    }
+
+    fn write_hwmon(&self, file: &str, value: u64) -> Result<(), SettingError> {
+        let path = self.hwmon_file(file)?;
+        transaction::record(&path);
+        usdpl_back::api::files::write_single(&path, value).map_err(|e| SettingError {
+            msg: format!("Failed to write `{}` to `{}`: {}", value, path.display(), e),
+            setting: SettingVariant::Fan,
+            kind: ErrorKind::from(&e),
+        })
+    }
+
+    fn pwm_limits(&self) -> (u64, u64) {
+        self.limits
+            .pwm
+            .as_ref()
+            .map(|x| (x.min, x.max))
+            .unwrap_or((0, PWM_MAX))
+    }
+
+    fn set_speed(&mut self) -> Result<(), SettingError> {
+        let curve = if let Some(curve) = &self.curve {
+            curve
+        } else {
+            return Ok(());
+        };
+        let temperature = read_temperature(curve.sensor).ok_or_else(|| SettingError {
+            msg: format!("Failed to read {:?} temperature for fan curve", curve.sensor),
+            setting: SettingVariant::Fan,
+            kind: ErrorKind::Unsupported,
+        })?;
+        let (min, max) = self.pwm_limits();
+        let speed = curve.speed_at(temperature).unwrap_or(max).clamp(min, max);
+        if self.state.old_pwm_enable.is_none() {
+            let path = self.hwmon_file(PWM_ENABLE_FILE)?;
+            let old = usdpl_back::api::files::read_single::<_, u64, _>(&path).map_err(|e| {
+                SettingError {
+                    msg: format!("Failed to read `{}`: {}", path.display(), e),
+                    setting: SettingVariant::Fan,
+                    kind: ErrorKind::from(&e),
+                }
+            })?;
+            self.write_hwmon(PWM_ENABLE_FILE, PWM_ENABLE_MANUAL)?;
+            self.state.old_pwm_enable = Some(old);
+            log::info!("Took fan control (old {}: {})", PWM_ENABLE_FILE, old);
+        }
+        if self.state.last_speed != Some(speed) {
+            log::debug!("Setting fan pwm to {} ({}C)", speed, temperature);
+            self.write_hwmon(PWM_FILE, speed)?;
+            self.state.last_speed = Some(speed);
+        }
+        Ok(())
+    }
+
+    /// Hand fan control back to the firmware/driver
+    fn release(&mut self) -> Result<(), SettingError> {
+        if let Some(old) = self.state.old_pwm_enable.take() {
+            self.state.last_speed = None;
+            // manual mode doesn't make sense without PowerTools setting the pwm
+            let mode = if old == PWM_ENABLE_MANUAL {
+                PWM_ENABLE_AUTO
+            } else {
+                old
+            };
+            log::info!("Returning fan control ({}: {})", PWM_ENABLE_FILE, mode);
+            self.write_hwmon(PWM_ENABLE_FILE, mode)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_all(&mut self) -> Result<(), Vec<SettingError>> {
+        let mut errors = Vec::new();
+        if self.curve.is_some() {
+            if let Err(e) = self.set_speed() {
+                errors.push(e);
+                // fail safe: let the firmware keep the fan going
+                self.state.old_pwm_enable.get_or_insert(PWM_ENABLE_AUTO);
+                self.release().unwrap_or_else(|e| errors.push(e));
+            }
+        } else {
+            self.release().unwrap_or_else(|e| errors.push(e));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn clamp_all(&mut self) {
+        let (min, max) = self.pwm_limits();
+        if let Some(curve) = &mut self.curve {
+            curve.clamp(min, max);
+        }
+    }
+}
+
+impl Drop for Fan {
+    fn drop(&mut self) {
+        if let Err(e) = self.release() {
+            log::error!("Failed to return fan control: {}", e);
+        }
+    }
+}
+
+impl OnSet for Fan {
+    fn on_set(&mut self) -> Result<(), Vec<SettingError>> {
+        self.clamp_all();
+        self.set_all()
+    }
+}
+
+impl OnResume for Fan {
+    fn on_resume(&self) -> Result<(), Vec<SettingError>> {
+        if let (Some(_), Some(speed)) = (self.state.old_pwm_enable, self.state.last_speed) {
+            self.write_hwmon(PWM_ENABLE_FILE, PWM_ENABLE_MANUAL)
+                .and_then(|_| self.write_hwmon(PWM_FILE, speed))
+                .map_err(|e| vec![e])
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl crate::settings::OnPowerEvent for Fan {}
+
+impl crate::settings::OnShutdown for Fan {
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        self.release().map_err(|e| vec![e])
+    }
+}
+
+impl crate::settings::OnVerify for Fan {}
+
+impl TFan for Fan {
+    fn limits(&self) -> crate::api::FanLimits {
+        let (min, max) = self.pwm_limits();
+        crate::api::FanLimits {
+            speed_limits: if self.hwmon().is_some() {
+                Some(crate::api::RangeLimit { min, max })
+            } else {
+                None
+            },
+            speed_step: 1,
+            speed_unit: "pwm".to_owned(),
+            sensors: vec![
+                crate::persist::ThermalSensorJson::Cpu,
+                crate::persist::ThermalSensorJson::Gpu,
+                crate::persist::ThermalSensorJson::Battery,
+            ],
+        }
+    }
+
+    fn json(&self) -> crate::persist::FanJson {
+        FanCurve::to_json(self.curve.as_ref())
+    }
+
+    fn curve(&mut self, curve: Option<FanCurve>) {
+        self.curve = curve;
+    }
+
+    fn update(&mut self) -> Result<(), Vec<SettingError>> {
+        if self.curve.is_some() {
+            self.set_all()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn provider(&self) -> crate::persist::DriverJson {
+        crate::persist::DriverJson::Generic
+    }
+}
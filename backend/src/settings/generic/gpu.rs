@@ -68,13 +68,13 @@ impl Gpu {
     }
 }
 
-impl Into<GpuJson> for Gpu {
+impl From<Gpu> for GpuJson {
     #[inline]
-    fn into(self) -> GpuJson {
+    fn from(other: Gpu) -> Self {
         GpuJson {
-            fast_ppt: self.fast_ppt,
-            slow_ppt: self.slow_ppt,
-            clock_limits: self.clock_limits.map(|x| x.into()),
+            fast_ppt: other.fast_ppt,
+            slow_ppt: other.slow_ppt,
+            clock_limits: other.clock_limits.map(|x| x.into()),
             slow_memory: false,
         }
     }
@@ -94,6 +94,10 @@ impl OnResume for Gpu {
 
 impl crate::settings::OnPowerEvent for Gpu {}
 
+impl crate::settings::OnShutdown for Gpu {}
+
+impl crate::settings::OnVerify for Gpu {}
+
 impl TGpu for Gpu {
     fn limits(&self) -> crate::api::GpuLimits {
         crate::api::GpuLimits {
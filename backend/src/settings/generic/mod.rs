@@ -1,9 +1,11 @@
 mod battery;
 mod cpu;
+mod fan;
 mod gpu;
 mod traits;
 
 pub use battery::Battery;
 pub use cpu::{Cpu, Cpus};
+pub use fan::Fan;
 pub use gpu::Gpu;
 pub use traits::FromGenericCpuInfo;
@@ -1,7 +1,7 @@
 use crate::persist::CpuJson;
 use crate::settings::generic::{Cpu as GenericCpu, Cpus as GenericCpus, FromGenericCpuInfo};
 use crate::settings::MinMax;
-use crate::settings::{OnResume, OnSet, SettingError};
+use crate::settings::{OnResume, OnSet, OnShutdown, OnVerify, SettingError};
 use crate::settings::{TCpu, TCpus};
 
 #[derive(Debug)]
@@ -41,6 +41,22 @@ impl OnSet for Cpus {
     }
 }
 
+impl OnVerify for Cpus {
+    fn verify(&self) -> Vec<crate::api::AppliedValue> {
+        self.generic.verify()
+    }
+}
+
+impl OnShutdown for Cpus {
+    fn snapshot(&self) {
+        self.generic.snapshot()
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        self.generic.on_shutdown()
+    }
+}
+
 impl crate::settings::OnPowerEvent for Cpus {}
 
 impl TCpus for Cpus {
@@ -1,11 +1,12 @@
-use libryzenadj::RyzenAdj;
+use libryzenadj::{RyzenAdj, RyzenAdjResult};
 use std::sync::Mutex;
 
 use crate::persist::GpuJson;
 use crate::settings::generic::Gpu as GenericGpu;
 use crate::settings::MinMax;
 use crate::settings::TGpu;
-use crate::settings::{OnResume, OnSet, SettingError, SettingVariant};
+use crate::settings::{startup, transaction};
+use crate::settings::{ErrorKind, OnResume, OnSet, OnShutdown, SettingError, SettingVariant};
 
 fn ryzen_adj_or_log() -> Option<Mutex<RyzenAdj>> {
     match RyzenAdj::new() {
@@ -17,6 +18,74 @@ fn ryzen_adj_or_log() -> Option<Mutex<RyzenAdj>> {
     }
 }
 
+const STARTUP_FAST_PPT: &str = "ryzenadj:fast_limit";
+const STARTUP_SLOW_PPT: &str = "ryzenadj:slow_limit";
+const UNDO_GFXCLK: &str = "ryzenadj:gfxclk";
+
+/// GPU clock limits last written as (min, max), since RyzenAdj can't read them back
+static GFXCLK: Mutex<Option<(u64, u64)>> = Mutex::new(None);
+
+/// Record how to put back RyzenAdj values for rolling back the open transaction.
+/// The undo is given a new RyzenAdj, since the one in use can't be sent along with it.
+fn record_undo<F, U>(key: &str, undo: F)
+where
+    F: FnOnce() -> Option<U>,
+    U: FnOnce(&RyzenAdj) -> RyzenAdjResult<()> + Send + 'static,
+{
+    transaction::record_with(key, || {
+        let undo = undo()?;
+        Some(Box::new(move || {
+            let ryzen_adj = RyzenAdj::new().map_err(|e| std::io::Error::other(e.to_string()))?;
+            undo(&ryzen_adj).map_err(|e| std::io::Error::other(e.to_string()))
+        }))
+    });
+}
+
+fn record_fast_ppt_undo(lock: &RyzenAdj) {
+    record_undo(STARTUP_FAST_PPT, || {
+        let old = (lock.get_fast_limit().ok()? * 1000.0) as u32;
+        Some(move |ryzen_adj: &RyzenAdj| ryzen_adj.set_fast_limit(old))
+    });
+}
+
+fn record_slow_ppt_undo(lock: &RyzenAdj) {
+    record_undo(STARTUP_SLOW_PPT, || {
+        let old = (lock.get_slow_limit().ok()? * 1000.0) as u32;
+        Some(move |ryzen_adj: &RyzenAdj| ryzen_adj.set_slow_limit(old))
+    });
+}
+
+/// Like record_fast_ppt_undo(), for GPU clock limits.
+/// Until PowerTools sets them, they are assumed to be `default`.
+fn record_gfxclk_undo(default: Option<(u64, u64)>) {
+    record_undo(UNDO_GFXCLK, || {
+        let (min, max) = GFXCLK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .or(default)?;
+        Some(move |ryzen_adj: &RyzenAdj| {
+            ryzen_adj.set_max_gfxclk_freq(max as _)?;
+            ryzen_adj.set_min_gfxclk_freq(min as _)?;
+            wrote_gfxclk(min, max);
+            Ok(())
+        })
+    });
+}
+
+fn wrote_gfxclk(min: u64, max: u64) {
+    *GFXCLK.lock().unwrap_or_else(|e| e.into_inner()) = Some((min, max));
+}
+
+/// Remember the first PPT limit seen as the startup value, and return that (in mW)
+fn startup_ppt(key: &str, watts: f32) -> u64 {
+    // RyzenAdj reports limits in W, but sets them in mW
+    let milliwatts = (watts * 1000.0) as u64;
+    startup::remember_with(key, || Some(milliwatts.to_string()));
+    startup::get(key)
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(milliwatts)
+}
+
 unsafe impl Send for Gpu {} // implementor (RyzenAdj) may be unsafe
 
 //#[derive(Debug)]
@@ -63,6 +132,7 @@ impl Gpu {
                 return Err(vec![SettingError {
                     msg: "RyzenAdj unavailable".to_owned(),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Unsupported,
                 }]);
             }
         };
@@ -72,62 +142,80 @@ impl Gpu {
                 return Err(vec![SettingError {
                     msg: format!("RyzenAdj lock acquire failed: {}", e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 }]);
             }
         };
         let mut errors = Vec::new();
         if let Some(fast_ppt) = &self.generic.fast_ppt {
             if self.state.old_fast_ppt.is_none() {
-                match lock.get_fast_value() {
-                    Ok(val) => self.state.old_fast_ppt = Some(val as _),
+                match lock.get_fast_limit() {
+                    Ok(val) => self.state.old_fast_ppt = Some(startup_ppt(STARTUP_FAST_PPT, val)),
                     Err(e) => errors.push(SettingError {
-                        msg: format!("RyzenAdj get_fast_value() err: {}", e),
+                        msg: format!("RyzenAdj get_fast_limit() err: {}", e),
                         setting: SettingVariant::Gpu,
+                        kind: ErrorKind::Io,
                     }),
                 }
             }
+            record_fast_ppt_undo(&lock);
             lock.set_fast_limit(*fast_ppt as _)
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_fast_limit({}) err: {}", *fast_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
         } else if let Some(fast_ppt) = &self.state.old_fast_ppt {
+            record_fast_ppt_undo(&lock);
             lock.set_fast_limit(*fast_ppt as _)
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_fast_limit({}) err: {}", *fast_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
             self.state.old_fast_ppt = None;
         }
         if let Some(slow_ppt) = &self.generic.slow_ppt {
             if self.state.old_slow_ppt.is_none() {
-                match lock.get_slow_value() {
-                    Ok(val) => self.state.old_fast_ppt = Some(val as _),
+                match lock.get_slow_limit() {
+                    Ok(val) => self.state.old_slow_ppt = Some(startup_ppt(STARTUP_SLOW_PPT, val)),
                     Err(e) => errors.push(SettingError {
-                        msg: format!("RyzenAdj get_slow_value() err: {}", e),
+                        msg: format!("RyzenAdj get_slow_limit() err: {}", e),
                         setting: SettingVariant::Gpu,
+                        kind: ErrorKind::Io,
                     }),
                 }
             }
+            record_slow_ppt_undo(&lock);
             lock.set_slow_limit(*slow_ppt as _)
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_slow_limit({}) err: {}", *slow_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
         } else if let Some(slow_ppt) = &self.state.old_slow_ppt {
+            record_slow_ppt_undo(&lock);
             lock.set_slow_limit(*slow_ppt as _)
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_slow_limit({}) err: {}", *slow_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
             self.state.old_slow_ppt = None;
         }
+        let limits = self.generic.limits();
+        let default_clocks = limits
+            .clock_min_limits
+            .as_ref()
+            .zip(limits.clock_max_limits.as_ref())
+            .map(|(min_limits, max_limits)| (min_limits.min, max_limits.max));
         if let Some(clock_limits) = &self.generic.clock_limits {
             self.state.clock_limits_set = true;
+            record_gfxclk_undo(default_clocks);
             lock.set_max_gfxclk_freq(clock_limits.max as _)
                 .map_err(|e| SettingError {
                     msg: format!(
@@ -135,6 +223,7 @@ impl Gpu {
                         clock_limits.max, e
                     ),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
             lock.set_min_gfxclk_freq(clock_limits.min as _)
@@ -144,13 +233,15 @@ impl Gpu {
                         clock_limits.min, e
                     ),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
+            wrote_gfxclk(clock_limits.min, clock_limits.max);
         } else if self.state.clock_limits_set {
             self.state.clock_limits_set = false;
-            let limits = self.generic.limits();
             if let Some(min_limits) = limits.clock_min_limits {
                 if let Some(max_limits) = limits.clock_max_limits {
+                    record_gfxclk_undo(default_clocks);
                     lock.set_max_gfxclk_freq(max_limits.max as _)
                         .map_err(|e| SettingError {
                             msg: format!(
@@ -158,6 +249,7 @@ impl Gpu {
                                 max_limits.max, e
                             ),
                             setting: SettingVariant::Gpu,
+                            kind: ErrorKind::Io,
                         })
                         .unwrap_or_else(|e| errors.push(e));
                     lock.set_min_gfxclk_freq(min_limits.min as _)
@@ -167,8 +259,10 @@ impl Gpu {
                                 min_limits.min, e
                             ),
                             setting: SettingVariant::Gpu,
+                            kind: ErrorKind::Io,
                         })
                         .unwrap_or_else(|e| errors.push(e));
+                    wrote_gfxclk(min_limits.min, max_limits.max);
                 }
             }
         }
@@ -188,6 +282,7 @@ impl Gpu {
                 return Err(vec![SettingError {
                     msg: "RyzenAdj unavailable".to_owned(),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Unsupported,
                 }]);
             }
         };
@@ -197,6 +292,7 @@ impl Gpu {
                 return Err(vec![SettingError {
                     msg: format!("RyzenAdj lock acquire failed: {}", e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 }]);
             }
         };
@@ -206,6 +302,7 @@ impl Gpu {
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_fast_limit({}) err: {}", *fast_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -214,6 +311,7 @@ impl Gpu {
                 .map_err(|e| SettingError {
                     msg: format!("RyzenAdj set_slow_limit({}) err: {}", *slow_ppt, e),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -225,6 +323,7 @@ impl Gpu {
                         clock_limits.max, e
                     ),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
             lock.set_min_gfxclk_freq(clock_limits.min as _)
@@ -234,6 +333,7 @@ impl Gpu {
                         clock_limits.min, e
                     ),
                     setting: SettingVariant::Gpu,
+                    kind: ErrorKind::Io,
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -255,6 +355,31 @@ impl OnSet for Gpu {
     }
 }
 
+impl OnShutdown for Gpu {
+    fn snapshot(&self) {
+        if let Some(Ok(lock)) = self.implementor.as_ref().map(|x| x.lock()) {
+            if let Ok(val) = lock.get_fast_limit() {
+                startup_ppt(STARTUP_FAST_PPT, val);
+            }
+            if let Ok(val) = lock.get_slow_limit() {
+                startup_ppt(STARTUP_SLOW_PPT, val);
+            }
+        }
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        // the "unset" paths of set_all() write back the old values
+        self.state.old_fast_ppt = startup::get(STARTUP_FAST_PPT).and_then(|x| x.parse().ok());
+        self.state.old_slow_ppt = startup::get(STARTUP_SLOW_PPT).and_then(|x| x.parse().ok());
+        self.generic.fast_ppt = None;
+        self.generic.slow_ppt = None;
+        self.generic.clock_limits = None;
+        self.set_all()
+    }
+}
+
+impl crate::settings::OnVerify for Gpu {}
+
 impl crate::settings::OnPowerEvent for Gpu {}
 
 impl TGpu for Gpu {
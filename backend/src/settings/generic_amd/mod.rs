@@ -1,5 +1,5 @@
 mod cpu;
 mod gpu;
 
-pub use cpu::{Cpu, Cpus};
+pub use cpu::Cpus;
 pub use gpu::Gpu;
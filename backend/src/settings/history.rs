@@ -0,0 +1,228 @@
+//! Per-profile history of changes, for undo and redo.
+//! Changes are found by comparing the settings document after each save with the one before,
+//! so every way of changing settings is recorded without each message having to describe itself.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use serde_json::Value;
+
+use crate::persist::{ChangeJson, ChangeSourceJson, HistoryEntryJson, HistoryJson, SettingsJson};
+use crate::save_worker::SaveMessage;
+
+/// Changed values between `old` and `new`.
+/// Lists which changed length (e.g. triggers) are one change, since their items can't be matched up.
+pub fn diff(old: &SettingsJson, new: &SettingsJson) -> Vec<ChangeJson> {
+    let mut changes = Vec::new();
+    diff_values(
+        String::new(),
+        &serde_json::to_value(old).unwrap_or_default(),
+        &serde_json::to_value(new).unwrap_or_default(),
+        &mut changes,
+    );
+    changes
+}
+
+fn diff_values(pointer: String, old: &Value, new: &Value, changes: &mut Vec<ChangeJson>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for key in old
+                .keys()
+                .chain(new.keys().filter(|k| !old.contains_key(*k)))
+            {
+                diff_values(
+                    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1")),
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+                diff_values(format!("{}/{}", pointer, i), old, new, changes);
+            }
+        }
+        (old, new) if old != new => changes.push(ChangeJson {
+            field: pointer,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Set the fields of `changes` to their old values (undo) or new values (redo)
+fn replay(json: SettingsJson, changes: &[ChangeJson], undo: bool) -> Result<SettingsJson, String> {
+    let mut value = serde_json::to_value(json).map_err(|e| e.to_string())?;
+    for change in changes {
+        let field = value
+            .pointer_mut(&change.field)
+            .ok_or_else(|| format!("Profile has no `{}` to change", change.field))?;
+        *field = if undo {
+            change.old.clone()
+        } else {
+            change.new.clone()
+        };
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+pub struct History {
+    /// Settings file of the profile whose history this is
+    profile: PathBuf,
+    journal: HistoryJson,
+    /// Values of the profile after the last change, which the next change is compared with
+    baseline: Option<SettingsJson>,
+    /// The next change is an undo or redo, which is in the history already
+    replaying: bool,
+    /// The journal is written by save_worker, like profiles
+    saves: Sender<SaveMessage>,
+}
+
+impl History {
+    pub fn new(saves: Sender<SaveMessage>) -> Self {
+        Self {
+            profile: PathBuf::new(),
+            journal: HistoryJson::default(),
+            baseline: None,
+            replaying: false,
+            saves,
+        }
+    }
+
+    fn journal_path(profile: &Path) -> PathBuf {
+        crate::utility::settings_dir()
+            .join(crate::consts::HISTORY_DIR)
+            .join(profile)
+    }
+
+    fn save(&self) {
+        let message = SaveMessage::SaveHistory(
+            Self::journal_path(&self.profile),
+            Box::new(self.journal.clone()),
+        );
+        if self.saves.send(message).is_err() {
+            log::error!(
+                "Failed to save history of {}, save_worker is gone",
+                self.profile.display()
+            );
+        }
+    }
+
+    pub fn json(&self) -> HistoryJson {
+        self.journal.clone()
+    }
+
+    /// Compare the values of `profile` with the ones before, and record the changes if there's a `source`.
+    /// When the profile is a different one, its history is loaded instead.
+    pub fn observe(
+        &mut self,
+        profile: &Path,
+        json: SettingsJson,
+        source: Option<ChangeSourceJson>,
+    ) {
+        if self.profile != profile || self.baseline.is_none() {
+            self.profile = profile.to_owned();
+            // a pending write of the journal would be older than the one read here
+            crate::save_worker::flush(&self.saves);
+            self.journal =
+                crate::persist::open_json(Self::journal_path(profile)).unwrap_or_default();
+            self.baseline = Some(json);
+            self.replaying = false;
+            return;
+        }
+        // whether the profile is saved at all isn't one of its values, so it isn't undone
+        let changes: Vec<ChangeJson> = self
+            .baseline
+            .as_ref()
+            .map(|old| diff(old, &json))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|change| change.field != "/persistent")
+            .collect();
+        self.baseline = Some(json);
+        let source = match source {
+            Some(source) if !self.replaying && !changes.is_empty() => source,
+            _ => {
+                self.replaying = false;
+                return;
+            }
+        };
+        let journal = &mut self.journal;
+        // a new change can't be put between the undone ones
+        journal.entries.truncate(journal.position);
+        journal.entries.push(HistoryEntryJson {
+            time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            source,
+            changes,
+        });
+        let excess = journal
+            .entries
+            .len()
+            .saturating_sub(crate::consts::HISTORY_LIMIT);
+        journal.entries.drain(..excess);
+        journal.position = journal.entries.len();
+        self.save();
+    }
+
+    /// Undo (or redo) one entry of the history on `current`; `load` applies the result
+    pub fn step<F: FnOnce(SettingsJson) -> Result<(), String>>(
+        &mut self,
+        current: SettingsJson,
+        undo: bool,
+        load: F,
+    ) -> Result<(), String> {
+        let journal = &self.journal;
+        let index = if undo {
+            journal
+                .position
+                .checked_sub(1)
+                .ok_or_else(|| "Nothing to undo".to_owned())?
+        } else if journal.position < journal.entries.len() {
+            journal.position
+        } else {
+            return Err("Nothing to redo".to_owned());
+        };
+        load(replay(current, &journal.entries[index].changes, undo)?)?;
+        self.journal.position = if undo { index } else { index + 1 };
+        self.replaying = true;
+        self.save();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::persist::CpuJson;
+
+    #[test]
+    fn diff_and_replay_test() {
+        let old = SettingsJson {
+            cpus: vec![CpuJson::default(); 2],
+            ..Default::default()
+        };
+        let mut new = old.clone();
+        new.cpus[1].governor = "powersave".to_owned();
+        new.gpu.fast_ppt = Some(15_000_000);
+        new.triggers.push(crate::persist::TriggerJson {
+            trigger: "resume".to_owned(),
+            action: Default::default(),
+        });
+        let changes = diff(&old, &new);
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["/cpus/1/governor", "/gpu/fast_ppt", "/triggers"]
+        );
+
+        let undone = replay(new.clone(), &changes, true).unwrap();
+        assert!(diff(&old, &undone).is_empty());
+        let redone = replay(undone, &changes, false).unwrap();
+        assert!(diff(&new, &redone).is_empty());
+    }
+}
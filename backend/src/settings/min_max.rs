@@ -13,12 +13,12 @@ pub fn min_max_from_json<T, X: Into<T>>(other: MinMaxJson<X>, _version: u64) ->
     }
 }
 
-impl<X: Into<Y>, Y> Into<MinMaxJson<Y>> for RangeLimit<X> {
+impl<X: Into<Y>, Y> From<RangeLimit<X>> for MinMaxJson<Y> {
     #[inline]
-    fn into(self) -> MinMaxJson<Y> {
+    fn from(other: RangeLimit<X>) -> Self {
         MinMaxJson {
-            max: self.max.into(),
-            min: self.min.into(),
+            max: other.max.into(),
+            min: other.min.into(),
         }
     }
 }
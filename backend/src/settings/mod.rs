@@ -1,23 +1,44 @@
 mod detect;
 pub mod driver;
 mod error;
+mod fan_curve;
 mod general;
+pub mod history;
 mod min_max;
+mod power_sources;
+pub mod presets;
+pub mod sharing;
+pub mod startup;
+pub mod thermal;
 mod traits;
+pub mod transaction;
+pub mod triggers;
 mod util;
+pub mod validate;
+pub mod verify;
 
 pub mod generic;
 pub mod generic_amd;
 pub mod steam_deck;
 pub mod unknown;
 
-pub use detect::{auto_detect0, auto_detect_provider, limits_worker::spawn as limits_worker_spawn};
+pub use detect::{
+    auto_detect0, auto_detect_provider, detected_config,
+    limits_worker::spawn as limits_worker_spawn,
+};
 pub use driver::Driver;
-pub use general::{General, SettingVariant, Settings};
+pub use fan_curve::FanCurve;
+pub use general::{General, SettingVariant, Settings, LATEST_VERSION};
 pub use min_max::{min_max_from_json, MinMax};
+pub use power_sources::PowerSources;
+pub use thermal::Thermal;
+pub use triggers::{TriggerEvent, Triggers};
 
-pub use error::SettingError;
-pub use traits::{OnPowerEvent, OnResume, OnSet, PowerMode, TBattery, TCpu, TCpus, TGeneral, TGpu};
+pub use error::{ErrorKind, SettingError};
+pub use traits::{
+    OnPowerEvent, OnResume, OnSet, OnShutdown, OnVerify, PowerMode, TBattery, TCpu, TCpus, TFan,
+    TGeneral, TGpu,
+};
 
 #[cfg(test)]
 mod tests {
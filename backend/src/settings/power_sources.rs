@@ -0,0 +1,108 @@
+//! Separate CPU and GPU values for when the device is plugged in and on battery, in one profile.
+//! The values of the power source in use are in the drivers, so they're changed like any other values.
+//! Values of the other power source have no setters of their own; they're changed with the profile's JSON
+//! (`power_sources` of api::general::set_settings_json()).
+
+use crate::persist::{PowerSourceJson, PowerSourceValuesJson, PowerSourcesJson, SettingsJson};
+
+#[derive(Debug, Default)]
+pub struct PowerSources {
+    /// Values of each power source; the ones in use are out of date until json()
+    variants: PowerSourcesJson,
+    /// Profile's own values, while the values of a power source are in use
+    base: Option<PowerSourceValuesJson>,
+    /// Power source whose values are in use
+    in_use: Option<PowerSourceJson>,
+}
+
+impl PowerSources {
+    pub fn from_json(variants: PowerSourcesJson) -> Self {
+        Self {
+            variants,
+            base: None,
+            in_use: None,
+        }
+    }
+
+    pub fn in_use(&self) -> Option<PowerSourceJson> {
+        self.in_use
+    }
+
+    /// Move the values in use from `cpus` and `gpu` of `json` to their power source,
+    /// so the profile's own values are saved there instead
+    pub fn json(&self, json: &mut SettingsJson) {
+        let mut variants = self.variants.clone();
+        if let (Some(source), Some(base)) = (self.in_use, &self.base) {
+            *variants.get_mut(source) = Some(PowerSourceValuesJson {
+                cpus: std::mem::replace(&mut json.cpus, base.cpus.clone()),
+                gpu: std::mem::replace(&mut json.gpu, base.gpu.clone()),
+            });
+        }
+        json.power_sources = variants;
+    }
+
+    /// Values to use on `source`, if they aren't the ones in use already.
+    /// `live` reads the values in use, which are kept for switching back.
+    pub fn switch<F: FnOnce() -> PowerSourceValuesJson>(
+        &mut self,
+        source: Option<PowerSourceJson>,
+        live: F,
+    ) -> Option<PowerSourceValuesJson> {
+        // without values for the source, the profile's own values are used
+        let target = source.filter(|s| self.variants.get(*s).is_some());
+        if target == self.in_use {
+            return None;
+        }
+        match self.in_use {
+            Some(old) => *self.variants.get_mut(old) = Some(live()),
+            None => self.base = Some(live()),
+        }
+        self.in_use = target;
+        match target {
+            Some(new) => self.variants.get(new).cloned(),
+            None => self.base.take(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::persist::GpuJson;
+
+    fn values(ppt: u64) -> PowerSourceValuesJson {
+        PowerSourceValuesJson {
+            cpus: Vec::new(),
+            gpu: GpuJson {
+                fast_ppt: Some(ppt),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn switch_and_save_test() {
+        let mut sources = PowerSources::from_json(PowerSourcesJson {
+            ac: Some(values(15)),
+            battery: None,
+        });
+        // no battery values, so the profile's own are used
+        assert!(sources
+            .switch(Some(PowerSourceJson::Battery), || values(10))
+            .is_none());
+        let ac = sources.switch(Some(PowerSourceJson::Ac), || values(10));
+        assert_eq!(ac.unwrap().gpu.fast_ppt, Some(15));
+        // AC value was changed while in use
+        let mut json = SettingsJson {
+            gpu: values(20).gpu,
+            ..Default::default()
+        };
+        sources.json(&mut json);
+        assert_eq!(json.gpu.fast_ppt, Some(10));
+        assert_eq!(json.power_sources.ac.unwrap().gpu.fast_ppt, Some(20));
+        let own = sources.switch(Some(PowerSourceJson::Battery), || values(20));
+        assert_eq!(own.unwrap().gpu.fast_ppt, Some(10));
+        assert!(sources.in_use().is_none());
+        assert_eq!(sources.variants.ac.unwrap().gpu.fast_ppt, Some(20));
+    }
+}
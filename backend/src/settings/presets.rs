@@ -0,0 +1,135 @@
+//! Named presets of settings, which profiles can follow instead of keeping their own values.
+//! Presets are stored like profiles, as `<name>.json` in the presets dir.
+
+use std::path::PathBuf;
+
+use super::{ErrorKind, SettingError, SettingVariant};
+use crate::persist::SettingsJson;
+
+fn presets_dir() -> PathBuf {
+    crate::utility::settings_dir().join(crate::consts::PRESETS_DIR)
+}
+
+fn error(kind: ErrorKind, msg: String) -> SettingError {
+    SettingError {
+        msg,
+        setting: SettingVariant::General,
+        kind,
+    }
+}
+
+fn path(name: &str) -> Result<PathBuf, SettingError> {
+    if name.trim().is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(error(
+            ErrorKind::UnknownOption,
+            format!("`{}` is not a valid preset name", name),
+        ));
+    }
+    Ok(presets_dir().join(format!("{}.json", name)))
+}
+
+/// Names of all presets, sorted
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(presets_dir())
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .map(|entry| PathBuf::from(entry.file_name()))
+                .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn exists(name: &str) -> bool {
+    path(name).map(|p| p.exists()).unwrap_or(false)
+}
+
+pub fn open(name: &str) -> Result<SettingsJson, SettingError> {
+    let path = path(name)?;
+    if !path.exists() {
+        return Err(error(
+            ErrorKind::UnknownOption,
+            format!("There is no preset named `{}`", name),
+        ));
+    }
+    SettingsJson::open(&path).map_err(|e| error(ErrorKind::Io, e.to_string()))
+}
+
+/// Save `settings` as the preset `name`, replacing any preset with that name
+pub fn save(name: &str, mut settings: SettingsJson) -> Result<(), SettingError> {
+    let path = path(name)?;
+    settings.name = name.to_owned();
+    settings.persistent = true;
+    settings.preset = None;
+    settings
+        .save(&path)
+        .map_err(|e| error(ErrorKind::Io, e.to_string()))?;
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), SettingError> {
+    let path = path(name)?;
+    std::fs::remove_file(&path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            error(
+                ErrorKind::UnknownOption,
+                format!("There is no preset named `{}`", name),
+            )
+        } else {
+            error((&e).into(), e.to_string())
+        }
+    })
+}
+
+/// Take the values of the preset `profile` follows, if it follows one.
+/// If the preset is gone, the profile keeps its own (last saved) values and stops following it.
+pub fn resolve(profile: SettingsJson) -> SettingsJson {
+    let name = match &profile.preset {
+        Some(name) => name.to_owned(),
+        None => return profile,
+    };
+    match open(&name) {
+        Ok(preset) => SettingsJson {
+            name: profile.name,
+            persistent: profile.persistent,
+            preset: Some(name),
+            ..preset
+        },
+        Err(e) => {
+            log::warn!(
+                "Profile `{}` keeps its own values, since its preset failed to load: {}",
+                profile.name,
+                e
+            );
+            SettingsJson {
+                preset: None,
+                ..profile
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preset_name_test() {
+        assert!(path("Battery saver").is_ok());
+        assert!(path("").is_err());
+        assert!(path("../default_settings").is_err());
+        assert!(path(".hidden").is_err());
+        // a missing preset leaves the profile's values alone
+        let profile = SettingsJson {
+            name: "Game".to_owned(),
+            preset: Some("/not a preset".to_owned()),
+            ..Default::default()
+        };
+        let resolved = resolve(profile);
+        assert_eq!(resolved.name, "Game");
+        assert!(resolved.preset.is_none());
+    }
+}
@@ -0,0 +1,248 @@
+//! Sharing profiles between devices.
+//! Exported profiles say what they were made on, so importing them can fit the values to this device's limits
+//! instead of failing like a hand-edited profile would.
+
+use super::validate;
+use super::Settings;
+use crate::api::{ImportReport, SettingsLimits};
+use crate::persist::{CpuJson, ProfileExportJson, ProvidersJson, SettingsJson, ValidationMode};
+
+/// Version of the export format; newer exports may have values this version would silently drop
+pub const FORMAT: u64 = 1;
+
+/// Governor used for governors which aren't available here, if it is
+const FALLBACK_GOVERNOR: &str = "schedutil";
+
+pub fn export(settings: &Settings) -> ProfileExportJson {
+    ProfileExportJson {
+        format: FORMAT,
+        powertools_version: crate::consts::PACKAGE_VERSION.to_owned(),
+        device: super::detected_config(),
+        providers: ProvidersJson {
+            general: settings.general.provider(),
+            cpus: settings.cpus.provider(),
+            gpu: settings.gpu.provider(),
+            battery: settings.battery.provider(),
+            fan: settings.fan.provider(),
+        },
+        // app profiles are saved as <app id>.json
+        app_id: settings
+            .general
+            .get_path()
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok()),
+        settings: settings.json(),
+    }
+}
+
+/// Fit the settings of `export` to `limits`.
+/// Values which can't be clamped are mapped to something close; anything left over fails the import.
+pub fn import(
+    export: ProfileExportJson,
+    limits: &SettingsLimits,
+) -> Result<(SettingsJson, ImportReport), Vec<super::SettingError>> {
+    if export.format > FORMAT {
+        return Err(vec![super::SettingError {
+            msg: format!(
+                "Profile export format {} is newer than this version of PowerTools ({}) understands",
+                export.format,
+                crate::consts::PACKAGE_VERSION
+            ),
+            setting: super::SettingVariant::General,
+            kind: super::ErrorKind::Unsupported,
+        }]);
+    }
+    let local = super::detected_config();
+    let mut report = ImportReport {
+        same_device: export.device.is_some() && export.device == local,
+        device: export.device,
+        ..Default::default()
+    };
+    let mut json = export.settings;
+    json.persistent = true;
+    // drivers are detected again for this device
+    json.provider = None;
+    if let Some(preset) = json.preset.take() {
+        // presets are local, so the profile keeps the preset's values as its own
+        report
+            .adjusted
+            .push(format!("Preset `{}` is not followed", preset));
+    }
+    map_cpus(&mut json.cpus, limits, "", &mut report);
+    if let Some(values) = &mut json.power_sources.ac {
+        map_cpus(&mut values.cpus, limits, "AC ", &mut report);
+    }
+    if let Some(values) = &mut json.power_sources.battery {
+        map_cpus(&mut values.cpus, limits, "Battery ", &mut report);
+    }
+    let charge_modes = &limits.battery.charge_modes;
+    if let Some(mode) = json.battery.charge_mode.take() {
+        if charge_modes.is_empty() || charge_modes.contains(&mode) {
+            json.battery.charge_mode = Some(mode);
+        } else {
+            report
+                .adjusted
+                .push(format!("Charge mode `{}` is not set", mode));
+        }
+    }
+    super::triggers::migrate_battery_events(&mut json);
+    for trigger in json.triggers.iter_mut() {
+        let action = &mut trigger.action;
+        if let Some(governor) = &action.cpu_governor {
+            if let Some(mapped) = map_governor(governor, limits) {
+                report.adjusted.push(format!(
+                    "Trigger `{}` CPU governor `{}` is now `{}`",
+                    trigger.trigger, governor, mapped
+                ));
+                action.cpu_governor = Some(mapped);
+            }
+        }
+        if let Some(mode) = &action.charge_mode {
+            if !charge_modes.is_empty() && !charge_modes.contains(mode) {
+                report.adjusted.push(format!(
+                    "Trigger `{}` charge mode `{}` is not set",
+                    trigger.trigger, mode
+                ));
+                action.charge_mode = None;
+            }
+        }
+    }
+    // rejecting tells what clamping is about to change
+    if let Err(errors) = validate::settings_json(&mut json.clone(), limits, ValidationMode::Reject)
+    {
+        report.adjusted.extend(errors.into_iter().map(|e| e.msg));
+    }
+    validate::settings_json(&mut json, limits, ValidationMode::Clamp)?;
+    Ok((json, report))
+}
+
+/// Match the CPU count of this device, and replace governors it doesn't have
+fn map_cpus(
+    cpus: &mut Vec<CpuJson>,
+    limits: &SettingsLimits,
+    prefix: &str,
+    report: &mut ImportReport,
+) {
+    if cpus.len() != limits.cpu.count {
+        report.adjusted.push(format!(
+            "{}CPU settings for {} CPUs are used for {} CPUs",
+            prefix,
+            cpus.len(),
+            limits.cpu.count
+        ));
+        // extra CPUs get the same settings as the last one
+        let last = cpus.last().cloned().unwrap_or_default();
+        cpus.resize(limits.cpu.count, last);
+    }
+    for (i, cpu) in cpus.iter_mut().enumerate() {
+        if let Some(governor) = map_governor(&cpu.governor, limits) {
+            report.adjusted.push(format!(
+                "{}CPU {} governor `{}` is now `{}`",
+                prefix, i, cpu.governor, governor
+            ));
+            cpu.governor = governor;
+        }
+    }
+}
+
+/// Available governor to use instead of `governor`, if it isn't available
+fn map_governor(governor: &str, limits: &SettingsLimits) -> Option<String> {
+    let governors = &limits.cpu.cpus.first()?.governors;
+    if governors.is_empty() || governors.iter().any(|g| g == governor) {
+        return None;
+    }
+    governors
+        .iter()
+        .find(|g| *g == FALLBACK_GOVERNOR)
+        .or_else(|| governors.first())
+        .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMITS: &str = r#"{
+        "battery": {
+            "charge_current": null,
+            "charge_current_step": 50,
+            "charge_modes": ["normal", "idle"],
+            "charge_limit": null,
+            "charge_limit_step": 1.0
+        },
+        "cpu": {
+            "cpus": [
+                {
+                    "clock_min_limits": { "min": 400, "max": 3500 },
+                    "clock_max_limits": { "min": 400, "max": 3500 },
+                    "clock_step": 100,
+                    "governors": ["powersave", "schedutil"]
+                },
+                {
+                    "clock_min_limits": { "min": 400, "max": 3500 },
+                    "clock_max_limits": { "min": 400, "max": 3500 },
+                    "clock_step": 100,
+                    "governors": ["powersave", "schedutil"]
+                }
+            ],
+            "count": 2,
+            "smt_capable": false,
+            "governors": ["powersave", "schedutil"]
+        },
+        "gpu": {
+            "fast_ppt_limits": { "min": 1000000, "max": 15000000 },
+            "slow_ppt_limits": { "min": 1000000, "max": 15000000 },
+            "ppt_step": 1000000,
+            "tdp_limits": null,
+            "tdp_boost_limits": null,
+            "tdp_step": 1,
+            "clock_min_limits": null,
+            "clock_max_limits": null,
+            "clock_step": 100,
+            "memory_control_capable": false
+        },
+        "fan": { "speed_limits": null, "speed_step": 1, "speed_unit": "rpm", "sensors": [] },
+        "general": {}
+    }"#;
+
+    #[test]
+    fn import_fits_limits_test() {
+        let limits: SettingsLimits = serde_json::from_str(LIMITS).unwrap();
+        let mut settings = SettingsJson {
+            cpus: vec![CpuJson {
+                governor: "performance".to_owned(),
+                ..Default::default()
+            }],
+            provider: Some(crate::persist::DriverJson::SteamDeck),
+            ..Default::default()
+        };
+        settings.gpu.fast_ppt = Some(30_000_000);
+        settings.battery.charge_mode = Some("turbo".to_owned());
+        let export = ProfileExportJson {
+            format: FORMAT,
+            powertools_version: "1.0.0".to_owned(),
+            device: Some("Some other device".to_owned()),
+            providers: ProvidersJson::default(),
+            app_id: Some(1234),
+            settings,
+        };
+        let (json, report) = import(export, &limits).unwrap();
+        assert!(!report.same_device);
+        assert_eq!(json.cpus.len(), 2);
+        assert!(json.cpus.iter().all(|cpu| cpu.governor == "schedutil"));
+        assert_eq!(json.gpu.fast_ppt, Some(15_000_000));
+        assert!(json.battery.charge_mode.is_none());
+        assert!(json.provider.is_none());
+        assert_eq!(
+            report.adjusted,
+            vec![
+                "CPU settings for 1 CPUs are used for 2 CPUs",
+                "CPU 0 governor `performance` is now `schedutil`",
+                "CPU 1 governor `performance` is now `schedutil`",
+                "Charge mode `turbo` is not set",
+                "GPU fast PPT `30000000` is outside of allowed range (1000000, 15000000)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Snapshot of hardware values from before PowerTools changed them, for restoring stock settings.
+//! Drivers record their values at startup (see OnShutdown::snapshot), and anything else
+//! is recorded the first time PowerTools writes to it.
+//! The snapshot is saved, so a restarted back-end still knows the stock values until reboot.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use super::{ErrorKind, SettingError, SettingVariant};
+use crate::persist::HardwareSnapshotJson;
+
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+static SNAPSHOT: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+/// Where the snapshot is saved once it's taken
+static SAVE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+fn boot_id() -> String {
+    usdpl_back::api::files::read_single(BOOT_ID_PATH).unwrap_or_default()
+}
+
+/// Load the snapshot saved earlier during this boot, if there is one
+pub fn load<P: AsRef<Path>>(path: P) {
+    match crate::persist::open_json::<HardwareSnapshotJson, _>(path) {
+        Ok(snapshot) if snapshot.boot_id == boot_id() => {
+            log::info!(
+                "Loaded hardware snapshot with {} values from earlier this boot",
+                snapshot.values.len()
+            );
+            *SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner()) = snapshot.values;
+        }
+        Ok(_) => log::info!("Ignoring hardware snapshot from a previous boot"),
+        Err(e) => log::info!("No hardware snapshot loaded: {}", e),
+    }
+}
+
+/// Save the snapshot to `path`, and save it there again whenever a value is remembered later on
+pub fn save<P: AsRef<Path>>(path: P) {
+    let path = SAVE_PATH.get_or_init(|| path.as_ref().to_owned());
+    let snapshot = HardwareSnapshotJson {
+        boot_id: boot_id(),
+        values: SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+    };
+    if let Err(e) = crate::persist::save_json(&snapshot, path) {
+        log::error!("Failed to save hardware snapshot: {}", e);
+    }
+}
+
+/// Record the value for `key` from `read`, unless something was already recorded for it
+pub fn remember_with<F: FnOnce() -> Option<String>>(key: &str, read: F) {
+    {
+        let mut values = SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+        if values.contains_key(key) {
+            return;
+        }
+        match read() {
+            Some(value) => {
+                log::debug!("Startup value for `{}` is `{}`", key, value);
+                values.insert(key.to_owned(), value);
+            }
+            None => return,
+        }
+    }
+    if let Some(path) = SAVE_PATH.get() {
+        save(path);
+    }
+}
+
+/// Record the current contents of the sysfs file at `path`
+pub fn remember<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    remember_with(&path.to_string_lossy(), || {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim_end().to_owned())
+    });
+}
+
+pub fn get(key: &str) -> Option<String> {
+    SNAPSHOT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(key)
+        .cloned()
+}
+
+/// Like get(), for values stored by path
+pub fn get_path<P: AsRef<Path>>(path: P) -> Option<String> {
+    get(&path.as_ref().to_string_lossy())
+}
+
+/// Like usdpl_back::api::files::write_single, but remembers the old value first
+/// (as startup value, and for rolling back the open transaction)
+pub fn write_single<P: AsRef<Path>, D: Display>(path: P, display: D) -> std::io::Result<()> {
+    remember(path.as_ref());
+    super::transaction::record(path.as_ref());
+    usdpl_back::api::files::write_single(path, display)
+}
+
+/// Write the startup value back to `path`; does nothing if there is no startup value
+pub fn restore<P: AsRef<Path>>(path: P, setting: SettingVariant) -> Result<(), SettingError> {
+    let path = path.as_ref();
+    if let Some(value) = get_path(path) {
+        log::info!("Restoring `{}` to `{}`", path.display(), value);
+        usdpl_back::api::files::write_single(path, &value).map_err(|e| SettingError {
+            msg: format!("Failed to write `{}` to `{}`: {}", value, path.display(), e),
+            setting,
+            kind: ErrorKind::from(&e),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Record SMT, online status and governors of `count` cpus
+pub fn remember_cpus(count: usize) {
+    // SMT can also be `forceoff` or `notsupported`, which can't be written back
+    remember_with(CPU_SMT_PATH, || {
+        usdpl_back::api::files::read_single::<_, String, _>(CPU_SMT_PATH)
+            .ok()
+            .filter(|smt| smt == "on" || smt == "off")
+    });
+    for i in 0..count {
+        if i != 0 {
+            remember(cpu_online_path(i));
+        }
+        remember(cpu_governor_path(i));
+    }
+}
+
+/// Restore SMT, online status and governors of `count` cpus.
+/// Online status goes first, since offline cpus don't have a governor to restore.
+pub fn restore_cpus(count: usize) -> Result<(), Vec<SettingError>> {
+    let mut errors = Vec::new();
+    restore(CPU_SMT_PATH, SettingVariant::Cpu).unwrap_or_else(|e| errors.push(e));
+    for i in 1..count {
+        restore(cpu_online_path(i), SettingVariant::Cpu).unwrap_or_else(|e| errors.push(e));
+    }
+    for i in 0..count {
+        let is_online = i == 0
+            || usdpl_back::api::files::read_single::<_, u8, _>(cpu_online_path(i))
+                .map(|x| x != 0)
+                .unwrap_or(false);
+        if is_online {
+            restore(cpu_governor_path(i), SettingVariant::Cpu).unwrap_or_else(|e| errors.push(e));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub(super) const CPU_SMT_PATH: &str = "/sys/devices/system/cpu/smt/control";
+
+#[inline]
+pub(super) fn cpu_online_path(index: usize) -> String {
+    format!("/sys/devices/system/cpu/cpu{}/online", index)
+}
+
+#[inline]
+pub(super) fn cpu_governor_path(index: usize) -> String {
+    format!(
+        "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor",
+        index
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remember_first_value_test() {
+        remember_with("test:startup_value", || Some("stock".to_owned()));
+        remember_with("test:startup_value", || Some("changed".to_owned()));
+        remember_with("test:missing_value", || None);
+        assert_eq!(get("test:startup_value"), Some("stock".to_owned()));
+        assert_eq!(get("test:missing_value"), None);
+    }
+}
@@ -3,7 +3,8 @@ use std::convert::Into;
 use super::oc_limits::{BatteryLimits, OverclockLimits};
 use super::util::ChargeMode;
 use crate::api::RangeLimit;
-use crate::persist::{BatteryEventJson, BatteryJson};
+use crate::persist::BatteryJson;
+use crate::settings::{startup, transaction};
 use crate::settings::TBattery;
 use crate::settings::{OnPowerEvent, OnResume, OnSet, PowerMode, SettingError};
 
@@ -11,165 +12,13 @@ use crate::settings::{OnPowerEvent, OnResume, OnSet, PowerMode, SettingError};
 pub struct Battery {
     pub charge_rate: Option<u64>,
     pub charge_mode: Option<ChargeMode>,
-    events: Vec<EventInstruction>,
     limits: BatteryLimits,
     state: crate::state::steam_deck::Battery,
     driver_mode: crate::persist::DriverJson,
 }
 
-#[derive(Debug, Clone)]
-enum EventTrigger {
-    PluggedIn,
-    PluggedOut,
-    BatteryAbove(f64),
-    BatteryBelow(f64),
-    Ignored,
-}
-
-#[derive(Debug, Clone)]
-struct EventInstruction {
-    trigger: EventTrigger,
-    charge_rate: Option<u64>,
-    charge_mode: Option<ChargeMode>,
-    is_triggered: bool,
-}
-
-impl OnPowerEvent for EventInstruction {
-    fn on_power_event(&mut self, new_mode: PowerMode) -> Result<(), Vec<SettingError>> {
-        match (&self.trigger, new_mode) {
-            (EventTrigger::PluggedIn, PowerMode::PluggedIn) => {
-                log::info!("Steam Deck plugged in event handled");
-                self.set_all()
-            }
-            (EventTrigger::PluggedOut, PowerMode::PluggedOut) => {
-                log::info!("Steam Deck plugged out event handled");
-                self.set_all()
-            }
-            (EventTrigger::BatteryAbove(exp), PowerMode::BatteryCharge(act)) => {
-                if act > *exp {
-                    if self.is_triggered {
-                        Ok(())
-                    } else {
-                        self.is_triggered = true;
-                        log::info!("Steam Deck battery above {} event handled", exp);
-                        self.set_all()
-                    }
-                } else {
-                    self.is_triggered = false;
-                    Ok(())
-                }
-            }
-            (EventTrigger::BatteryBelow(exp), PowerMode::BatteryCharge(act)) => {
-                if act < *exp {
-                    if self.is_triggered {
-                        Ok(())
-                    } else {
-                        self.is_triggered = true;
-                        log::info!("Steam Deck battery below {} event handled", exp);
-                        self.set_all()
-                    }
-                } else {
-                    self.is_triggered = false;
-                    Ok(())
-                }
-            }
-            _ => Ok(()),
-        }
-    }
-}
-
-impl EventInstruction {
-    #[inline]
-    fn trigger_to_str(mode: EventTrigger) -> String {
-        match mode {
-            EventTrigger::PluggedIn => "plug-in".to_owned(),
-            EventTrigger::PluggedOut => "plug-out".to_owned(),
-            EventTrigger::BatteryAbove(x) => format!(">{:#0.2}", x * 100.0),
-            EventTrigger::BatteryBelow(x) => format!("<{:#0.2}", x * 100.0),
-            EventTrigger::Ignored => "/shrug".to_owned(),
-        }
-    }
-
-    #[inline]
-    fn str_to_trigger(s: &str) -> Option<EventTrigger> {
-        match s {
-            "plug-in" => Some(EventTrigger::PluggedIn),
-            "plug-out" => Some(EventTrigger::PluggedOut),
-            s if s.starts_with('>') => s
-                .trim_start_matches('>')
-                .parse::<f64>()
-                .ok()
-                .map(|x| EventTrigger::BatteryAbove(x/100.0)),
-            s if s.starts_with('<') => s
-                .trim_start_matches('<')
-                .parse::<f64>()
-                .ok()
-                .map(|x| EventTrigger::BatteryBelow(x/100.0)),
-            _ => None,
-        }
-    }
-
-    fn from_json(other: BatteryEventJson, _version: u64) -> Self {
-        Self {
-            trigger: Self::str_to_trigger(&other.trigger).unwrap_or(EventTrigger::Ignored),
-            charge_rate: other.charge_rate,
-            charge_mode: other
-                .charge_mode
-                .map(|x| Battery::str_to_charge_mode(&x))
-                .flatten(),
-            is_triggered: false,
-        }
-    }
-
-    fn set_charge_mode(&self) -> Result<(), SettingError> {
-        if let Some(charge_mode) = self.charge_mode {
-            super::util::set(super::util::Setting::ChargeMode, charge_mode as _)
-                .map_err(|e| SettingError {
-                    msg: format!("Failed to set charge mode: {}", e),
-                    setting: crate::settings::SettingVariant::Battery,
-                })
-                .map(|_| ())
-        } else {
-            Ok(())
-        }
-    }
-
-    fn set_charge_rate(&self) -> Result<(), SettingError> {
-        if let Some(charge_rate) = self.charge_rate {
-            usdpl_back::api::files::write_single(BATTERY_CHARGE_RATE_PATH, charge_rate)
-                .map_err(|e| SettingError {
-                    msg: format!("Failed to write to `{}`: {}", BATTERY_CHARGE_RATE_PATH, e),
-                    setting: crate::settings::SettingVariant::Battery,
-                })
-                .map(|_| ())
-        } else {
-            Ok(())
-        }
-    }
-
-    fn set_all(&self) -> Result<(), Vec<SettingError>> {
-        let mut errors = Vec::new();
-
-        self.set_charge_rate().unwrap_or_else(|e| errors.push(e));
-        self.set_charge_mode().unwrap_or_else(|e| errors.push(e));
-
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
-        }
-    }
-}
-
-impl Into<BatteryEventJson> for EventInstruction {
-    fn into(self) -> BatteryEventJson {
-        BatteryEventJson {
-            trigger: Self::trigger_to_str(self.trigger),
-            charge_rate: self.charge_rate,
-            charge_mode: self.charge_mode.map(|c| Battery::charge_mode_to_str(c)),
-        }
-    }
-}
+/// Startup charge mode, read through the embedded controller since there's no sysfs file for it
+const STARTUP_CHARGE_MODE: &str = "ec:charge_mode";
 
 const BATTERY_VOLTAGE: f64 = 7.7;
 
@@ -195,13 +44,7 @@ impl Battery {
                 charge_rate: other.charge_rate,
                 charge_mode: other
                     .charge_mode
-                    .map(|x| Self::str_to_charge_mode(&x))
-                    .flatten(),
-                events: other
-                    .events
-                    .into_iter()
-                    .map(|x| EventInstruction::from_json(x, version))
-                    .collect(),
+                    .and_then(|x| Self::str_to_charge_mode(&x)),
                 limits: oc_limits,
                 state: crate::state::steam_deck::Battery::default(),
                 driver_mode: driver,
@@ -210,13 +53,7 @@ impl Battery {
                 charge_rate: other.charge_rate,
                 charge_mode: other
                     .charge_mode
-                    .map(|x| Self::str_to_charge_mode(&x))
-                    .flatten(),
-                events: other
-                    .events
-                    .into_iter()
-                    .map(|x| EventInstruction::from_json(x, version))
-                    .collect(),
+                    .and_then(|x| Self::str_to_charge_mode(&x)),
                 limits: oc_limits,
                 state: crate::state::steam_deck::Battery::default(),
                 driver_mode: driver,
@@ -244,21 +81,44 @@ impl Battery {
         }
     }
 
+    #[inline]
+    fn u8_to_charge_mode(mode: u8) -> Option<ChargeMode> {
+        match mode {
+            x if x == ChargeMode::Normal as u8 => Some(ChargeMode::Normal),
+            x if x == ChargeMode::Idle as u8 => Some(ChargeMode::Idle),
+            x if x == ChargeMode::Discharge as u8 => Some(ChargeMode::Discharge),
+            _ => None,
+        }
+    }
+
+    fn record_charge_mode_undo() {
+        transaction::record_with(STARTUP_CHARGE_MODE, || {
+            let old = super::util::get(super::util::Setting::ChargeMode).ok()?;
+            Some(Box::new(move || {
+                super::util::set(super::util::Setting::ChargeMode, old).map(|_| ())
+            }))
+        });
+    }
+
     fn set_charge_mode(&mut self) -> Result<(), SettingError> {
         if let Some(charge_mode) = self.charge_mode {
+            Self::record_charge_mode_undo();
             self.state.charge_mode_set = true;
             super::util::set(super::util::Setting::ChargeMode, charge_mode as _)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to set charge mode: {}", e),
                     setting: crate::settings::SettingVariant::Battery,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .map(|_| ())
         } else if self.state.charge_mode_set {
+            Self::record_charge_mode_undo();
             self.state.charge_mode_set = false;
             super::util::set(super::util::Setting::ChargeMode, ChargeMode::Normal as _)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to set charge mode: {}", e),
                     setting: crate::settings::SettingVariant::Battery,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .map(|_| ())
         } else {
@@ -270,14 +130,17 @@ impl Battery {
         let mut errors = Vec::new();
         if let Some(charge_rate) = self.charge_rate {
             self.state.charge_rate_set = true;
+            transaction::record(BATTERY_CHARGE_RATE_PATH);
             usdpl_back::api::files::write_single(BATTERY_CHARGE_RATE_PATH, charge_rate)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to write to `{}`: {}", BATTERY_CHARGE_RATE_PATH, e),
                     setting: crate::settings::SettingVariant::Battery,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         } else if self.state.charge_rate_set {
             self.state.charge_rate_set = false;
+            transaction::record(BATTERY_CHARGE_RATE_PATH);
             usdpl_back::api::files::write_single(
                 BATTERY_CHARGE_RATE_PATH,
                 self.limits.charge_rate.max,
@@ -285,6 +148,7 @@ impl Battery {
             .map_err(|e| SettingError {
                 msg: format!("Failed to write to `{}`: {}", BATTERY_CHARGE_RATE_PATH, e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             })
             .unwrap_or_else(|e| errors.push(e));
         }
@@ -308,6 +172,7 @@ impl Battery {
             Err(e) => Err(SettingError {
                 msg: format!("Failed to read from `{}`: {}", BATTERY_CURRENT_NOW_PATH, e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // this value is in uA, while it's set in mA
             // so convert this to mA for consistency
@@ -320,6 +185,7 @@ impl Battery {
             Err(e) => Err(SettingError {
                 msg: format!("Failed to read from `{}`: {}", BATTERY_CHARGE_NOW_PATH, e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // convert to Wh
             Ok(val) => Ok((val as f64) / 1000000.0 * BATTERY_VOLTAGE),
@@ -331,6 +197,7 @@ impl Battery {
             Err(e) => Err(SettingError {
                 msg: format!("Failed to read from `{}`: {}", BATTERY_CHARGE_FULL_PATH, e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // convert to Wh
             Ok(val) => Ok((val as f64) / 1000000.0 * BATTERY_VOLTAGE),
@@ -345,6 +212,7 @@ impl Battery {
                     BATTERY_CHARGE_DESIGN_PATH, e
                 ),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // convert to Wh
             Ok(val) => Ok((val as f64) / 1000000.0 * BATTERY_VOLTAGE),
@@ -356,6 +224,7 @@ impl Battery {
             Err(e) => Err(SettingError {
                 msg: format!("Failed to read from `{}`: {}", USB_PD_IN_MVOLTAGE_PATH, e),
                 setting: crate::settings::SettingVariant::Battery,
+                kind: crate::settings::ErrorKind::from(&e),
             }),
             // convert to V (from mV)
             Ok(val) => Ok((val as f64) / 1000.0),
@@ -373,49 +242,21 @@ impl Battery {
         Self {
             charge_rate: None,
             charge_mode: None,
-            events: Vec::new(),
             limits: oc_limits,
             state: crate::state::steam_deck::Battery::default(),
             driver_mode: driver,
         }
     }
 
-    fn find_limit_event(&self) -> Option<usize> {
-        for (i, event) in self.events.iter().enumerate() {
-            match event.trigger {
-                EventTrigger::BatteryAbove(_) => {
-                    if event.charge_mode.is_some() {
-                        return Some(i);
-                    }
-                }
-                _ => {}
-            }
-        }
-        None
-    }
-
-    fn find_unlimit_event(&self) -> Option<usize> {
-        for (i, event) in self.events.iter().enumerate() {
-            match event.trigger {
-                EventTrigger::BatteryBelow(_) => {
-                    if event.charge_mode.is_some() {
-                        return Some(i);
-                    }
-                }
-                _ => {}
-            }
-        }
-        None
-    }
 }
 
-impl Into<BatteryJson> for Battery {
+impl From<Battery> for BatteryJson {
     #[inline]
-    fn into(self) -> BatteryJson {
+    fn from(other: Battery) -> Self {
         BatteryJson {
-            charge_rate: self.charge_rate,
-            charge_mode: self.charge_mode.map(Self::charge_mode_to_str),
-            events: self.events.into_iter().map(|x| x.into()).collect(),
+            charge_rate: other.charge_rate,
+            charge_mode: other.charge_mode.map(Battery::charge_mode_to_str),
+            events: Vec::new(),
         }
     }
 }
@@ -433,36 +274,63 @@ impl OnResume for Battery {
     }
 }
 
+impl crate::settings::OnVerify for Battery {
+    fn verify(&self) -> Vec<crate::api::AppliedValue> {
+        // the charge rate can't be read back, but the charge mode can
+        self.charge_mode
+            .map(|mode| {
+                // the embedded controller is slow to read, so only read it when the mode changes
+                let actual = match self.state.charge_mode_read.get() {
+                    Some((requested, actual)) if requested == mode as u8 => actual,
+                    _ => {
+                        let actual = super::util::get(super::util::Setting::ChargeMode).ok();
+                        self.state.charge_mode_read.set(Some((mode as u8, actual)));
+                        actual
+                    }
+                };
+                let actual = actual
+                    .and_then(Self::u8_to_charge_mode)
+                    .map(Self::charge_mode_to_str);
+                crate::settings::verify::value(
+                    crate::settings::SettingVariant::Battery,
+                    STARTUP_CHARGE_MODE,
+                    Self::charge_mode_to_str(mode),
+                    actual,
+                )
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+impl crate::settings::OnShutdown for Battery {
+    fn snapshot(&self) {
+        startup::remember_with(STARTUP_CHARGE_MODE, || {
+            super::util::get(super::util::Setting::ChargeMode)
+                .ok()
+                .and_then(Self::u8_to_charge_mode)
+                .map(Self::charge_mode_to_str)
+        });
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        // the charge rate can't be read back, so go through the "unset" paths instead
+        // (this also lifts the charge limit, since that's done with the charge mode)
+        let mut copy = self.clone();
+        copy.charge_rate = None;
+        copy.charge_mode = startup::get(STARTUP_CHARGE_MODE)
+            .and_then(|mode| Self::str_to_charge_mode(&mode))
+            .filter(|mode| !matches!(mode, ChargeMode::Normal));
+        copy.state.charge_rate_set = true;
+        copy.state.charge_mode_set = true;
+        copy.set_all()
+    }
+}
+
 impl OnPowerEvent for Battery {
-    fn on_power_event(&mut self, new_mode: PowerMode) -> Result<(), Vec<SettingError>> {
-        let mut errors = Vec::new();
-        match new_mode {
-            PowerMode::PluggedIn => {
-                // plug event resets battery settings
-                self.events
-                    .iter_mut()
-                    .for_each(|ev| ev.is_triggered = false);
-                self.set_charge_mode().map_err(|e| vec![e])
-            }
-            PowerMode::PluggedOut => {
-                // plug event resets battery settings
-                self.events
-                    .iter_mut()
-                    .for_each(|ev| ev.is_triggered = false);
-                self.set_charge_mode().map_err(|e| vec![e])
-            }
-            PowerMode::BatteryCharge(_) => Ok(()),
-        }
-        .unwrap_or_else(|mut e| errors.append(&mut e));
-        for ev in &mut self.events {
-            ev.on_power_event(new_mode)
-                .unwrap_or_else(|mut e| errors.append(&mut e));
-        }
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
-        }
+    fn on_power_event(&mut self, _new_mode: PowerMode) -> Result<(), Vec<SettingError>> {
+        // the charge limit is done with triggers, see crate::settings::triggers
+        Ok(())
     }
 }
 
@@ -500,7 +368,7 @@ impl TBattery for Battery {
     }
 
     fn charge_mode(&mut self, mode: Option<String>) {
-        self.charge_mode = mode.map(|s| Self::str_to_charge_mode(&s)).flatten()
+        self.charge_mode = mode.and_then(|s| Self::str_to_charge_mode(&s))
     }
 
     fn get_charge_mode(&self) -> Option<String> {
@@ -547,83 +415,6 @@ impl TBattery for Battery {
         }
     }
 
-    fn charge_limit(&mut self, limit: Option<f64>) {
-        // upper limit
-        let index = self.find_limit_event();
-        if let Some(index) = index {
-            if let Some(limit) = limit {
-                log::info!(
-                    "Updating Steam Deck charge limit event instruction to >{}",
-                    limit
-                );
-                self.events[index] = EventInstruction {
-                    trigger: EventTrigger::BatteryAbove(limit / 100.0),
-                    charge_rate: None,
-                    charge_mode: Some(ChargeMode::Idle),
-                    is_triggered: false,
-                };
-            } else {
-                self.events.remove(index);
-            }
-        } else if let Some(limit) = limit {
-            log::info!(
-                "Creating Steam Deck charge limit event instruction of >{}",
-                limit
-            );
-            self.events.push(EventInstruction {
-                trigger: EventTrigger::BatteryAbove(limit / 100.0),
-                charge_rate: None,
-                charge_mode: Some(ChargeMode::Idle),
-                is_triggered: false,
-            });
-        }
-        // lower limit
-        let index = self.find_unlimit_event();
-        if let Some(index) = index {
-            if let Some(limit) = limit {
-                let limit = (limit - 10.0).clamp(0.0, 100.0);
-                log::info!(
-                    "Updating Steam Deck charge limit event instruction to <{}",
-                    limit
-                );
-                self.events[index] = EventInstruction {
-                    trigger: EventTrigger::BatteryBelow(limit / 100.0),
-                    charge_rate: None,
-                    charge_mode: Some(ChargeMode::Normal),
-                    is_triggered: false,
-                };
-            } else {
-                self.events.remove(index);
-            }
-        } else if let Some(limit) = limit {
-            let limit = (limit - 10.0).clamp(0.0, 100.0);
-            log::info!(
-                "Creating Steam Deck charge limit event instruction of <{}",
-                limit
-            );
-            self.events.push(EventInstruction {
-                trigger: EventTrigger::BatteryBelow(limit / 100.0),
-                charge_rate: None,
-                charge_mode: Some(ChargeMode::Normal),
-                is_triggered: false,
-            });
-        }
-    }
-
-    fn get_charge_limit(&self) -> Option<f64> {
-        let index = self.find_limit_event();
-        if let Some(index) = index {
-            if let EventTrigger::BatteryAbove(limit) = self.events[index].trigger {
-                Some(limit * 100.0)
-            } else {
-                log::error!("Got index {} for battery charge limit which does not have expected event trigger: {:?}", index, &self.events);
-                None
-            }
-        } else {
-            None
-        }
-    }
-
     fn check_power(&mut self) -> Result<Vec<PowerMode>, Vec<SettingError>> {
         log::debug!("Steam Deck power vibe check");
         let mut errors = Vec::new();
@@ -1,11 +1,14 @@
 use std::convert::Into;
 
 use super::oc_limits::{CpuLimits, CpusLimits, OverclockLimits};
+use super::od_clocks;
 use super::POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT;
-use crate::api::RangeLimit;
+use crate::api::{AppliedValue, RangeLimit};
 use crate::persist::CpuJson;
+use crate::settings::startup;
+use crate::settings::verify;
 use crate::settings::{min_max_from_json, MinMax};
-use crate::settings::{OnResume, OnSet, SettingError};
+use crate::settings::{OnResume, OnSet, OnShutdown, OnVerify, SettingError};
 use crate::settings::{TCpu, TCpus};
 
 const CPU_PRESENT_PATH: &str = "/sys/devices/system/cpu/present";
@@ -26,17 +29,19 @@ impl OnSet for Cpus {
         if self.smt_capable {
             // toggle SMT
             if self.smt {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "on")
+                startup::write_single(CPU_SMT_PATH, "on")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `on` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
             } else {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "off")
+                startup::write_single(CPU_SMT_PATH, "off")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `off` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
             }
@@ -68,6 +73,96 @@ impl OnResume for Cpus {
     }
 }
 
+impl OnVerify for Cpus {
+    fn verify(&self) -> Vec<AppliedValue> {
+        let mut values: Vec<AppliedValue> = verify::smt(self.smt_capable, self.smt)
+            .into_iter()
+            .collect();
+        // clock limits aren't read back, since the clock table only shows the last edited core
+        for cpu in &self.cpus {
+            values.append(&mut verify::cpu(
+                cpu.index,
+                cpu.online,
+                cpu.state.do_set_online,
+                &cpu.governor,
+            ));
+        }
+        values
+    }
+}
+
+impl OnShutdown for Cpus {
+    fn snapshot(&self) {
+        startup::remember_cpus(self.cpus.len());
+        od_clocks::remember(od_clocks::CPU_SECTION);
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.snapshot();
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        let mut errors = Vec::new();
+        // only the last edited core shows up in the clock table, so check for set limits too
+        let clocks_changed = self.cpus.iter().any(|cpu| cpu.clock_limits.is_some())
+            || match (
+                od_clocks::read(od_clocks::CPU_SECTION),
+                self.cpus.first().map(|cpu| cpu.stock_clocks()),
+            ) {
+                (Some(now), Some(stock)) => (now.min, now.max) != (stock.min, stock.max),
+                _ => false,
+            };
+        if clocks_changed {
+            // clock limits can only be changed in manual mode
+            POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_cpu(true, 0);
+            POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+                .enforce_level()
+                .unwrap_or_else(|mut e| errors.append(&mut e));
+            // one clock limit per core, which is shared by its hardware threads
+            for cpu in self.cpus.iter().step_by(2) {
+                let stock = cpu.stock_clocks();
+                log::info!(
+                    "Restoring CPU {} clocks to ({}, {})",
+                    cpu.index,
+                    stock.min,
+                    stock.max
+                );
+                for payload in [
+                    format!("p {} 0 {}\n", cpu.index / 2, stock.min),
+                    format!("p {} 1 {}\n", cpu.index / 2, stock.max),
+                ] {
+                    write_clock_limits(&payload).unwrap_or_else(|e| errors.push(e));
+                }
+                od_clocks::wrote_core(cpu.index / 2, stock.min, stock.max);
+            }
+            write_clock_limits("c\n").unwrap_or_else(|e| errors.push(e));
+        }
+        for i in 0..self.cpus.len() {
+            POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_cpu(false, i);
+        }
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+            .enforce_level()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        startup::restore_cpus(self.cpus.len()).unwrap_or_else(|mut e| errors.append(&mut e));
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+            .restore_level()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn write_clock_limits(payload: &str) -> Result<(), SettingError> {
+    usdpl_back::api::files::write_single(CPU_CLOCK_LIMITS_PATH, payload).map_err(|e| SettingError {
+        msg: format!(
+            "Failed to write `{}` to `{}`: {}",
+            payload, CPU_CLOCK_LIMITS_PATH, e
+        ),
+        setting: crate::settings::SettingVariant::Cpu,
+        kind: crate::settings::ErrorKind::from(&e),
+    })
+}
+
 impl Cpus {
     pub fn cpu_count() -> Option<usize> {
         let mut data: String = usdpl_back::api::files::read_single(CPU_PRESENT_PATH)
@@ -190,8 +285,7 @@ impl TCpus for Cpus {
             smt_capable: self.smt_capable,
             governors: if self.limits.global_governors {
                 self.cpus
-                    .iter()
-                    .next()
+                    .first()
                     .map(|x| x.governors())
                     .unwrap_or_else(|| Vec::with_capacity(0))
             } else {
@@ -235,6 +329,14 @@ pub struct Cpu {
 const CPU_CLOCK_LIMITS_PATH: &str = "/sys/class/drm/card0/device/pp_od_clk_voltage";
 
 impl Cpu {
+    /// Clock limits from before PowerTools changed them, or the widest allowed range
+    fn stock_clocks(&self) -> MinMax<u64> {
+        od_clocks::startup_clocks(od_clocks::CPU_SECTION).unwrap_or(MinMax {
+            min: self.limits.clock_min.min,
+            max: self.limits.clock_max.max,
+        })
+    }
+
     #[inline]
     fn from_json(other: CpuJson, version: u64, i: usize, oc_limits: CpuLimits) -> Self {
         match version {
@@ -273,6 +375,7 @@ impl Cpu {
                 clock_limits.max
             );
             self.state.clock_limits_set = true;
+            od_clocks::record_core_undo(self.index / 2, &self.stock_clocks());
             // max clock
             let payload_max = format!("p {} 1 {}\n", self.index / 2, clock_limits.max);
             usdpl_back::api::files::write_single(CPU_CLOCK_LIMITS_PATH, &payload_max)
@@ -282,6 +385,7 @@ impl Cpu {
                         &payload_max, CPU_CLOCK_LIMITS_PATH, e
                     ),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
             // min clock
@@ -298,8 +402,10 @@ impl Cpu {
                         &payload_min, CPU_CLOCK_LIMITS_PATH, e
                     ),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
+            od_clocks::wrote_core(self.index / 2, valid_min, clock_limits.max);
         } else if self.state.clock_limits_set
             || (self.state.is_resuming && !self.limits.skip_resume_reclock)
             || POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.needs_manual()
@@ -308,9 +414,11 @@ impl Cpu {
             if POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.needs_manual() {
                 POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.enforce_level()?;
                 // disable manual clock limits
+                let stock = self.stock_clocks();
                 log::debug!("Setting CPU {} to default clockspeed", self.index);
+                od_clocks::record_core_undo(self.index / 2, &stock);
                 // max clock
-                let payload_max = format!("p {} 1 {}\n", self.index / 2, self.limits.clock_max.max);
+                let payload_max = format!("p {} 1 {}\n", self.index / 2, stock.max);
                 usdpl_back::api::files::write_single(CPU_CLOCK_LIMITS_PATH, &payload_max)
                     .map_err(|e| SettingError {
                         msg: format!(
@@ -318,10 +426,11 @@ impl Cpu {
                             &payload_max, CPU_CLOCK_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
                 // min clock
-                let payload_min = format!("p {} 0 {}\n", self.index / 2, self.limits.clock_min.min);
+                let payload_min = format!("p {} 0 {}\n", self.index / 2, stock.min);
                 usdpl_back::api::files::write_single(CPU_CLOCK_LIMITS_PATH, &payload_min)
                     .map_err(|e| SettingError {
                         msg: format!(
@@ -329,8 +438,10 @@ impl Cpu {
                             &payload_min, CPU_CLOCK_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
+                od_clocks::wrote_core(self.index / 2, stock.min, stock.max);
             }
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_cpu(false, self.index);
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
@@ -344,6 +455,7 @@ impl Cpu {
                     vec![SettingError {
                         msg: format!("Failed to write `c` to `{}`: {}", CPU_CLOCK_LIMITS_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     }]
                 })
             } else {
@@ -360,10 +472,11 @@ impl Cpu {
         if self.index != 0 && self.state.do_set_online {
             // cpu0 cannot be disabled
             let online_path = cpu_online_path(self.index);
-            usdpl_back::api::files::write_single(&online_path, self.online as u8)
+            startup::write_single(&online_path, self.online as u8)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to write to `{}`: {}", &online_path, e),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -374,13 +487,14 @@ impl Cpu {
         // set governor
         if self.index == 0 || self.online {
             let governor_path = cpu_governor_path(self.index);
-            usdpl_back::api::files::write_single(&governor_path, &self.governor)
+            startup::write_single(&governor_path, &self.governor)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         &self.governor, &governor_path, e
                     ),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -454,13 +568,13 @@ impl Cpu {
     }
 }
 
-impl Into<CpuJson> for Cpu {
+impl From<Cpu> for CpuJson {
     #[inline]
-    fn into(self) -> CpuJson {
+    fn from(other: Cpu) -> Self {
         CpuJson {
-            online: self.online,
-            clock_limits: self.clock_limits.map(|x| x.into()),
-            governor: self.governor,
+            online: other.online,
+            clock_limits: other.clock_limits.map(|x| x.into()),
+            governor: other.governor,
         }
     }
 }
@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+
+use super::oc_limits::{FanLimits, OverclockLimits};
+use crate::persist::FanJson;
+use crate::settings::thermal::{find_hwmon, read_temperature};
+use crate::settings::{transaction, FanCurve, TFan};
+use crate::settings::{ErrorKind, OnResume, OnSet, SettingError, SettingVariant};
+
+/// The jupiter (Steam Deck) EC hwmon driver, which was renamed in newer kernels
+const FAN_HWMON_NAMES: &[&str] = &["jupiter", "steamdeck_hwmon"];
+const FAN_TARGET_FILE: &str = "fan1_target"; // RPM
+/// 1 = fan speed is set by fan1_target, 0 = EC controls the fan
+const FAN_RECALCULATE_FILE: &str = "recalculate";
+
+#[derive(Debug)]
+pub struct Fan {
+    curve: Option<FanCurve>,
+    limits: FanLimits,
+    state: crate::state::steam_deck::Fan,
+    driver_mode: crate::persist::DriverJson,
+}
+
+fn hwmon_file(file: &str) -> Result<PathBuf, SettingError> {
+    find_hwmon(FAN_HWMON_NAMES)
+        .map(|hwmon| hwmon.join(file))
+        .ok_or_else(|| SettingError {
+            msg: format!("Failed to find fan hwmon (tried {:?})", FAN_HWMON_NAMES),
+            setting: SettingVariant::Fan,
+            kind: ErrorKind::Unsupported,
+        })
+}
+
+fn write_hwmon(file: &str, value: u64) -> Result<(), SettingError> {
+    let path = hwmon_file(file)?;
+    transaction::record(&path);
+    usdpl_back::api::files::write_single(&path, value).map_err(|e| SettingError {
+        msg: format!("Failed to write `{}` to `{}`: {}", value, path.display(), e),
+        setting: SettingVariant::Fan,
+        kind: ErrorKind::from(&e),
+    })
+}
+
+impl Fan {
+    #[inline]
+    pub fn from_json(other: FanJson, version: u64) -> Self {
+        let (oc_limits, is_default) = OverclockLimits::load_or_default();
+        let driver = if is_default {
+            crate::persist::DriverJson::SteamDeck
+        } else {
+            crate::persist::DriverJson::SteamDeckAdvance
+        };
+        Self {
+            curve: other
+                .curve
+                .map(|points| FanCurve::from_json(points, other.sensor, version)),
+            limits: oc_limits.fan,
+            state: crate::state::steam_deck::Fan::default(),
+            driver_mode: driver,
+        }
+    }
+
+    pub fn system_default() -> Self {
+        let (oc_limits, is_default) = OverclockLimits::load_or_default();
+        Self {
+            curve: None,
+            limits: oc_limits.fan,
+            state: crate::state::steam_deck::Fan::default(),
+            driver_mode: if is_default {
+                crate::persist::DriverJson::SteamDeck
+            } else {
+                crate::persist::DriverJson::SteamDeckAdvance
+            },
+        }
+    }
+
+    fn set_speed(&mut self) -> Result<(), SettingError> {
+        let curve = if let Some(curve) = &self.curve {
+            curve
+        } else {
+            return Ok(());
+        };
+        let temperature = read_temperature(curve.sensor).ok_or_else(|| SettingError {
+            msg: format!("Failed to read {:?} temperature for fan curve", curve.sensor),
+            setting: SettingVariant::Fan,
+            kind: ErrorKind::Unsupported,
+        })?;
+        let speed = curve
+            .speed_at(temperature)
+            .unwrap_or(self.limits.speed.max)
+            .clamp(self.limits.speed.min, self.limits.speed.max);
+        if !self.state.manual {
+            write_hwmon(FAN_RECALCULATE_FILE, 1)?;
+            self.state.manual = true;
+            log::info!("Took fan control from EC");
+        }
+        if self.state.last_speed != Some(speed) {
+            log::debug!("Setting fan speed to {} RPM ({}C)", speed, temperature);
+            write_hwmon(FAN_TARGET_FILE, speed)?;
+            self.state.last_speed = Some(speed);
+        }
+        Ok(())
+    }
+
+    /// Hand fan control back to the EC
+    fn release(&mut self) -> Result<(), SettingError> {
+        if self.state.manual {
+            self.state.manual = false;
+            self.state.last_speed = None;
+            log::info!("Returning fan control to EC");
+            write_hwmon(FAN_RECALCULATE_FILE, 0)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_all(&mut self) -> Result<(), Vec<SettingError>> {
+        let mut errors = Vec::new();
+        if self.curve.is_some() {
+            if let Err(e) = self.set_speed() {
+                errors.push(e);
+                // fail safe: let the EC keep the fan going
+                self.state.manual = true;
+                self.release().unwrap_or_else(|e| errors.push(e));
+            }
+        } else {
+            self.release().unwrap_or_else(|e| errors.push(e));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn clamp_all(&mut self) {
+        if let Some(curve) = &mut self.curve {
+            curve.clamp(self.limits.speed.min, self.limits.speed.max);
+        }
+    }
+}
+
+impl Drop for Fan {
+    fn drop(&mut self) {
+        if let Err(e) = self.release() {
+            log::error!("Failed to return fan control to EC: {}", e);
+        }
+    }
+}
+
+impl OnSet for Fan {
+    fn on_set(&mut self) -> Result<(), Vec<SettingError>> {
+        self.clamp_all();
+        self.set_all()
+    }
+}
+
+impl OnResume for Fan {
+    fn on_resume(&self) -> Result<(), Vec<SettingError>> {
+        // the EC may have taken back control during sleep
+        if let (true, Some(speed)) = (self.state.manual, self.state.last_speed) {
+            write_hwmon(FAN_RECALCULATE_FILE, 1)
+                .and_then(|_| write_hwmon(FAN_TARGET_FILE, speed))
+                .map_err(|e| vec![e])
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl crate::settings::OnPowerEvent for Fan {}
+
+impl crate::settings::OnShutdown for Fan {
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        self.release().map_err(|e| vec![e])
+    }
+}
+
+impl crate::settings::OnVerify for Fan {}
+
+impl TFan for Fan {
+    fn limits(&self) -> crate::api::FanLimits {
+        crate::api::FanLimits {
+            speed_limits: Some(crate::api::RangeLimit {
+                min: self.limits.speed.min,
+                max: self.limits.speed.max,
+            }),
+            speed_step: self.limits.speed_step,
+            speed_unit: "rpm".to_owned(),
+            sensors: vec![
+                crate::persist::ThermalSensorJson::Cpu,
+                crate::persist::ThermalSensorJson::Gpu,
+                crate::persist::ThermalSensorJson::Battery,
+            ],
+        }
+    }
+
+    fn json(&self) -> crate::persist::FanJson {
+        FanCurve::to_json(self.curve.as_ref())
+    }
+
+    fn curve(&mut self, curve: Option<FanCurve>) {
+        self.curve = curve;
+    }
+
+    fn update(&mut self) -> Result<(), Vec<SettingError>> {
+        if self.curve.is_some() {
+            self.set_all()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn provider(&self) -> crate::persist::DriverJson {
+        self.driver_mode.clone()
+    }
+}
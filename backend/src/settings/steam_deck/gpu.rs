@@ -1,12 +1,15 @@
 use std::convert::Into;
 
 use super::oc_limits::{GpuLimits, OverclockLimits};
+use super::od_clocks;
 use super::POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT;
-use crate::api::RangeLimit;
+use crate::api::{AppliedValue, RangeLimit};
 use crate::persist::GpuJson;
+use crate::settings::verify;
 use crate::settings::TGpu;
 use crate::settings::{min_max_from_json, MinMax};
-use crate::settings::{OnResume, OnSet, SettingError};
+use crate::settings::{startup, transaction};
+use crate::settings::{OnResume, OnSet, OnShutdown, OnVerify, SettingError, SettingVariant};
 
 const SLOW_PPT: u8 = 1;
 const FAST_PPT: u8 = 2;
@@ -57,11 +60,20 @@ impl Gpu {
         }
     }
 
+    /// Clock limits from before PowerTools changed them, or the widest limits if unknown
+    fn stock_clocks(&self) -> MinMax<u64> {
+        od_clocks::startup_clocks(od_clocks::GPU_SECTION).unwrap_or(MinMax {
+            min: self.limits.clock_min.min,
+            max: self.limits.clock_max.max,
+        })
+    }
+
     fn set_clocks(&mut self) -> Result<(), Vec<SettingError>> {
         let mut errors = Vec::new();
         if let Some(clock_limits) = &self.clock_limits {
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_gpu(true);
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.enforce_level()?;
+            od_clocks::record_undo(od_clocks::GPU_SECTION, "s");
             // set clock limits
             self.state.clock_limits_set = true;
             // max clock
@@ -73,6 +85,7 @@ impl Gpu {
                         &payload_max, GPU_CLOCK_LIMITS_PATH, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
             // min clock
@@ -84,6 +97,7 @@ impl Gpu {
                         &payload_min, GPU_CLOCK_LIMITS_PATH, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
             usdpl_back::api::files::write_single(GPU_CLOCK_LIMITS_PATH, "c\n").unwrap_or_else(
@@ -91,6 +105,7 @@ impl Gpu {
                     errors.push(SettingError {
                         msg: format!("Failed to write `c` to `{}`: {}", GPU_CLOCK_LIMITS_PATH, e),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                 },
             );
@@ -101,9 +116,11 @@ impl Gpu {
             self.state.clock_limits_set = false;
             if POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.needs_manual() {
                 POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.enforce_level()?;
+                od_clocks::record_undo(od_clocks::GPU_SECTION, "s");
                 // disable manual clock limits
+                let stock = self.stock_clocks();
                 // max clock
-                let payload_max = format!("s 1 {}\n", self.limits.clock_max.max);
+                let payload_max = format!("s 1 {}\n", stock.max);
                 usdpl_back::api::files::write_single(GPU_CLOCK_LIMITS_PATH, &payload_max)
                     .map_err(|e| SettingError {
                         msg: format!(
@@ -111,10 +128,11 @@ impl Gpu {
                             &payload_max, GPU_CLOCK_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
                 // min clock
-                let payload_min = format!("s 0 {}\n", self.limits.clock_min.min);
+                let payload_min = format!("s 0 {}\n", stock.min);
                 usdpl_back::api::files::write_single(GPU_CLOCK_LIMITS_PATH, &payload_min)
                     .map_err(|e| SettingError {
                         msg: format!(
@@ -122,6 +140,7 @@ impl Gpu {
                             &payload_min, GPU_CLOCK_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| errors.push(e));
                 usdpl_back::api::files::write_single(GPU_CLOCK_LIMITS_PATH, "c\n").unwrap_or_else(
@@ -132,6 +151,7 @@ impl Gpu {
                                 GPU_CLOCK_LIMITS_PATH, e
                             ),
                             setting: crate::settings::SettingVariant::Gpu,
+                            kind: crate::settings::ErrorKind::from(&e),
                         })
                     },
                 );
@@ -156,19 +176,23 @@ impl Gpu {
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
                 .enforce_level()
                 .unwrap_or_else(|mut e| errors.append(&mut e));
+            transaction::record(GPU_MEMORY_DOWNCLOCK_PATH);
             usdpl_back::api::files::write_single(GPU_MEMORY_DOWNCLOCK_PATH, self.slow_memory as u8)
                 .unwrap_or_else(|e| {
                     errors.push(SettingError {
                         msg: format!("Failed to write to `{}`: {}", GPU_MEMORY_DOWNCLOCK_PATH, e),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     });
                 });
         } else if POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.needs_manual() {
+            transaction::record(GPU_MEMORY_DOWNCLOCK_PATH);
             usdpl_back::api::files::write_single(GPU_MEMORY_DOWNCLOCK_PATH, self.slow_memory as u8)
                 .unwrap_or_else(|e| {
                     errors.push(SettingError {
                         msg: format!("Failed to write to `{}`: {}", GPU_MEMORY_DOWNCLOCK_PATH, e),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     });
                 });
             POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_gpu(self.clock_limits.is_some());
@@ -185,6 +209,7 @@ impl Gpu {
                     vec![SettingError {
                         msg: format!("Failed to write `c` to `{}`: {}", GPU_CLOCK_LIMITS_PATH, e),
                         setting: crate::settings::SettingVariant::Gpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     }]
                 })
             } else {
@@ -201,28 +226,32 @@ impl Gpu {
         if let Some(fast_ppt) = &self.fast_ppt {
             self.state.fast_ppt_set = true;
             let fast_ppt_path = gpu_power_path(FAST_PPT);
-            usdpl_back::api::files::write_single(&fast_ppt_path, fast_ppt)
+            startup::write_single(&fast_ppt_path, fast_ppt)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         fast_ppt, &fast_ppt_path, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| {
                     errors.push(e);
                 });
         } else if self.state.fast_ppt_set {
             self.state.fast_ppt_set = false;
-            let fast_ppt = self.limits.fast_ppt_default;
             let fast_ppt_path = gpu_power_path(FAST_PPT);
-            usdpl_back::api::files::write_single(&fast_ppt_path, fast_ppt)
+            let fast_ppt = startup::get_path(&fast_ppt_path)
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(self.limits.fast_ppt_default);
+            startup::write_single(&fast_ppt_path, fast_ppt)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         fast_ppt, &fast_ppt_path, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| {
                     errors.push(e);
@@ -232,28 +261,32 @@ impl Gpu {
         if let Some(slow_ppt) = &self.slow_ppt {
             self.state.slow_ppt_set = true;
             let slow_ppt_path = gpu_power_path(SLOW_PPT);
-            usdpl_back::api::files::write_single(&slow_ppt_path, slow_ppt)
+            startup::write_single(&slow_ppt_path, slow_ppt)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         slow_ppt, &slow_ppt_path, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| {
                     errors.push(e);
                 });
         } else if self.state.slow_ppt_set {
             self.state.slow_ppt_set = false;
-            let slow_ppt = self.limits.slow_ppt_default;
             let slow_ppt_path = gpu_power_path(SLOW_PPT);
-            usdpl_back::api::files::write_single(&slow_ppt_path, slow_ppt)
+            let slow_ppt = startup::get_path(&slow_ppt_path)
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(self.limits.slow_ppt_default);
+            startup::write_single(&slow_ppt_path, slow_ppt)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         slow_ppt, &slow_ppt_path, e
                     ),
                     setting: crate::settings::SettingVariant::Gpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| {
                     errors.push(e);
@@ -303,14 +336,14 @@ impl Gpu {
     }
 }
 
-impl Into<GpuJson> for Gpu {
+impl From<Gpu> for GpuJson {
     #[inline]
-    fn into(self) -> GpuJson {
+    fn from(other: Gpu) -> Self {
         GpuJson {
-            fast_ppt: self.fast_ppt,
-            slow_ppt: self.slow_ppt,
-            clock_limits: self.clock_limits.map(|x| x.into()),
-            slow_memory: self.slow_memory,
+            fast_ppt: other.fast_ppt,
+            slow_ppt: other.slow_ppt,
+            clock_limits: other.clock_limits.map(|x| x.into()),
+            slow_memory: other.slow_memory,
         }
     }
 }
@@ -322,6 +355,92 @@ impl OnSet for Gpu {
     }
 }
 
+impl OnVerify for Gpu {
+    fn verify(&self) -> Vec<AppliedValue> {
+        let mut values = Vec::new();
+        if let Some(fast_ppt) = self.fast_ppt {
+            values.push(verify::read_back(
+                SettingVariant::Gpu,
+                gpu_power_path(FAST_PPT),
+                fast_ppt,
+            ));
+        }
+        if let Some(slow_ppt) = self.slow_ppt {
+            values.push(verify::read_back(
+                SettingVariant::Gpu,
+                gpu_power_path(SLOW_PPT),
+                slow_ppt,
+            ));
+        }
+        if let Some(clock_limits) = &self.clock_limits {
+            values.push(od_clocks::verify(
+                od_clocks::GPU_SECTION,
+                SettingVariant::Gpu,
+                clock_limits,
+            ));
+        }
+        values
+    }
+}
+
+impl OnShutdown for Gpu {
+    fn snapshot(&self) {
+        for ppt in [FAST_PPT, SLOW_PPT] {
+            startup::remember(gpu_power_path(ppt));
+        }
+        od_clocks::remember(od_clocks::GPU_SECTION);
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.snapshot();
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        let mut errors = Vec::new();
+        for ppt in [FAST_PPT, SLOW_PPT] {
+            startup::restore(gpu_power_path(ppt), crate::settings::SettingVariant::Gpu)
+                .unwrap_or_else(|e| errors.push(e));
+        }
+        let stock = self.stock_clocks();
+        if let Some(now) = od_clocks::read(od_clocks::GPU_SECTION) {
+            if (now.min, now.max) != (stock.min, stock.max) {
+                log::info!("Restoring GPU clocks to ({}, {})", stock.min, stock.max);
+                // clock limits can only be changed in manual mode
+                POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_gpu(true);
+                POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+                    .enforce_level()
+                    .unwrap_or_else(|mut e| errors.append(&mut e));
+                for payload in [
+                    format!("s 0 {}\n", stock.min),
+                    format!("s 1 {}\n", stock.max),
+                    "c\n".to_owned(),
+                ] {
+                    usdpl_back::api::files::write_single(GPU_CLOCK_LIMITS_PATH, &payload)
+                        .map_err(|e| SettingError {
+                            msg: format!(
+                                "Failed to write `{}` to `{}`: {}",
+                                &payload, GPU_CLOCK_LIMITS_PATH, e
+                            ),
+                            setting: crate::settings::SettingVariant::Gpu,
+                            kind: crate::settings::ErrorKind::from(&e),
+                        })
+                        .unwrap_or_else(|e| errors.push(e));
+                }
+            }
+        }
+        // leaving manual mode also puts memory clocks back to automatic
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT.set_gpu(false);
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+            .enforce_level()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT
+            .restore_level()
+            .unwrap_or_else(|mut e| errors.append(&mut e));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 impl OnResume for Gpu {
     fn on_resume(&self) -> Result<(), Vec<SettingError>> {
         let mut copy = self.clone();
@@ -1,13 +1,16 @@
 mod battery;
 mod cpu;
+mod fan;
 mod gpu;
 mod oc_limits;
+mod od_clocks;
 mod power_dpm_force;
 mod util;
 
 pub use battery::Battery;
-pub use cpu::{Cpu, Cpus};
+pub use cpu::Cpus;
+pub use fan::Fan;
 pub use gpu::Gpu;
-pub(self) use power_dpm_force::POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT;
+use power_dpm_force::POWER_DPM_FORCE_PERFORMANCE_LEVEL_MGMT;
 
 pub use util::flash_led;
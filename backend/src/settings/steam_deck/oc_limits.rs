@@ -3,21 +3,13 @@ use serde::{Deserialize, Serialize};
 
 const OC_LIMITS_FILEPATH: &str = "pt_oc.json";
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub(super) struct OverclockLimits {
     pub battery: BatteryLimits,
     pub cpus: CpusLimits,
     pub gpu: GpuLimits,
-}
-
-impl Default for OverclockLimits {
-    fn default() -> Self {
-        Self {
-            battery: BatteryLimits::default(),
-            cpus: CpusLimits::default(),
-            gpu: GpuLimits::default(),
-        }
-    }
+    #[serde(default)]
+    pub fan: FanLimits,
 }
 
 impl OverclockLimits {
@@ -163,6 +155,21 @@ impl Default for GpuLimits {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(super) struct FanLimits {
+    pub speed: MinMax<u64>,
+    pub speed_step: u64,
+}
+
+impl Default for FanLimits {
+    fn default() -> Self {
+        Self {
+            speed: MinMax { min: 0, max: 7300 },
+            speed_step: 100,
+        }
+    }
+}
+
 fn oc_limits_filepath() -> std::path::PathBuf {
     crate::utility::settings_dir().join(OC_LIMITS_FILEPATH)
 }
@@ -0,0 +1,152 @@
+//! Reading back the overdrive clock table, so clock limits can be restored to their startup values.
+//! The table looks like this on the Steam Deck (only the last edited CPU core is shown):
+//!
+//! ```text
+//! OD_SCLK:
+//! 0:        200Mhz
+//! 1:       1600Mhz
+//! CCLK_RANGE in Core0:
+//! 0:       1400Mhz
+//! 1:       3500Mhz
+//! OD_RANGE:
+//! SCLK:     200Mhz       1600Mhz
+//! CCLK:    1400Mhz       3500Mhz
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::api::AppliedValue;
+use crate::settings::{startup, transaction, verify, MinMax, SettingVariant};
+
+const OD_CLOCKS_PATH: &str = "/sys/class/drm/card0/device/pp_od_clk_voltage";
+
+pub const GPU_SECTION: &str = "OD_SCLK";
+pub const CPU_SECTION: &str = "CCLK_RANGE";
+
+/// CPU clock limits last written to each core as (min, max),
+/// since the table only shows the last edited core
+static CORE_CLOCKS: Mutex<BTreeMap<usize, (u64, u64)>> = Mutex::new(BTreeMap::new());
+
+fn parse_section(table: &str, section: &str) -> Option<MinMax<u64>> {
+    let mut lines = table
+        .lines()
+        .skip_while(|line| !line.starts_with(section))
+        .skip(1);
+    let mut next_clock = |level: &str| -> Option<u64> {
+        lines
+            .next()?
+            .trim()
+            .strip_prefix(level)?
+            .trim()
+            .trim_end_matches(|c: char| c.is_alphabetic())
+            .parse()
+            .ok()
+    };
+    let min = next_clock("0:")?;
+    let max = next_clock("1:")?;
+    Some(MinMax { min, max })
+}
+
+/// Current clock limits of a section of the table
+pub fn read(section: &str) -> Option<MinMax<u64>> {
+    parse_section(&std::fs::read_to_string(OD_CLOCKS_PATH).ok()?, section)
+}
+
+#[inline]
+fn key(section: &str) -> String {
+    format!("{}:{}", OD_CLOCKS_PATH, section)
+}
+
+pub fn remember(section: &str) {
+    startup::remember_with(&key(section), || {
+        read(section).map(|clocks| format!("{} {}", clocks.min, clocks.max))
+    });
+}
+
+/// Record the current clock limits of a section for rolling back the open transaction.
+/// `command` is what sets the section's limits (e.g. `s` for GPU clocks).
+/// CPU clocks can't be read back like this, so they use record_core_undo() instead.
+pub fn record_undo(section: &'static str, command: &'static str) {
+    transaction::record_with(&key(section), || {
+        let old = read(section)?;
+        Some(Box::new(move || {
+            for payload in [
+                format!("{} 0 {}\n", command, old.min),
+                format!("{} 1 {}\n", command, old.max),
+                "c\n".to_owned(),
+            ] {
+                usdpl_back::api::files::write_single(OD_CLOCKS_PATH, payload)?;
+            }
+            Ok(())
+        }))
+    });
+}
+
+/// Note the clock limits just written to a CPU core, for record_core_undo()
+pub fn wrote_core(core: usize, min: u64, max: u64) {
+    CORE_CLOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(core, (min, max));
+}
+
+/// Record the clock limits last written to a CPU core for rolling back the open transaction.
+/// Cores which haven't been written to yet are put back to `stock`.
+pub fn record_core_undo(core: usize, stock: &MinMax<u64>) {
+    let stock = (stock.min, stock.max);
+    transaction::record_with(&format!("{}:{}", key(CPU_SECTION), core), || {
+        let (min, max) = CORE_CLOCKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&core)
+            .copied()
+            .unwrap_or(stock);
+        Some(Box::new(move || {
+            for payload in [
+                format!("p {} 0 {}\n", core, min),
+                format!("p {} 1 {}\n", core, max),
+                "c\n".to_owned(),
+            ] {
+                usdpl_back::api::files::write_single(OD_CLOCKS_PATH, payload)?;
+            }
+            wrote_core(core, min, max);
+            Ok(())
+        }))
+    });
+}
+
+/// Compare `requested` to the current clock limits of a section of the table
+pub fn verify(section: &str, setting: SettingVariant, requested: &MinMax<u64>) -> AppliedValue {
+    verify::value(
+        setting,
+        key(section),
+        format!("{} {}", requested.min, requested.max),
+        read(section).map(|clocks| format!("{} {}", clocks.min, clocks.max)),
+    )
+}
+
+/// Clock limits of a section of the table from before PowerTools changed them
+pub fn startup_clocks(section: &str) -> Option<MinMax<u64>> {
+    let value = startup::get(&key(section))?;
+    let (min, max) = value.split_once(' ')?;
+    Some(MinMax {
+        min: min.parse().ok()?,
+        max: max.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_od_table_test() {
+        let table = "OD_SCLK:\n0:        200Mhz\n1:       1600Mhz\nCCLK_RANGE in Core0:\n0:       1400Mhz\n1:       3500Mhz\nOD_RANGE:\nSCLK:     200Mhz       1600Mhz\nCCLK:    1400Mhz       3500Mhz\n";
+        let gpu = parse_section(table, GPU_SECTION).expect("Missing GPU clocks");
+        assert_eq!((gpu.min, gpu.max), (200, 1600));
+        let cpu = parse_section(table, CPU_SECTION).expect("Missing CPU clocks");
+        assert_eq!((cpu.min, cpu.max), (1400, 3500));
+        assert!(parse_section(table, "OD_MCLK").is_none());
+    }
+}
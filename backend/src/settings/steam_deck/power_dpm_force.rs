@@ -5,6 +5,7 @@
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::settings::startup;
 use crate::settings::SettingError;
 
 const DEFAULT_BITS: u64 = 0;
@@ -56,20 +57,38 @@ impl PDFPLManager {
         self.set(DEFAULT_BITS);
     }
 
+    pub fn snapshot(&self) {
+        startup::remember(DPM_FORCE_LIMITS_PATH);
+    }
+
+    /// Put the performance level back to how it was at startup, once nothing needs manual anymore
+    pub fn restore_level(&self) -> Result<(), Vec<SettingError>> {
+        if self.needs_manual() {
+            Ok(())
+        } else {
+            startup::restore(
+                DPM_FORCE_LIMITS_PATH,
+                crate::settings::SettingVariant::General,
+            )
+            .map_err(|e| vec![e])
+        }
+    }
+
     pub fn enforce_level(&self) -> Result<(), Vec<SettingError>> {
         let needs = self.needs_manual();
         let mut errors = Vec::new();
-        let mode: String = usdpl_back::api::files::read_single(DPM_FORCE_LIMITS_PATH.to_owned())
-            .map_err(|e| {
+        let mode: String =
+            usdpl_back::api::files::read_single(DPM_FORCE_LIMITS_PATH).map_err(|e| {
                 vec![SettingError {
                     msg: format!("Failed to read `{}`: {}", DPM_FORCE_LIMITS_PATH, e),
                     setting: crate::settings::SettingVariant::General,
+                    kind: crate::settings::ErrorKind::from(&e),
                 }]
             })?;
         if mode != "manual" && needs {
             log::info!("Setting `{}` to manual", DPM_FORCE_LIMITS_PATH);
             // set manual control
-            usdpl_back::api::files::write_single(DPM_FORCE_LIMITS_PATH, "manual")
+            startup::write_single(DPM_FORCE_LIMITS_PATH, "manual")
                 .map_err(|e| {
                     errors.push(SettingError {
                         msg: format!(
@@ -77,13 +96,14 @@ impl PDFPLManager {
                             DPM_FORCE_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::General,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                 })
                 .unwrap_or(());
         } else if mode != "auto" && !needs {
             log::info!("Setting `{}` to auto", DPM_FORCE_LIMITS_PATH);
             // unset manual control
-            usdpl_back::api::files::write_single(DPM_FORCE_LIMITS_PATH, "auto")
+            startup::write_single(DPM_FORCE_LIMITS_PATH, "auto")
                 .map_err(|e| {
                     errors.push(SettingError {
                         msg: format!(
@@ -91,12 +111,13 @@ impl PDFPLManager {
                             DPM_FORCE_LIMITS_PATH, e
                         ),
                         setting: crate::settings::SettingVariant::General,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                 })
                 .unwrap_or(());
         }
         if let Ok(mode_now) =
-            usdpl_back::api::files::read_single::<_, String, _>(DPM_FORCE_LIMITS_PATH.to_owned())
+            usdpl_back::api::files::read_single::<_, String, _>(DPM_FORCE_LIMITS_PATH)
         {
             log::debug!("Mode for `{}` is now `{}`", DPM_FORCE_LIMITS_PATH, mode_now);
         } else {
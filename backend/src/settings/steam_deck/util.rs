@@ -36,7 +36,7 @@ fn read_from(location: u64) -> Result<u8, Error> {
     let mut file = OpenOptions::new().read(true).open("/dev/port")?;
     file.seek(SeekFrom::Start(location))?;
     let mut buffer = [0];
-    file.read(&mut buffer)?;
+    file.read_exact(&mut buffer)?;
     Ok(buffer[0])
 }
 
@@ -96,6 +96,10 @@ pub fn set(setting: Setting, mode: u8) -> Result<usize, Error> {
     write2(setting as u8, mode)
 }
 
+pub fn get(setting: Setting) -> Result<u8, Error> {
+    write_read(setting as u8)
+}
+
 #[derive(Copy, Clone)]
 #[repr(u8)]
 pub enum Setting {
@@ -0,0 +1,362 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::{MinMax, TCpus, TGpu};
+use crate::persist::{ThermalActionJson, ThermalRuleJson, ThermalSensorJson};
+
+const HWMON_DIR: &str = "/sys/class/hwmon";
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+const CPU_HWMON_NAMES: &[&str] = &["k10temp", "coretemp", "zenpower"];
+pub const GPU_HWMON_NAMES: &[&str] = &["amdgpu"];
+
+fn read_number<P: AsRef<Path>>(path: P) -> Option<f64> {
+    usdpl_back::api::files::read_single::<_, f64, _>(path).ok()
+}
+
+/// Find the hwmon directory of the first device with one of the given names
+pub fn find_hwmon(names: &[&str]) -> Option<PathBuf> {
+    std::fs::read_dir(HWMON_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            usdpl_back::api::files::read_single::<_, String, _>(path.join("name"))
+                .map(|name| names.contains(&name.trim()))
+                .unwrap_or(false)
+        })
+}
+
+/// Find the power_supply directory of the first battery
+pub fn find_battery() -> Option<PathBuf> {
+    std::fs::read_dir(POWER_SUPPLY_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            usdpl_back::api::files::read_single::<_, String, _>(path.join("type"))
+                .map(|t| t.trim() == "Battery")
+                .unwrap_or(false)
+        })
+}
+
+/// Read a temperature sensor, in degrees Celsius
+pub fn read_temperature(sensor: ThermalSensorJson) -> Option<f64> {
+    match sensor {
+        ThermalSensorJson::Cpu => find_hwmon(CPU_HWMON_NAMES)
+            .and_then(|hwmon| read_number(hwmon.join("temp1_input")))
+            .map(|millis| millis / 1000.0),
+        ThermalSensorJson::Gpu => find_hwmon(GPU_HWMON_NAMES)
+            .and_then(|hwmon| read_number(hwmon.join("temp1_input")))
+            .map(|millis| millis / 1000.0),
+        ThermalSensorJson::Battery => find_battery()
+            .and_then(|bat| read_number(bat.join("temp")))
+            .map(|decis| decis / 10.0),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ThermalRule {
+    rule: ThermalRuleJson,
+    over_since: Option<Instant>,
+    under_since: Option<Instant>,
+    active: bool,
+}
+
+impl ThermalRule {
+    fn update(&mut self, temperature: Option<f64>, now: Instant) {
+        let temperature = if let Some(t) = temperature {
+            t
+        } else {
+            // sensor disappeared; keep current state but restart timers
+            self.over_since = None;
+            self.under_since = None;
+            return;
+        };
+        let duration = Duration::from_secs(self.rule.duration);
+        if self.active {
+            if temperature <= self.rule.threshold - self.rule.hysteresis {
+                let since = *self.under_since.get_or_insert(now);
+                if now.duration_since(since) >= duration {
+                    log::info!(
+                        "Thermal rule {:?} < {}C released ({}C)",
+                        self.rule.sensor,
+                        self.rule.threshold - self.rule.hysteresis,
+                        temperature
+                    );
+                    self.active = false;
+                    self.under_since = None;
+                }
+            } else {
+                self.under_since = None;
+            }
+        } else if temperature >= self.rule.threshold {
+            let since = *self.over_since.get_or_insert(now);
+            if now.duration_since(since) >= duration {
+                log::info!(
+                    "Thermal rule {:?} >= {}C triggered ({}C)",
+                    self.rule.sensor,
+                    self.rule.threshold,
+                    temperature
+                );
+                self.active = true;
+                self.over_since = None;
+            }
+        } else {
+            self.over_since = None;
+        }
+    }
+}
+
+/// Values which are (or were) overwritten by thermal throttling, in API units
+#[derive(Debug, Clone, PartialEq)]
+struct ThrottleValues {
+    fast_ppt: Option<u64>,
+    slow_ppt: Option<u64>,
+    cpu_clocks: Vec<Option<(u64, u64)>>, // (min, max)
+}
+
+impl ThrottleValues {
+    fn capture(gpu: &dyn TGpu, cpus: &mut dyn TCpus) -> Self {
+        let (fast_ppt, slow_ppt) = gpu.get_ppt();
+        Self {
+            fast_ppt,
+            slow_ppt,
+            cpu_clocks: cpus
+                .cpus()
+                .iter()
+                .map(|cpu| cpu.get_clock_limits().map(|x| (x.min, x.max)))
+                .collect(),
+        }
+    }
+
+    /// Same values, but in the units used when saving settings (which may differ from API units)
+    fn capture_json(gpu: &dyn TGpu, cpus: &dyn TCpus) -> Self {
+        let gpu = gpu.json();
+        Self {
+            fast_ppt: gpu.fast_ppt,
+            slow_ppt: gpu.slow_ppt,
+            cpu_clocks: cpus
+                .json()
+                .iter()
+                .map(|cpu| cpu.clock_limits.as_ref().map(|x| (x.min, x.max)))
+                .collect(),
+        }
+    }
+
+    fn apply(&self, gpu: &mut dyn TGpu, cpus: &mut dyn TCpus) {
+        gpu.ppt(self.fast_ppt, self.slow_ppt);
+        for (cpu, clocks) in cpus.cpus().into_iter().zip(self.cpu_clocks.iter()) {
+            cpu.clock_limits(clocks.map(|(min, max)| MinMax { min, max }));
+        }
+    }
+}
+
+/// Thermal throttling rules and their runtime state.
+/// While any rule is active, the lowest limits of all active rules are applied on top of the user's settings.
+#[derive(Debug, Default)]
+pub struct Thermal {
+    rules: Vec<ThermalRule>,
+    /// User's settings from before throttling started
+    original: Option<ThrottleValues>,
+    /// Throttled values last applied
+    applied: Option<ThrottleValues>,
+    /// `original` and `applied`, as saved to json
+    original_json: Option<ThrottleValues>,
+    applied_json: Option<ThrottleValues>,
+}
+
+impl Thermal {
+    pub fn from_json(rules: Vec<ThermalRuleJson>) -> Self {
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|rule| ThermalRule {
+                    rule,
+                    over_since: None,
+                    under_since: None,
+                    active: false,
+                })
+                .collect(),
+            original: None,
+            applied: None,
+            original_json: None,
+            applied_json: None,
+        }
+    }
+
+    pub fn json(&self) -> Vec<ThermalRuleJson> {
+        self.rules.iter().map(|r| r.rule.clone()).collect()
+    }
+
+    /// Read sensors and throttle or restore accordingly.
+    /// Returns true when settings were modified and need to be applied.
+    pub fn check(&mut self, gpu: &mut dyn TGpu, cpus: &mut dyn TCpus) -> bool {
+        self.check_with(Instant::now(), read_temperature, gpu, cpus)
+    }
+
+    fn check_with<F: Fn(ThermalSensorJson) -> Option<f64>>(
+        &mut self,
+        now: Instant,
+        read_temp: F,
+        gpu: &mut dyn TGpu,
+        cpus: &mut dyn TCpus,
+    ) -> bool {
+        for rule in self.rules.iter_mut() {
+            rule.update(read_temp(rule.rule.sensor), now);
+        }
+        if !self.rules.iter().any(|r| r.active) {
+            return self.restore(gpu, cpus);
+        }
+        let current = ThrottleValues::capture(gpu, cpus);
+        if self.applied.as_ref() != Some(&current) {
+            // first throttle, or settings were changed by the user while throttled
+            self.original = Some(current.clone());
+            self.original_json = Some(ThrottleValues::capture_json(gpu, cpus));
+        }
+        let throttled = self.throttled(self.original.as_ref().unwrap(), cpus);
+        let changed = throttled != current;
+        if changed {
+            log::info!("Applying thermal throttle {:?}", throttled);
+            throttled.apply(gpu, cpus);
+        }
+        self.applied = Some(throttled);
+        self.applied_json = Some(ThrottleValues::capture_json(gpu, cpus));
+        changed
+    }
+
+    /// Restore settings from before throttling, if throttled.
+    /// Returns true when settings were modified and need to be applied.
+    pub fn restore(&mut self, gpu: &mut dyn TGpu, cpus: &mut dyn TCpus) -> bool {
+        self.applied = None;
+        self.original_json = None;
+        self.applied_json = None;
+        if let Some(original) = self.original.take() {
+            log::info!(
+                "Restoring settings from before thermal throttle {:?}",
+                original
+            );
+            original.apply(gpu, cpus);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replace throttled values in the settings json with the originals, so throttling is not persisted.
+    /// Values which were changed since throttling was applied are left as-is.
+    pub fn unthrottled_json(&self, json: &mut crate::persist::SettingsJson) {
+        if let (Some(original), Some(applied)) = (&self.original_json, &self.applied_json) {
+            if json.gpu.fast_ppt == applied.fast_ppt {
+                json.gpu.fast_ppt = original.fast_ppt;
+            }
+            if json.gpu.slow_ppt == applied.slow_ppt {
+                json.gpu.slow_ppt = original.slow_ppt;
+            }
+            for ((cpu, original), applied) in json
+                .cpus
+                .iter_mut()
+                .zip(original.cpu_clocks.iter())
+                .zip(applied.cpu_clocks.iter())
+            {
+                if cpu.clock_limits.as_ref().map(|x| (x.min, x.max)) == *applied {
+                    cpu.clock_limits =
+                        original.map(|(min, max)| crate::persist::MinMaxJson { min, max });
+                }
+            }
+        }
+    }
+
+    fn throttled(&self, original: &ThrottleValues, cpus: &dyn TCpus) -> ThrottleValues {
+        let mut fast_ppt = None;
+        let mut slow_ppt = None;
+        let mut cpu_clock_max = None;
+        for rule in self.rules.iter().filter(|r| r.active) {
+            let action: &ThermalActionJson = &rule.rule.action;
+            fast_ppt = min_option(fast_ppt, action.fast_ppt);
+            slow_ppt = min_option(slow_ppt, action.slow_ppt);
+            cpu_clock_max = min_option(cpu_clock_max, action.cpu_clock_max);
+        }
+        let cpu_limits = cpus.limits().cpus;
+        ThrottleValues {
+            fast_ppt: limit_option(original.fast_ppt, fast_ppt),
+            slow_ppt: limit_option(original.slow_ppt, slow_ppt),
+            cpu_clocks: original
+                .cpu_clocks
+                .iter()
+                .enumerate()
+                .map(|(i, clocks)| {
+                    let max = if let Some(max) = cpu_clock_max {
+                        max
+                    } else {
+                        return *clocks;
+                    };
+                    match clocks {
+                        Some((old_min, old_max)) => {
+                            Some(((*old_min).min(max), (*old_max).min(max)))
+                        }
+                        None => cpu_limits
+                            .get(i)
+                            .and_then(|l| l.clock_min_limits.as_ref())
+                            .map(|min_limits| (min_limits.min.min(max), max)),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[inline]
+fn min_option(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Lower `value` to `limit`, treating an unset value as unlimited
+#[inline]
+fn limit_option(value: Option<u64>, limit: Option<u64>) -> Option<u64> {
+    match (value, limit) {
+        (Some(value), Some(limit)) => Some(value.min(limit)),
+        (value, None) => value,
+        (None, limit) => limit,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rule_hysteresis_test() {
+        let mut rule = ThermalRule {
+            rule: ThermalRuleJson {
+                sensor: ThermalSensorJson::Cpu,
+                threshold: 90.0,
+                duration: 10,
+                hysteresis: 5.0,
+                action: ThermalActionJson::default(),
+            },
+            over_since: None,
+            under_since: None,
+            active: false,
+        };
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        rule.update(Some(91.0), at(0));
+        rule.update(Some(92.0), at(5));
+        assert!(!rule.active);
+        rule.update(Some(91.0), at(10));
+        assert!(rule.active);
+        // below threshold, but not below threshold - hysteresis
+        rule.update(Some(87.0), at(15));
+        rule.update(Some(87.0), at(30));
+        assert!(rule.active);
+        rule.update(Some(84.0), at(31));
+        rule.update(Some(84.0), at(40));
+        assert!(rule.active);
+        rule.update(Some(84.0), at(41));
+        assert!(!rule.active);
+    }
+}
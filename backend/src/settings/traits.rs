@@ -1,3 +1,4 @@
+use super::FanCurve;
 use super::MinMax;
 use super::SettingError;
 use std::fmt::Debug;
@@ -10,6 +11,23 @@ pub trait OnResume {
     fn on_resume(&self) -> Result<(), Vec<SettingError>>;
 }
 
+pub trait OnVerify {
+    /// Read back what on_set() wrote, to catch values which were overridden afterwards
+    fn verify(&self) -> Vec<crate::api::AppliedValue> {
+        Vec::new()
+    }
+}
+
+pub trait OnShutdown {
+    /// Record everything on_shutdown() restores in the startup snapshot (see settings::startup)
+    fn snapshot(&self) {}
+
+    /// Put the hardware back to how it was before PowerTools changed it
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        Ok(())
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug)]
 pub enum PowerMode {
@@ -40,7 +58,7 @@ pub trait OnPowerEvent {
     }
 }
 
-pub trait TGpu: OnSet + OnResume + OnPowerEvent + Debug + Send {
+pub trait TGpu: OnSet + OnResume + OnShutdown + OnVerify + OnPowerEvent + Debug + Send {
     fn limits(&self) -> crate::api::GpuLimits;
 
     fn json(&self) -> crate::persist::GpuJson;
@@ -60,7 +78,7 @@ pub trait TGpu: OnSet + OnResume + OnPowerEvent + Debug + Send {
     }
 }
 
-pub trait TCpus: OnSet + OnResume + OnPowerEvent + Debug + Send {
+pub trait TCpus: OnSet + OnResume + OnShutdown + OnVerify + OnPowerEvent + Debug + Send {
     fn limits(&self) -> crate::api::CpusLimits;
 
     fn json(&self) -> Vec<crate::persist::CpuJson>;
@@ -106,7 +124,7 @@ pub trait TGeneral: OnSet + OnResume + OnPowerEvent + Debug + Send {
     fn provider(&self) -> crate::persist::DriverJson;
 }
 
-pub trait TBattery: OnSet + OnResume + OnPowerEvent + Debug + Send {
+pub trait TBattery: OnSet + OnResume + OnShutdown + OnVerify + OnPowerEvent + Debug + Send {
     fn limits(&self) -> crate::api::BatteryLimits;
 
     fn json(&self) -> crate::persist::BatteryJson;
@@ -127,10 +145,6 @@ pub trait TBattery: OnSet + OnResume + OnPowerEvent + Debug + Send {
 
     fn read_current_now(&self) -> Option<f64>;
 
-    fn charge_limit(&mut self, limit: Option<f64>);
-
-    fn get_charge_limit(&self) -> Option<f64>;
-
     fn check_power(&mut self) -> Result<Vec<PowerMode>, Vec<SettingError>> {
         log::warn!("Power event check using default trait implementation");
         let mut events = Vec::new();
@@ -144,3 +158,20 @@ pub trait TBattery: OnSet + OnResume + OnPowerEvent + Debug + Send {
         crate::persist::DriverJson::AutoDetect
     }
 }
+
+pub trait TFan: OnSet + OnResume + OnShutdown + OnVerify + OnPowerEvent + Debug + Send {
+    fn limits(&self) -> crate::api::FanLimits;
+
+    fn json(&self) -> crate::persist::FanJson;
+
+    /// Set fan curve, or None to leave fan control to the firmware
+    fn curve(&mut self, curve: Option<FanCurve>);
+
+    /// Re-evaluate the fan curve against the current temperature.
+    /// Implementations should fall back to firmware fan control when this fails.
+    fn update(&mut self) -> Result<(), Vec<SettingError>>;
+
+    fn provider(&self) -> crate::persist::DriverJson {
+        crate::persist::DriverJson::AutoDetect
+    }
+}
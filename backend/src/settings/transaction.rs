@@ -0,0 +1,126 @@
+//! Undo journal for applying settings, so a partially failed on_set() can be rolled back.
+//! While a transaction is open, every write records how to put back the value from before it,
+//! the first time each value is written (see startup::write_single).
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{ErrorKind, SettingError, SettingVariant};
+
+type Undo = Box<dyn FnOnce() -> std::io::Result<()> + Send>;
+
+/// Undo actions of the open transaction (in write order), or None when there is no transaction
+static JOURNAL: Mutex<Option<Vec<(String, Undo)>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Everything was applied
+    Committed,
+    /// Something failed, so everything was put back to how it was before
+    RolledBack,
+    /// Something failed, and so did putting everything back
+    RollbackFailed,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Committed => write!(f, "committed"),
+            Self::RolledBack => write!(f, "rolled-back"),
+            Self::RollbackFailed => write!(f, "rollback-failed"),
+        }
+    }
+}
+
+pub fn begin() {
+    let mut journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    if journal.is_some() {
+        log::warn!("Transaction started while another was still open, discarding the old one");
+    }
+    *journal = Some(Vec::new());
+}
+
+/// Record how to undo a change to `key`, unless the open transaction already knows.
+/// Does nothing when no transaction is open.
+pub fn record_with<F: FnOnce() -> Option<Undo>>(key: &str, undo: F) {
+    let mut journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(journal) = journal.as_mut() {
+        if journal.iter().any(|(k, _)| k == key) {
+            return;
+        }
+        if let Some(undo) = undo() {
+            journal.push((key.to_owned(), undo));
+        }
+    }
+}
+
+/// Record the current contents of the sysfs file at `path`, to write it back on rollback
+pub fn record<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    record_with(&path.to_string_lossy(), || {
+        let old = std::fs::read_to_string(path).ok()?.trim_end().to_owned();
+        let path = path.to_owned();
+        Some(Box::new(move || {
+            usdpl_back::api::files::write_single(path, old)
+        }))
+    });
+}
+
+pub fn commit() {
+    *JOURNAL.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Undo everything written since begin(), newest first
+pub fn rollback() -> Result<(), Vec<SettingError>> {
+    let journal = JOURNAL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    let mut errors = Vec::new();
+    for (key, undo) in journal.into_iter().rev() {
+        log::info!("Rolling back `{}`", key);
+        undo().unwrap_or_else(|e| {
+            errors.push(SettingError {
+                msg: format!("Failed to roll back `{}`: {}", key, e),
+                setting: SettingVariant::General,
+                kind: ErrorKind::from(&e),
+            })
+        });
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Whether `errors` leave the hardware in a mix of old and new values.
+/// Unsupported settings are never written, so they don't need a rollback.
+pub fn needs_rollback(errors: &[SettingError]) -> bool {
+    errors.iter().any(|e| e.kind != ErrorKind::Unsupported)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rollback_order_test() {
+        let order = std::sync::Arc::new(Mutex::new(Vec::new()));
+        begin();
+        for key in ["first", "second", "first"] {
+            let order = order.clone();
+            record_with(key, move || {
+                Some(Box::new(move || {
+                    order.lock().unwrap().push(key);
+                    Ok(())
+                }))
+            });
+        }
+        assert!(rollback().is_ok());
+        assert_eq!(*order.lock().unwrap(), vec!["second", "first"]);
+        // nothing is recorded outside of a transaction
+        record_with("outside", || panic!("Recorded outside of a transaction"));
+    }
+}
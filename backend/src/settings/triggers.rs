@@ -0,0 +1,400 @@
+//! Triggers which change settings when something happens, like unplugging the charger or waking up.
+//! Trigger actions change settings the same way the front-end does, but only until the profile is loaded again;
+//! the profile's own values are saved instead, and battery values are restored when plugging in or out.
+//! The Steam Deck charge limit is a pair of battery triggers too.
+
+use std::fmt::Display;
+
+use super::{MinMax, PowerMode, TBattery, TCpus, TGpu};
+use crate::persist::{
+    BatteryJson, CpuJson, GpuJson, PowerSourceValuesJson, SettingsJson, TriggerActionJson,
+    TriggerJson,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    PluggedIn,
+    PluggedOut,
+    Resume,
+    /// Battery fill amount: 0 = empty, 1 = full
+    BatteryAbove(f64),
+    BatteryBelow(f64),
+    Ignored,
+}
+
+/// Something which happened, which may fire triggers
+#[derive(Debug, Clone, Copy)]
+pub enum TriggerEvent {
+    Power(PowerMode),
+    Resume,
+}
+
+impl Trigger {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "plug-in" => Some(Self::PluggedIn),
+            "plug-out" => Some(Self::PluggedOut),
+            "resume" => Some(Self::Resume),
+            s if s.starts_with('>') => s
+                .trim_start_matches('>')
+                .parse::<f64>()
+                .ok()
+                .map(|x| Self::BatteryAbove(x / 100.0)),
+            s if s.starts_with('<') => s
+                .trim_start_matches('<')
+                .parse::<f64>()
+                .ok()
+                .map(|x| Self::BatteryBelow(x / 100.0)),
+            _ => None,
+        }
+    }
+
+    /// Whether `event` fires this trigger.
+    /// Battery triggers fire once when the charge crosses into their range; `is_triggered` keeps track of that.
+    pub fn fires(&self, event: TriggerEvent, is_triggered: &mut bool) -> bool {
+        match (self, event) {
+            (Self::PluggedIn, TriggerEvent::Power(PowerMode::PluggedIn))
+            | (Self::PluggedOut, TriggerEvent::Power(PowerMode::PluggedOut))
+            | (Self::Resume, TriggerEvent::Resume) => true,
+            (Self::BatteryAbove(exp), TriggerEvent::Power(PowerMode::BatteryCharge(act))) => {
+                crossed(act > *exp, is_triggered)
+            }
+            (Self::BatteryBelow(exp), TriggerEvent::Power(PowerMode::BatteryCharge(act))) => {
+                crossed(act < *exp, is_triggered)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PluggedIn => write!(f, "plug-in"),
+            Self::PluggedOut => write!(f, "plug-out"),
+            Self::Resume => write!(f, "resume"),
+            Self::BatteryAbove(x) => write!(f, ">{:#0.2}", x * 100.0),
+            Self::BatteryBelow(x) => write!(f, "<{:#0.2}", x * 100.0),
+            Self::Ignored => write!(f, "/shrug"),
+        }
+    }
+}
+
+#[inline]
+fn crossed(in_range: bool, is_triggered: &mut bool) -> bool {
+    let fires = in_range && !*is_triggered;
+    *is_triggered = in_range;
+    fires
+}
+
+#[derive(Debug, Clone)]
+struct TriggerRule {
+    /// Trigger as written, so unknown triggers are saved as-is
+    json: TriggerJson,
+    trigger: Trigger,
+    is_triggered: bool,
+}
+
+impl TriggerRule {
+    fn from_json(json: TriggerJson) -> Self {
+        Self {
+            trigger: Trigger::parse(&json.trigger).unwrap_or_else(|| {
+                log::warn!("Ignoring unknown trigger `{}`", json.trigger);
+                Trigger::Ignored
+            }),
+            json,
+            is_triggered: false,
+        }
+    }
+
+    fn charge_mode(trigger: Trigger, mode: &str) -> Self {
+        Self::from_json(TriggerJson {
+            trigger: trigger.to_string(),
+            action: TriggerActionJson {
+                charge_mode: Some(mode.to_owned()),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Rule is part of the charge limit, which stops charging above the limit and charges again below it
+    fn is_charge_limit(&self, above: bool) -> bool {
+        self.json.action.charge_mode.is_some()
+            && match self.trigger {
+                Trigger::BatteryAbove(_) => above,
+                Trigger::BatteryBelow(_) => !above,
+                _ => false,
+            }
+    }
+}
+
+/// Values of a settings section before triggers changed them, and after
+#[derive(Debug, Clone)]
+struct Fired<T> {
+    original: T,
+    applied: T,
+}
+
+impl<T: PartialEq + Clone> Fired<T> {
+    /// Keep track of triggers changing a section from `before` to `after`.
+    /// The original values of earlier triggers are kept while their values are still in use.
+    fn track(fired: &mut Option<Self>, before: T, after: T) {
+        if before == after {
+            return;
+        }
+        let original = match fired.take() {
+            Some(f) if f.applied == before => f.original,
+            _ => before,
+        };
+        *fired = Some(Self {
+            original,
+            applied: after,
+        });
+    }
+
+    /// Replace `live` with the original values, unless something else changed them since
+    fn unfired(fired: &Option<Self>, live: &mut T) {
+        if let Some(f) = fired {
+            if *live == f.applied {
+                *live = f.original.clone();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Triggers {
+    rules: Vec<TriggerRule>,
+    gpu: Option<Fired<GpuJson>>,
+    cpus: Option<Fired<Vec<CpuJson>>>,
+    battery: Option<Fired<BatteryJson>>,
+}
+
+/// Move the Steam Deck battery events of older profiles to triggers, which do the same for every driver
+pub fn migrate_battery_events(json: &mut SettingsJson) {
+    for event in json.battery.events.drain(..) {
+        log::info!("Moving battery event `{}` to triggers", event.trigger);
+        json.triggers.push(TriggerJson {
+            trigger: event.trigger,
+            action: TriggerActionJson {
+                charge_rate: event.charge_rate,
+                charge_mode: event.charge_mode,
+                ..Default::default()
+            },
+        });
+    }
+}
+
+impl Triggers {
+    pub fn from_json(triggers: Vec<TriggerJson>) -> Self {
+        Self {
+            rules: triggers.into_iter().map(TriggerRule::from_json).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn json(&self) -> Vec<TriggerJson> {
+        self.rules.iter().map(|r| r.json.clone()).collect()
+    }
+
+    /// Change settings for the triggers `event` fires, in order.
+    /// Returns true when settings were modified and need to be applied.
+    pub fn fire(
+        &mut self,
+        event: TriggerEvent,
+        gpu: &mut dyn TGpu,
+        cpus: &mut dyn TCpus,
+        battery: &mut dyn TBattery,
+    ) -> bool {
+        let mut restored = false;
+        if let TriggerEvent::Power(PowerMode::PluggedIn | PowerMode::PluggedOut) = event {
+            // battery triggers may fire again after plugging in or out
+            self.rules.iter_mut().for_each(|r| r.is_triggered = false);
+            restored = self.restore_battery(battery);
+        }
+        let fired: Vec<usize> = self
+            .rules
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, r)| r.trigger.fires(event, &mut r.is_triggered).then_some(i))
+            .collect();
+        if fired.is_empty() {
+            return restored;
+        }
+        let (gpu_before, cpus_before, battery_before) = (gpu.json(), cpus.json(), battery.json());
+        for i in fired {
+            log::info!("Trigger `{}` fired", self.rules[i].trigger);
+            act(&self.rules[i].json.action, gpu, cpus, battery);
+        }
+        Fired::track(&mut self.gpu, gpu_before, gpu.json());
+        Fired::track(&mut self.cpus, cpus_before, cpus.json());
+        Fired::track(&mut self.battery, battery_before, battery.json());
+        true
+    }
+
+    /// Go back to the battery values from before triggers changed them.
+    /// Returns true when settings were modified and need to be applied.
+    fn restore_battery(&mut self, battery: &mut dyn TBattery) -> bool {
+        match self.battery.take() {
+            Some(fired) if battery.json() == fired.applied => {
+                log::info!("Restoring battery values changed by triggers");
+                battery.charge_rate(fired.original.charge_rate);
+                battery.charge_mode(fired.original.charge_mode);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace values changed by triggers in `json` with the profile's own values
+    pub fn unfired_json(&self, json: &mut SettingsJson) {
+        Fired::unfired(&self.gpu, &mut json.gpu);
+        Fired::unfired(&self.cpus, &mut json.cpus);
+        Fired::unfired(&self.battery, &mut json.battery);
+    }
+
+    /// Replace values changed by triggers in CPU and GPU `values`, which are switched for the values of another power source
+    pub fn unfired_values(&mut self, values: &mut PowerSourceValuesJson) {
+        Fired::unfired(&self.gpu.take(), &mut values.gpu);
+        Fired::unfired(&self.cpus.take(), &mut values.cpus);
+    }
+
+    /// Stop charging above `limit` percent, and charge again 10% below it
+    pub fn charge_limit(&mut self, limit: Option<f64>) {
+        self.charge_limit_rule(
+            true,
+            limit.map(|limit| {
+                TriggerRule::charge_mode(Trigger::BatteryAbove(limit / 100.0), "idle")
+            }),
+        );
+        self.charge_limit_rule(
+            false,
+            limit.map(|limit| {
+                let limit = (limit - 10.0).clamp(0.0, 100.0);
+                TriggerRule::charge_mode(Trigger::BatteryBelow(limit / 100.0), "normal")
+            }),
+        );
+    }
+
+    pub fn get_charge_limit(&self) -> Option<f64> {
+        self.rules
+            .iter()
+            .filter(|r| r.is_charge_limit(true))
+            .find_map(|r| match r.trigger {
+                Trigger::BatteryAbove(limit) => Some(limit * 100.0),
+                _ => None,
+            })
+    }
+
+    fn charge_limit_rule(&mut self, above: bool, rule: Option<TriggerRule>) {
+        let index = self.rules.iter().position(|r| r.is_charge_limit(above));
+        match (index, rule) {
+            (Some(index), Some(rule)) => {
+                log::info!("Updating charge limit trigger to `{}`", rule.trigger);
+                self.rules[index] = rule;
+            }
+            (Some(index), None) => {
+                log::info!(
+                    "Removing charge limit trigger `{}`",
+                    self.rules[index].trigger
+                );
+                self.rules.remove(index);
+            }
+            (None, Some(rule)) => {
+                log::info!("Creating charge limit trigger `{}`", rule.trigger);
+                self.rules.push(rule);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn act(
+    action: &TriggerActionJson,
+    gpu: &mut dyn TGpu,
+    cpus: &mut dyn TCpus,
+    battery: &mut dyn TBattery,
+) {
+    if action.fast_ppt.is_some() || action.slow_ppt.is_some() {
+        let (fast, slow) = gpu.get_ppt();
+        gpu.ppt(action.fast_ppt.or(fast), action.slow_ppt.or(slow));
+    }
+    if let Some(clocks) = &action.gpu_clock_limits {
+        gpu.clock_limits(Some(MinMax {
+            min: clocks.min,
+            max: clocks.max,
+        }));
+    }
+    if let Some(slow_memory) = action.slow_memory {
+        *gpu.slow_memory() = slow_memory;
+    }
+    if let Some(smt) = action.smt {
+        *cpus.smt() = smt;
+    }
+    if let Some(governor) = &action.cpu_governor {
+        for cpu in cpus.cpus() {
+            if *cpu.online() {
+                cpu.governor(governor.to_owned());
+            }
+        }
+    }
+    if let Some(rate) = action.charge_rate {
+        battery.charge_rate(Some(rate));
+    }
+    if let Some(mode) = &action.charge_mode {
+        battery.charge_mode(Some(mode.to_owned()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trigger_fires_once_test() {
+        assert_eq!(Trigger::parse("<20"), Some(Trigger::BatteryBelow(0.2)));
+        assert_eq!(Trigger::parse("resume"), Some(Trigger::Resume));
+        assert_eq!(Trigger::parse("sometimes"), None);
+        assert_eq!(Trigger::BatteryAbove(0.8).to_string(), ">80.00");
+
+        let low = Trigger::BatteryBelow(0.2);
+        let charge = |x| TriggerEvent::Power(PowerMode::BatteryCharge(x));
+        let mut is_triggered = false;
+        assert!(!low.fires(charge(0.5), &mut is_triggered));
+        assert!(low.fires(charge(0.19), &mut is_triggered));
+        assert!(!low.fires(charge(0.18), &mut is_triggered));
+        assert!(!low.fires(charge(0.25), &mut is_triggered));
+        assert!(low.fires(charge(0.1), &mut is_triggered));
+        assert!(!low.fires(TriggerEvent::Resume, &mut is_triggered));
+        assert!(Trigger::Resume.fires(TriggerEvent::Resume, &mut is_triggered));
+    }
+
+    #[test]
+    fn fired_values_test() {
+        let mut fired = None;
+        Fired::track(&mut fired, 10, 20);
+        Fired::track(&mut fired, 20, 30);
+        let mut live = 30;
+        Fired::unfired(&fired, &mut live);
+        assert_eq!(live, 10);
+        // changed by the user since, so that's kept
+        let mut live = 25;
+        Fired::unfired(&fired, &mut live);
+        assert_eq!(live, 25);
+        Fired::track(&mut fired, 25, 35);
+        let mut live = 35;
+        Fired::unfired(&fired, &mut live);
+        assert_eq!(live, 25);
+
+        let mut triggers = Triggers::default();
+        triggers.charge_limit(Some(80.0));
+        triggers.charge_limit(Some(85.0));
+        assert_eq!(triggers.get_charge_limit(), Some(85.0));
+        let json = triggers.json();
+        assert_eq!(json.len(), 2);
+        assert_eq!(json[1].trigger, "<75.00");
+        triggers.charge_limit(None);
+        assert_eq!(triggers.get_charge_limit(), None);
+        assert!(triggers.json().is_empty());
+    }
+}
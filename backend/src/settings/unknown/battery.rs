@@ -7,9 +7,9 @@ use crate::settings::{OnResume, OnSet, SettingError};
 #[derive(Debug, Clone)]
 pub struct Battery;
 
-impl Into<BatteryJson> for Battery {
+impl From<Battery> for BatteryJson {
     #[inline]
-    fn into(self) -> BatteryJson {
+    fn from(_val: Battery) -> Self {
         BatteryJson {
             charge_rate: None,
             charge_mode: None,
@@ -32,6 +32,10 @@ impl OnResume for Battery {
 
 impl crate::settings::OnPowerEvent for Battery {}
 
+impl crate::settings::OnShutdown for Battery {}
+
+impl crate::settings::OnVerify for Battery {}
+
 impl TBattery for Battery {
     fn limits(&self) -> crate::api::BatteryLimits {
         crate::api::BatteryLimits {
@@ -75,12 +79,6 @@ impl TBattery for Battery {
         None
     }
 
-    fn charge_limit(&mut self, _limit: Option<f64>) {}
-
-    fn get_charge_limit(&self) -> Option<f64> {
-        None
-    }
-
     fn provider(&self) -> crate::persist::DriverJson {
         crate::persist::DriverJson::Unknown
     }
@@ -1,8 +1,11 @@
 use std::convert::Into;
 
+use crate::api::AppliedValue;
 use crate::persist::CpuJson;
+use crate::settings::startup;
+use crate::settings::verify;
 use crate::settings::MinMax;
-use crate::settings::{OnResume, OnSet, SettingError};
+use crate::settings::{OnResume, OnSet, OnShutdown, OnVerify, SettingError};
 use crate::settings::{TCpu, TCpus};
 
 const CPU_PRESENT_PATH: &str = "/sys/devices/system/cpu/present";
@@ -21,19 +24,21 @@ impl OnSet for Cpus {
         if self.smt_capable {
             // toggle SMT
             if self.smt {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "on")
+                startup::write_single(CPU_SMT_PATH, "on")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `on` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| {
                         errors.push(e);
                     });
             } else {
-                usdpl_back::api::files::write_single(CPU_SMT_PATH, "off")
+                startup::write_single(CPU_SMT_PATH, "off")
                     .map_err(|e| SettingError {
                         msg: format!("Failed to write `off` to `{}`: {}", CPU_SMT_PATH, e),
                         setting: crate::settings::SettingVariant::Cpu,
+                        kind: crate::settings::ErrorKind::from(&e),
                     })
                     .unwrap_or_else(|e| {
                         errors.push(e);
@@ -67,6 +72,33 @@ impl OnResume for Cpus {
     }
 }
 
+impl OnVerify for Cpus {
+    fn verify(&self) -> Vec<AppliedValue> {
+        let mut values: Vec<AppliedValue> = verify::smt(self.smt_capable, self.smt)
+            .into_iter()
+            .collect();
+        for cpu in &self.cpus {
+            values.append(&mut verify::cpu(
+                cpu.index,
+                cpu.online,
+                cpu.state.do_set_online,
+                &cpu.governor,
+            ));
+        }
+        values
+    }
+}
+
+impl OnShutdown for Cpus {
+    fn snapshot(&self) {
+        startup::remember_cpus(self.cpus.len());
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Vec<SettingError>> {
+        startup::restore_cpus(self.cpus.len())
+    }
+}
+
 impl crate::settings::OnPowerEvent for Cpus {}
 
 impl Cpus {
@@ -207,10 +239,11 @@ impl Cpu {
         if self.index != 0 && self.state.do_set_online {
             // cpu0 cannot be disabled
             let online_path = cpu_online_path(self.index);
-            usdpl_back::api::files::write_single(&online_path, self.online as u8)
+            startup::write_single(&online_path, self.online as u8)
                 .map_err(|e| SettingError {
                     msg: format!("Failed to write to `{}`: {}", &online_path, e),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -218,13 +251,14 @@ impl Cpu {
         // set governor
         if self.index == 0 || self.online {
             let governor_path = cpu_governor_path(self.index);
-            usdpl_back::api::files::write_single(&governor_path, &self.governor)
+            startup::write_single(&governor_path, &self.governor)
                 .map_err(|e| SettingError {
                     msg: format!(
                         "Failed to write `{}` to `{}`: {}",
                         &self.governor, &governor_path, e
                     ),
                     setting: crate::settings::SettingVariant::Cpu,
+                    kind: crate::settings::ErrorKind::from(&e),
                 })
                 .unwrap_or_else(|e| errors.push(e));
         }
@@ -256,13 +290,13 @@ impl Cpu {
     }
 }
 
-impl Into<CpuJson> for Cpu {
+impl From<Cpu> for CpuJson {
     #[inline]
-    fn into(self) -> CpuJson {
+    fn from(other: Cpu) -> Self {
         CpuJson {
-            online: self.online,
+            online: other.online,
             clock_limits: None,
-            governor: self.governor,
+            governor: other.governor,
         }
     }
 }
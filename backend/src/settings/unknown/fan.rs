@@ -0,0 +1,49 @@
+use crate::persist::FanJson;
+use crate::settings::{FanCurve, TFan};
+use crate::settings::{OnResume, OnSet, SettingError};
+
+#[derive(Debug, Clone)]
+pub struct Fan;
+
+impl OnSet for Fan {
+    fn on_set(&mut self) -> Result<(), Vec<SettingError>> {
+        Ok(())
+    }
+}
+
+impl OnResume for Fan {
+    fn on_resume(&self) -> Result<(), Vec<SettingError>> {
+        Ok(())
+    }
+}
+
+impl crate::settings::OnPowerEvent for Fan {}
+
+impl crate::settings::OnShutdown for Fan {}
+
+impl crate::settings::OnVerify for Fan {}
+
+impl TFan for Fan {
+    fn limits(&self) -> crate::api::FanLimits {
+        crate::api::FanLimits {
+            speed_limits: None,
+            speed_step: 1,
+            speed_unit: "pwm".to_owned(),
+            sensors: vec![],
+        }
+    }
+
+    fn json(&self) -> crate::persist::FanJson {
+        FanJson::default()
+    }
+
+    fn curve(&mut self, _curve: Option<FanCurve>) {}
+
+    fn update(&mut self) -> Result<(), Vec<SettingError>> {
+        Ok(())
+    }
+
+    fn provider(&self) -> crate::persist::DriverJson {
+        crate::persist::DriverJson::Unknown
+    }
+}
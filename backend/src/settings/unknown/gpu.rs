@@ -21,9 +21,9 @@ impl Gpu {
     }
 }
 
-impl Into<GpuJson> for Gpu {
+impl From<Gpu> for GpuJson {
     #[inline]
-    fn into(self) -> GpuJson {
+    fn from(_val: Gpu) -> Self {
         GpuJson {
             fast_ppt: None,
             slow_ppt: None,
@@ -47,6 +47,10 @@ impl OnResume for Gpu {
 
 impl crate::settings::OnPowerEvent for Gpu {}
 
+impl crate::settings::OnShutdown for Gpu {}
+
+impl crate::settings::OnVerify for Gpu {}
+
 impl TGpu for Gpu {
     fn limits(&self) -> crate::api::GpuLimits {
         crate::api::GpuLimits {
@@ -1,7 +1,9 @@
 mod battery;
 mod cpu;
+mod fan;
 mod gpu;
 
 pub use battery::Battery;
-pub use cpu::{Cpu, Cpus};
+pub use cpu::Cpus;
+pub use fan::Fan;
 pub use gpu::Gpu;
@@ -1,4 +1,4 @@
-pub fn guess_smt(cpus: &Vec<crate::persist::CpuJson>) -> bool {
+pub fn guess_smt(cpus: &[crate::persist::CpuJson]) -> bool {
     let mut guess = true;
     for i in (0..cpus.len()).step_by(2) {
         guess &= cpus[i].online == cpus[i + 1].online;
@@ -24,7 +24,7 @@ mod test {
             cpu_with_online(true),
             cpu_with_online(true),
         ];
-        assert_eq!(guess_smt(&input), true);
+        assert!(guess_smt(&input));
 
         let input = vec![
             cpu_with_online(true),
@@ -36,7 +36,7 @@ mod test {
             cpu_with_online(true),
             cpu_with_online(false),
         ];
-        assert_eq!(guess_smt(&input), false);
+        assert!(!guess_smt(&input));
     }
 
     fn cpu_with_online(status: bool) -> CpuJson {
@@ -0,0 +1,395 @@
+//! Checking settings against the limits of the current drivers, before anything is applied.
+
+use super::triggers::Trigger;
+use super::{ErrorKind, SettingError, SettingVariant};
+use crate::api::{RangeLimit, SettingsLimits};
+use crate::persist::{CpuJson, GpuJson, SettingsJson, TriggerJson, ValidationJson, ValidationMode};
+
+fn config_path() -> std::path::PathBuf {
+    crate::utility::settings_dir().join(crate::consts::VALIDATION_CONFIG_FILE)
+}
+
+/// Load whether to reject or clamp values from the settings dir, falling back to rejecting
+pub fn load_mode() -> ValidationMode {
+    crate::persist::open_json::<ValidationJson, _>(config_path())
+        .map(|json| json.mode)
+        .unwrap_or_default()
+}
+
+pub fn save_mode(mode: ValidationMode) {
+    if let Err(e) = crate::persist::save_json(&ValidationJson { mode }, config_path()) {
+        log::error!("Failed to save validation config: {}", e);
+    }
+}
+
+/// Collects problems with the values of one setting.
+/// In clamp mode, values which can be adjusted are changed in-place instead.
+pub struct Checks {
+    setting: SettingVariant,
+    mode: ValidationMode,
+    errors: Vec<SettingError>,
+}
+
+impl Checks {
+    pub fn new(setting: SettingVariant, mode: ValidationMode) -> Self {
+        Self {
+            setting,
+            mode,
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, kind: ErrorKind, msg: String) {
+        self.errors.push(SettingError {
+            msg,
+            setting: self.setting,
+            kind,
+        });
+    }
+
+    /// Check that `value` is within `range` and lands on a `step` from the range minimum
+    pub fn value(
+        &mut self,
+        name: &str,
+        value: &mut u64,
+        range: &Option<RangeLimit<u64>>,
+        step: u64,
+    ) {
+        let range = if let Some(range) = range {
+            range
+        } else {
+            if self.mode == ValidationMode::Reject {
+                self.error(ErrorKind::Unsupported, format!("{} is not supported", name));
+            }
+            return;
+        };
+        if *value < range.min || *value > range.max {
+            if self.mode == ValidationMode::Clamp {
+                *value = (*value).clamp(range.min, range.max);
+            } else {
+                self.error(
+                    ErrorKind::OutOfRange,
+                    format!(
+                        "{} `{}` is outside of allowed range ({}, {})",
+                        name, value, range.min, range.max
+                    ),
+                );
+                return;
+            }
+        }
+        // a step of 0 or 1 means any value is fine
+        if step > 1 && !(*value - range.min).is_multiple_of(step) {
+            if self.mode == ValidationMode::Clamp {
+                let rounded = range.min + (*value - range.min + step / 2) / step * step;
+                *value = if rounded > range.max {
+                    rounded - step
+                } else {
+                    rounded
+                };
+            } else {
+                self.error(
+                    ErrorKind::OffStep,
+                    format!(
+                        "{} `{}` is not a multiple of {} from {}",
+                        name, value, step, range.min
+                    ),
+                );
+            }
+        }
+    }
+
+    pub fn optional(
+        &mut self,
+        name: &str,
+        value: &mut Option<u64>,
+        range: &Option<RangeLimit<u64>>,
+        step: u64,
+    ) {
+        if let Some(value) = value {
+            self.value(name, value, range, step);
+        }
+    }
+
+    /// Check a min-max pair; in clamp mode, a minimum greater than the maximum is swapped
+    pub fn min_max(
+        &mut self,
+        name: &str,
+        min: &mut u64,
+        max: &mut u64,
+        ranges: (&Option<RangeLimit<u64>>, &Option<RangeLimit<u64>>),
+        step: u64,
+    ) {
+        if *min > *max {
+            if self.mode == ValidationMode::Clamp {
+                std::mem::swap(min, max);
+            } else {
+                self.error(
+                    ErrorKind::MinOverMax,
+                    format!(
+                        "{} minimum `{}` is greater than maximum `{}`",
+                        name, min, max
+                    ),
+                );
+            }
+        }
+        self.value(&format!("{} minimum", name), min, ranges.0, step);
+        self.value(&format!("{} maximum", name), max, ranges.1, step);
+    }
+
+    /// Check that `value` is one of `options`; there's no nearest option, so this is never clamped
+    pub fn option(&mut self, name: &str, value: &str, options: &[String]) {
+        // an empty list means the driver doesn't know what's available
+        if !options.is_empty() && !options.iter().any(|o| o == value) {
+            self.error(
+                ErrorKind::UnknownOption,
+                format!("{} `{}` is not one of {:?}", name, value, options),
+            );
+        }
+    }
+
+    /// Check that there is something at `index`
+    pub fn index<'a, T>(&mut self, name: &str, items: &'a [T], index: usize) -> Option<&'a T> {
+        let item = items.get(index);
+        if item.is_none() {
+            self.error(
+                ErrorKind::BadIndex,
+                format!("{} index {} is not below {}", name, index, items.len()),
+            );
+        }
+        item
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.errors.extend(other.errors);
+    }
+
+    pub fn finish(self) -> Result<(), Vec<SettingError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// Check that every value of `json` is allowed by `limits`
+pub fn settings_json(
+    json: &mut SettingsJson,
+    limits: &SettingsLimits,
+    mode: ValidationMode,
+) -> Result<(), Vec<SettingError>> {
+    // CPUs and GPU, of the profile itself and of each power source
+    let mut checks = cpus_json(&mut json.cpus, limits, mode, "");
+    checks.merge(gpu_json(&mut json.gpu, limits, mode, ""));
+    if let Some(values) = &mut json.power_sources.ac {
+        checks.merge(cpus_json(&mut values.cpus, limits, mode, "AC "));
+        checks.merge(gpu_json(&mut values.gpu, limits, mode, "AC "));
+    }
+    if let Some(values) = &mut json.power_sources.battery {
+        checks.merge(cpus_json(&mut values.cpus, limits, mode, "Battery "));
+        checks.merge(gpu_json(&mut values.gpu, limits, mode, "Battery "));
+    }
+    // battery
+    let mut battery_checks = Checks::new(SettingVariant::Battery, mode);
+    battery_checks.optional(
+        "Charge rate",
+        &mut json.battery.charge_rate,
+        &limits.battery.charge_current,
+        limits.battery.charge_current_step,
+    );
+    if let Some(charge_mode) = &json.battery.charge_mode {
+        battery_checks.option("Charge mode", charge_mode, &limits.battery.charge_modes);
+    }
+    checks.merge(battery_checks);
+    // triggers
+    for trigger in json.triggers.iter_mut() {
+        checks.merge(trigger_json(trigger, limits, mode));
+    }
+    checks.finish()
+}
+
+/// Check CPU values; `prefix` tells apart the values of a power source
+fn cpus_json(
+    cpus: &mut [CpuJson],
+    limits: &SettingsLimits,
+    mode: ValidationMode,
+    prefix: &str,
+) -> Checks {
+    let mut checks = Checks::new(SettingVariant::Cpu, mode);
+    if cpus.len() != limits.cpu.count {
+        checks.error(
+            ErrorKind::BadIndex,
+            format!(
+                "Expected {}settings for {} CPUs, got {}",
+                prefix,
+                limits.cpu.count,
+                cpus.len()
+            ),
+        );
+    }
+    for (i, (cpu, cpu_limits)) in cpus.iter_mut().zip(limits.cpu.cpus.iter()).enumerate() {
+        if let Some(clocks) = &mut cpu.clock_limits {
+            checks.min_max(
+                &format!("{}CPU {} clock limits", prefix, i),
+                &mut clocks.min,
+                &mut clocks.max,
+                (&cpu_limits.clock_min_limits, &cpu_limits.clock_max_limits),
+                cpu_limits.clock_step,
+            );
+        }
+        if cpu.online || i == 0 {
+            checks.option(
+                &format!("{}CPU {} governor", prefix, i),
+                &cpu.governor,
+                &cpu_limits.governors,
+            );
+        }
+    }
+    checks
+}
+
+/// Check GPU values; `prefix` tells apart the values of a power source
+fn gpu_json(
+    json: &mut GpuJson,
+    limits: &SettingsLimits,
+    mode: ValidationMode,
+    prefix: &str,
+) -> Checks {
+    let mut checks = Checks::new(SettingVariant::Gpu, mode);
+    let gpu = &limits.gpu;
+    checks.optional(
+        &format!("{}GPU fast PPT", prefix),
+        &mut json.fast_ppt,
+        &gpu.fast_ppt_limits,
+        gpu.ppt_step,
+    );
+    checks.optional(
+        &format!("{}GPU slow PPT", prefix),
+        &mut json.slow_ppt,
+        &gpu.slow_ppt_limits,
+        gpu.ppt_step,
+    );
+    if let Some(clocks) = &mut json.clock_limits {
+        checks.min_max(
+            &format!("{}GPU clock limits", prefix),
+            &mut clocks.min,
+            &mut clocks.max,
+            (&gpu.clock_min_limits, &gpu.clock_max_limits),
+            gpu.clock_step,
+        );
+    }
+    checks
+}
+
+/// Check that `trigger` can fire, and that the values it sets are allowed by `limits`
+fn trigger_json(
+    trigger: &mut TriggerJson,
+    limits: &SettingsLimits,
+    mode: ValidationMode,
+) -> Checks {
+    let mut checks = Checks::new(SettingVariant::General, mode);
+    if Trigger::parse(&trigger.trigger).is_none() {
+        checks.error(
+            ErrorKind::UnknownOption,
+            format!(
+                "Trigger `{}` is not plug-in, plug-out, resume, >N or <N",
+                trigger.trigger
+            ),
+        );
+    }
+    let name = |value: &str| format!("Trigger `{}` {}", trigger.trigger, value);
+    let action = &mut trigger.action;
+    let gpu = &limits.gpu;
+    let mut gpu_checks = Checks::new(SettingVariant::Gpu, mode);
+    gpu_checks.optional(
+        &name("GPU fast PPT"),
+        &mut action.fast_ppt,
+        &gpu.fast_ppt_limits,
+        gpu.ppt_step,
+    );
+    gpu_checks.optional(
+        &name("GPU slow PPT"),
+        &mut action.slow_ppt,
+        &gpu.slow_ppt_limits,
+        gpu.ppt_step,
+    );
+    if let Some(clocks) = &mut action.gpu_clock_limits {
+        gpu_checks.min_max(
+            &name("GPU clock limits"),
+            &mut clocks.min,
+            &mut clocks.max,
+            (&gpu.clock_min_limits, &gpu.clock_max_limits),
+            gpu.clock_step,
+        );
+    }
+    checks.merge(gpu_checks);
+    let mut cpu_checks = Checks::new(SettingVariant::Cpu, mode);
+    if let (Some(governor), Some(cpu_limits)) = (&action.cpu_governor, limits.cpu.cpus.first()) {
+        cpu_checks.option(&name("CPU governor"), governor, &cpu_limits.governors);
+    }
+    checks.merge(cpu_checks);
+    let mut battery_checks = Checks::new(SettingVariant::Battery, mode);
+    battery_checks.optional(
+        &name("charge rate"),
+        &mut action.charge_rate,
+        &limits.battery.charge_current,
+        limits.battery.charge_current_step,
+    );
+    if let Some(charge_mode) = &action.charge_mode {
+        battery_checks.option(
+            &name("charge mode"),
+            charge_mode,
+            &limits.battery.charge_modes,
+        );
+    }
+    checks.merge(battery_checks);
+    checks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reject_checks_test() {
+        let mut checks = Checks::new(SettingVariant::Gpu, ValidationMode::Reject);
+        let range = Some(RangeLimit { min: 100, max: 200 });
+        checks.value("ok", &mut 150, &range, 10);
+        checks.optional("unset", &mut None, &range, 10);
+        assert!(checks.errors.is_empty());
+        checks.value("high", &mut 250, &range, 10);
+        checks.value("no range", &mut 150, &None, 10);
+        checks.value("off step", &mut 155, &range, 10);
+        checks.min_max("backwards", &mut 180, &mut 120, (&range, &range), 10);
+        checks.index("CPU", &[(); 8], 8);
+        let kinds: Vec<ErrorKind> = checks.errors.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ErrorKind::OutOfRange,
+                ErrorKind::Unsupported,
+                ErrorKind::OffStep,
+                ErrorKind::MinOverMax,
+                ErrorKind::BadIndex,
+            ]
+        );
+    }
+
+    #[test]
+    fn clamp_checks_test() {
+        let mut checks = Checks::new(SettingVariant::Gpu, ValidationMode::Clamp);
+        let range = Some(RangeLimit { min: 100, max: 200 });
+        let mut high = 250;
+        checks.value("high", &mut high, &range, 10);
+        assert_eq!(high, 200);
+        let mut off_step = 156;
+        checks.value("off step", &mut off_step, &range, 10);
+        assert_eq!(off_step, 160);
+        let (mut min, mut max) = (180, 20);
+        checks.min_max("backwards", &mut min, &mut max, (&range, &range), 10);
+        assert_eq!((min, max), (100, 180));
+        checks.option("governor", "turbo", &["schedutil".to_owned()]);
+        let kinds: Vec<ErrorKind> = checks.errors.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![ErrorKind::UnknownOption]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Reading back values after on_set(), since other software (e.g. Steam) can override them.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use super::SettingVariant;
+use crate::api::AppliedValue;
+
+/// Compare `requested` to the current contents of the sysfs file at `path`
+pub fn read_back<P: AsRef<Path>, D: Display>(
+    setting: SettingVariant,
+    path: P,
+    requested: D,
+) -> AppliedValue {
+    let path = path.as_ref();
+    value(
+        setting,
+        path.display(),
+        requested,
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|s| s.trim_end().to_owned()),
+    )
+}
+
+/// Read back SMT, if the cpus can change it
+pub fn smt(smt_capable: bool, smt: bool) -> Option<AppliedValue> {
+    smt_capable.then(|| {
+        read_back(
+            SettingVariant::Cpu,
+            super::startup::CPU_SMT_PATH,
+            if smt { "on" } else { "off" },
+        )
+    })
+}
+
+/// Read back the online status (if it was set) and governor (if online) of a cpu
+pub fn cpu(index: usize, online: bool, set_online: bool, governor: &str) -> Vec<AppliedValue> {
+    let mut values = Vec::new();
+    if index != 0 && set_online {
+        values.push(read_back(
+            SettingVariant::Cpu,
+            super::startup::cpu_online_path(index),
+            online as u8,
+        ));
+    }
+    if index == 0 || online {
+        values.push(read_back(
+            SettingVariant::Cpu,
+            super::startup::cpu_governor_path(index),
+            governor,
+        ));
+    }
+    values
+}
+
+pub fn value<N: Display, D: Display, A: Display>(
+    setting: SettingVariant,
+    name: N,
+    requested: D,
+    actual: Option<A>,
+) -> AppliedValue {
+    AppliedValue {
+        setting: setting.to_string(),
+        name: name.to_string(),
+        requested: requested.to_string(),
+        actual: actual.map(|a| a.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::ApplyReport;
+
+    #[test]
+    fn drift_report_test() {
+        let report = ApplyReport::new(vec![
+            value(
+                SettingVariant::Cpu,
+                "governor",
+                "powersave",
+                Some("powersave"),
+            ),
+            value(SettingVariant::Gpu, "power1_cap", 15000000, Some(20000000)),
+            read_back(SettingVariant::Gpu, "/not/a/real/path", 1),
+        ]);
+        assert_eq!(report.drifted, 1);
+        assert!(report.has_drift());
+        assert!(report.values[1].is_drifted());
+        assert!(!report.values[2].is_drifted());
+    }
+}
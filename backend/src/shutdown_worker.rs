@@ -0,0 +1,61 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use crate::api::handler::ApiMessage;
+
+/// main.py kills the back-end 5 seconds after asking it to stop, so don't wait longer than that
+const TIMEOUT: Duration = Duration::from_secs(4);
+
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Ask api_worker to restore startup hardware settings, and wait for it to finish
+fn request_shutdown(sender: &Sender<ApiMessage>) {
+    let (tx, rx) = mpsc::channel();
+    let callback = move |_: ()| tx.send(()).unwrap_or(());
+    if sender
+        .send(ApiMessage::Shutdown(Box::new(callback)))
+        .is_err()
+    {
+        log::error!("Failed to request shutdown, api_worker is gone");
+    } else if rx.recv_timeout(TIMEOUT).is_err() {
+        log::error!("Timed out waiting for api_worker to shutdown");
+    }
+}
+
+pub fn spawn(sender: Sender<ApiMessage>) -> JoinHandle<()> {
+    // register before spawning, so there's no window where signals kill the back-end outright
+    let mut signals =
+        Signals::new([SIGTERM, SIGINT]).expect("shutdown_worker signal registration failed");
+    thread::spawn(move || {
+        log::info!("shutdown_worker starting...");
+        if let Some(signal) = signals.forever().next() {
+            log::info!("Received signal {}, shutting down", signal);
+            request_shutdown(&sender);
+            log::info!("Shutdown complete");
+            std::process::exit(0);
+        }
+    })
+}
+
+/// Restore startup hardware settings when the main thread panics, since that ends the back-end.
+/// api_worker owns the settings, so it catches its own panics instead (see api_worker::spawn).
+/// Panics in other threads (e.g. a web method with a bad request) only end that thread.
+pub fn set_panic_hook(sender: Sender<ApiMessage>) {
+    let sender = Mutex::new(sender);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        log::error!("Back-end panicked: {}", info);
+        if thread::current().name() != Some("main") {
+            return;
+        }
+        if let Ok(sender) = sender.lock() {
+            request_shutdown(&sender);
+        }
+    }));
+}
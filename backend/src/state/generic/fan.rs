@@ -0,0 +1,6 @@
+#[derive(Debug, Clone, Default)]
+pub struct Fan {
+    /// pwm1_enable value from before PowerTools took control of the fan
+    pub old_pwm_enable: Option<u64>,
+    pub last_speed: Option<u64>,
+}
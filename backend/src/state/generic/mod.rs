@@ -1,3 +1,5 @@
+mod fan;
 mod gpu;
 
+pub use fan::Fan;
 pub use gpu::Gpu;
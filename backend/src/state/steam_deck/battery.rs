@@ -1,8 +1,13 @@
+use std::cell::Cell;
+
 #[derive(Debug, Clone)]
 pub struct Battery {
     pub charge_rate_set: bool,
     pub charge_mode_set: bool,
     pub charger_state: ChargeState,
+    /// Charge mode last read back from the embedded controller, as (requested, actual),
+    /// so it's only read again once a different charge mode is requested
+    pub charge_mode_read: Cell<Option<(u8, Option<u8>)>>,
 }
 
 impl std::default::Default for Battery {
@@ -11,6 +16,7 @@ impl std::default::Default for Battery {
             charge_rate_set: true,
             charge_mode_set: true,
             charger_state: ChargeState::Unknown,
+            charge_mode_read: Cell::new(None),
         }
     }
 }
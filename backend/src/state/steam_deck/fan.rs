@@ -0,0 +1,6 @@
+#[derive(Debug, Clone, Default)]
+pub struct Fan {
+    /// Fan is controlled by PowerTools instead of the EC
+    pub manual: bool,
+    pub last_speed: Option<u64>,
+}
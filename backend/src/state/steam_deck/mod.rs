@@ -1,7 +1,9 @@
 mod battery;
 mod cpu;
+mod fan;
 mod gpu;
 
 pub use battery::{Battery, ChargeState};
 pub use cpu::Cpu;
+pub use fan::Fan;
 pub use gpu::Gpu;
@@ -1,5 +0,0 @@
-use super::StateError;
-
-pub trait OnPoll {
-    fn on_poll(&self) -> Result<(), StateError>;
-}
@@ -0,0 +1,137 @@
+mod ring;
+mod sample;
+mod sensors;
+
+pub use sample::{TelemetrySample, CSV_HEADER};
+
+use std::path::PathBuf;
+
+use crate::persist::TelemetryJson;
+use crate::settings::Settings;
+use ring::RingFile;
+
+/// Optional recorder of power and thermal readings, stored in a ring file under the settings dir
+pub struct Telemetry {
+    config: TelemetryJson,
+    ring: Option<RingFile>,
+}
+
+impl Telemetry {
+    /// Load telemetry config from the settings dir, falling back to defaults (disabled)
+    pub fn load() -> Self {
+        let config = crate::persist::open_json(Self::config_path()).unwrap_or_default();
+        let mut result = Self { config, ring: None };
+        if result.config.enabled {
+            result.open_ring();
+        }
+        result
+    }
+
+    fn config_path() -> PathBuf {
+        crate::utility::settings_dir().join(crate::consts::TELEMETRY_CONFIG_FILE)
+    }
+
+    fn ring_path() -> PathBuf {
+        crate::utility::settings_dir().join(crate::consts::TELEMETRY_FILE)
+    }
+
+    fn open_ring(&mut self) {
+        match RingFile::open_or_create(Self::ring_path(), self.config.capacity) {
+            Ok(ring) => self.ring = Some(ring),
+            Err(e) => {
+                log::error!("Failed to open telemetry file: {}", e);
+                self.ring = None;
+            }
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.config.enabled == enabled {
+            return;
+        }
+        self.config.enabled = enabled;
+        if enabled {
+            self.open_ring();
+        } else {
+            self.ring = None;
+        }
+        if let Err(e) = crate::persist::save_json(&self.config, Self::config_path()) {
+            log::error!("Failed to save telemetry config: {}", e);
+        }
+    }
+
+    /// Take a sample of the current state and append it to the ring file (if enabled)
+    pub fn record(&mut self, settings: &Settings) {
+        let ring = if let Some(ring) = &mut self.ring {
+            ring
+        } else {
+            return;
+        };
+        let sample = Self::sample(settings);
+        log::debug!("Recording telemetry sample {:?}", sample);
+        if let Err(e) = ring.push(&sample) {
+            log::error!("Failed to record telemetry sample: {}", e);
+        }
+    }
+
+    fn sample(settings: &Settings) -> TelemetrySample {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let battery_percent = match (
+            settings.battery.read_charge_now(),
+            settings.battery.read_charge_full(),
+        ) {
+            (Some(now), Some(full)) if full > 0.0 => (now / full * 100.0) as f32,
+            _ => f32::NAN,
+        };
+        // PPT is recorded in whatever unit the driver uses
+        let (fast_ppt, slow_ppt) = settings.gpu.get_ppt();
+        TelemetrySample {
+            timestamp,
+            profile: settings.general.get_name().to_owned(),
+            battery_percent,
+            power_w: sensors::battery_power(),
+            cpu_mhz: sensors::cpu_clock(),
+            gpu_mhz: sensors::gpu_clock(),
+            fast_ppt: fast_ppt.unwrap_or(0) as u32,
+            slow_ppt: slow_ppt.unwrap_or(0) as u32,
+            cpu_temp_c: sensors::cpu_temperature(),
+            gpu_temp_c: sensors::gpu_temperature(),
+            battery_temp_c: sensors::battery_temperature(),
+        }
+    }
+
+    /// Export samples within the time range (inclusive, seconds since UNIX epoch) as CSV
+    pub fn export_csv(&self, start: Option<u64>, end: Option<u64>) -> Result<String, String> {
+        let samples = match &self.ring {
+            Some(ring) => ring.read_all().map_err(|e| e.to_string())?,
+            None => {
+                let path = Self::ring_path();
+                if path.exists() {
+                    // telemetry is disabled, but there may still be old data worth exporting
+                    RingFile::open(&path)
+                        .and_then(|ring| ring.read_all())
+                        .map_err(|e| e.to_string())?
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+        let start = start.unwrap_or(u64::MIN);
+        let end = end.unwrap_or(u64::MAX);
+        let mut csv = String::with_capacity(CSV_HEADER.len() + 1 + (samples.len() * 64));
+        csv.push_str(CSV_HEADER);
+        csv.push('\n');
+        samples
+            .iter()
+            .filter(|s| s.timestamp >= start && s.timestamp <= end)
+            .for_each(|s| s.write_csv(&mut csv));
+        Ok(csv)
+    }
+}
@@ -0,0 +1,185 @@
+//! Fixed-size on-disk ring buffer of telemetry samples.
+//! Layout: 16 byte header (magic, capacity, next write index, sample count) followed by `capacity` samples.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::sample::{TelemetrySample, SAMPLE_SIZE};
+
+const MAGIC: &[u8; 4] = b"PTT1";
+const HEADER_SIZE: u64 = 16;
+
+pub struct RingFile {
+    path: PathBuf,
+    capacity: u32,
+    head: u32,
+    count: u32,
+}
+
+impl RingFile {
+    /// Open an existing ring file with whatever capacity it has, without changing it
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_owned();
+        let (capacity, head, count) = Self::read_header(&path)?;
+        Ok(Self {
+            path,
+            capacity,
+            head,
+            count,
+        })
+    }
+
+    /// Open an existing ring file, or create a new one when it does not exist or has a different capacity
+    pub fn open_or_create<P: AsRef<Path>>(path: P, capacity: u32) -> Result<Self, Error> {
+        let path = path.as_ref().to_owned();
+        // a ring has room for at least one sample
+        let capacity = capacity.max(1);
+        if path.exists() {
+            match Self::read_header(&path) {
+                Ok((file_capacity, head, count)) if file_capacity == capacity => {
+                    return Ok(Self {
+                        path,
+                        capacity,
+                        head,
+                        count,
+                    })
+                }
+                Ok((file_capacity, _, _)) => log::info!(
+                    "Telemetry file {} capacity changed ({} -> {}), starting over",
+                    path.display(),
+                    file_capacity,
+                    capacity
+                ),
+                Err(e) => log::warn!(
+                    "Telemetry file {} is invalid ({}), starting over",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        let ring = Self {
+            path,
+            capacity,
+            head: 0,
+            count: 0,
+        };
+        let mut file = crate::utility::create_settings_file(&ring.path)?;
+        ring.write_header(&mut file)?;
+        Ok(ring)
+    }
+
+    fn read_header(path: &Path) -> Result<(u32, u32, u32), Error> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "bad magic"));
+        }
+        let u32_at =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let (capacity, head, count) = (u32_at(4), u32_at(8), u32_at(12));
+        if capacity == 0 || head >= capacity || count > capacity {
+            return Err(Error::new(ErrorKind::InvalidData, "bad header"));
+        }
+        Ok((capacity, head, count))
+    }
+
+    fn write_header(&self, file: &mut File) -> Result<(), Error> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..8].copy_from_slice(&self.capacity.to_le_bytes());
+        header[8..12].copy_from_slice(&self.head.to_le_bytes());
+        header[12..16].copy_from_slice(&self.count.to_le_bytes());
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)
+    }
+
+    #[inline]
+    fn offset_of(index: u32) -> u64 {
+        HEADER_SIZE + (index as u64 * SAMPLE_SIZE as u64)
+    }
+
+    pub fn push(&mut self, sample: &TelemetrySample) -> Result<(), Error> {
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.seek(SeekFrom::Start(Self::offset_of(self.head)))?;
+        file.write_all(&sample.to_bytes())?;
+        self.head = (self.head + 1) % self.capacity;
+        self.count = (self.count + 1).min(self.capacity);
+        self.write_header(&mut file)
+    }
+
+    /// All stored samples, oldest first
+    pub fn read_all(&self) -> Result<Vec<TelemetrySample>, Error> {
+        let mut file = File::open(&self.path)?;
+        let mut samples = Vec::with_capacity(self.count as usize);
+        let start = (self.head + self.capacity - self.count) % self.capacity;
+        let mut buf = [0u8; SAMPLE_SIZE];
+        for i in 0..self.count {
+            let index = (start + i) % self.capacity;
+            file.seek(SeekFrom::Start(Self::offset_of(index)))?;
+            file.read_exact(&mut buf)?;
+            samples.push(TelemetrySample::from_bytes(&buf));
+        }
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(timestamp: u64) -> TelemetrySample {
+        TelemetrySample {
+            timestamp,
+            profile: "Cool profile".to_owned(),
+            battery_percent: 42.0,
+            power_w: 8.5,
+            cpu_mhz: 2800,
+            gpu_mhz: 1600,
+            fast_ppt: 15,
+            slow_ppt: 0,
+            cpu_temp_c: 65.0,
+            gpu_temp_c: f32::NAN,
+            battery_temp_c: 30.1,
+        }
+    }
+
+    #[test]
+    fn ring_wraparound_test() {
+        let path = std::env::temp_dir().join(format!("powertools_ring_{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut ring = RingFile::open_or_create(&path, 3).expect("create ring");
+        for t in 0..5 {
+            ring.push(&sample(t)).expect("push sample");
+        }
+        let reopened = RingFile::open_or_create(&path, 3).expect("reopen ring");
+        let timestamps: Vec<u64> = reopened
+            .read_all()
+            .expect("read ring")
+            .iter()
+            .map(|s| s.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![2, 3, 4]);
+        // opening to read doesn't start over with another capacity
+        assert_eq!(RingFile::open(&path).expect("open ring").capacity, 3);
+        let mut ring = RingFile::open_or_create(&path, 0).expect("reopen ring");
+        assert_eq!(ring.capacity, 1);
+        ring.push(&sample(5)).expect("push sample");
+        // a capacity of 0 is 1, so the file isn't started over every time
+        let reopened = RingFile::open_or_create(&path, 0).expect("reopen ring");
+        assert_eq!(reopened.count, 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sample_csv_test() {
+        let read_back = TelemetrySample::from_bytes(&sample(1).to_bytes());
+        let mut csv = String::new();
+        read_back.write_csv(&mut csv);
+        assert_eq!(
+            csv,
+            "1,Cool profile,42.00,8.50,2800,1600,15,,65.00,,30.10\n"
+        );
+    }
+}
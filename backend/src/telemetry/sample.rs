@@ -0,0 +1,120 @@
+use std::fmt::Write;
+
+const PROFILE_NAME_LEN: usize = 32;
+
+/// Size of a single sample when stored in the ring file, in bytes
+pub const SAMPLE_SIZE: usize = 8 + (4 * 9) + PROFILE_NAME_LEN;
+
+pub const CSV_HEADER: &str = "timestamp,profile,battery_percent,power_w,cpu_mhz,gpu_mhz,fast_ppt,slow_ppt,cpu_temp_c,gpu_temp_c,battery_temp_c";
+
+/// Telemetry reading at a single point in time.
+/// Unavailable readings are stored as NaN (floats) or 0 (integers).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetrySample {
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+    pub profile: String,
+    pub battery_percent: f32,
+    pub power_w: f32,
+    pub cpu_mhz: u32,
+    pub gpu_mhz: u32,
+    pub fast_ppt: u32,
+    pub slow_ppt: u32,
+    pub cpu_temp_c: f32,
+    pub gpu_temp_c: f32,
+    pub battery_temp_c: f32,
+}
+
+impl TelemetrySample {
+    pub fn to_bytes(&self) -> [u8; SAMPLE_SIZE] {
+        let mut buf = [0u8; SAMPLE_SIZE];
+        buf[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.battery_percent.to_le_bytes());
+        buf[12..16].copy_from_slice(&self.power_w.to_le_bytes());
+        buf[16..20].copy_from_slice(&self.cpu_mhz.to_le_bytes());
+        buf[20..24].copy_from_slice(&self.gpu_mhz.to_le_bytes());
+        buf[24..28].copy_from_slice(&self.fast_ppt.to_le_bytes());
+        buf[28..32].copy_from_slice(&self.slow_ppt.to_le_bytes());
+        buf[32..36].copy_from_slice(&self.cpu_temp_c.to_le_bytes());
+        buf[36..40].copy_from_slice(&self.gpu_temp_c.to_le_bytes());
+        buf[40..44].copy_from_slice(&self.battery_temp_c.to_le_bytes());
+        // truncate profile name to fit, without splitting a multi-byte character
+        let mut name_len = self.profile.len().min(PROFILE_NAME_LEN);
+        while !self.profile.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+        buf[44..44 + name_len].copy_from_slice(&self.profile.as_bytes()[..name_len]);
+        buf
+    }
+
+    pub fn from_bytes(buf: &[u8; SAMPLE_SIZE]) -> Self {
+        let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        let f32_at = |i: usize| f32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&buf[0..8]);
+        let name_bytes = &buf[44..];
+        let name_len = name_bytes
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(PROFILE_NAME_LEN);
+        Self {
+            timestamp: u64::from_le_bytes(timestamp),
+            profile: String::from_utf8_lossy(&name_bytes[..name_len]).into_owned(),
+            battery_percent: f32_at(8),
+            power_w: f32_at(12),
+            cpu_mhz: u32_at(16),
+            gpu_mhz: u32_at(20),
+            fast_ppt: u32_at(24),
+            slow_ppt: u32_at(28),
+            cpu_temp_c: f32_at(32),
+            gpu_temp_c: f32_at(36),
+            battery_temp_c: f32_at(40),
+        }
+    }
+
+    /// Append this sample as a CSV row (including trailing newline)
+    pub fn write_csv(&self, out: &mut String) {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.timestamp,
+            csv_escape(&self.profile),
+            csv_f32(self.battery_percent),
+            csv_f32(self.power_w),
+            csv_u32(self.cpu_mhz),
+            csv_u32(self.gpu_mhz),
+            csv_u32(self.fast_ppt),
+            csv_u32(self.slow_ppt),
+            csv_f32(self.cpu_temp_c),
+            csv_f32(self.gpu_temp_c),
+            csv_f32(self.battery_temp_c),
+        )
+        .unwrap_or(());
+    }
+}
+
+#[inline]
+fn csv_f32(val: f32) -> String {
+    if val.is_nan() {
+        String::new()
+    } else {
+        format!("{:.2}", val)
+    }
+}
+
+#[inline]
+fn csv_u32(val: u32) -> String {
+    if val == 0 {
+        String::new()
+    } else {
+        val.to_string()
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
@@ -0,0 +1,82 @@
+//! Best-effort sysfs readings which are not exposed by the settings drivers.
+//! Every function returns NaN (or 0) when the relevant sensor cannot be found.
+
+use std::path::Path;
+
+use crate::persist::ThermalSensorJson;
+use crate::settings::thermal::{find_battery, find_hwmon, read_temperature, GPU_HWMON_NAMES};
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+fn read_number<P: AsRef<Path>>(path: P) -> Option<f64> {
+    usdpl_back::api::files::read_single::<_, f64, _>(path).ok()
+}
+
+/// CPU package temperature, in degrees Celsius
+pub fn cpu_temperature() -> f32 {
+    read_temperature(ThermalSensorJson::Cpu)
+        .map(|t| t as f32)
+        .unwrap_or(f32::NAN)
+}
+
+/// GPU edge temperature, in degrees Celsius
+pub fn gpu_temperature() -> f32 {
+    read_temperature(ThermalSensorJson::Gpu)
+        .map(|t| t as f32)
+        .unwrap_or(f32::NAN)
+}
+
+/// Current GPU shader clock, in MHz
+pub fn gpu_clock() -> u32 {
+    find_hwmon(GPU_HWMON_NAMES)
+        .and_then(|hwmon| read_number(hwmon.join("freq1_input")))
+        .map(|hz| (hz / 1_000_000.0) as u32)
+        .unwrap_or(0)
+}
+
+/// Average current clock of all online CPUs, in MHz
+pub fn cpu_clock() -> u32 {
+    let mut total = 0.0;
+    let mut count = 0;
+    if let Ok(dir) = std::fs::read_dir(CPU_DIR) {
+        for entry in dir.filter_map(|entry| entry.ok()) {
+            if let Some(khz) = read_number(entry.path().join("cpufreq/scaling_cur_freq")) {
+                total += khz;
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        0
+    } else {
+        (total / count as f64 / 1000.0) as u32
+    }
+}
+
+/// Battery temperature, in degrees Celsius
+pub fn battery_temperature() -> f32 {
+    read_temperature(ThermalSensorJson::Battery)
+        .map(|t| t as f32)
+        .unwrap_or(f32::NAN)
+}
+
+/// Power flowing out of (or into, when charging) the battery, in Watts
+pub fn battery_power() -> f32 {
+    let bat = if let Some(bat) = find_battery() {
+        bat
+    } else {
+        return f32::NAN;
+    };
+    if let Some(micro_watts) = read_number(bat.join("power_now")) {
+        return (micro_watts / 1_000_000.0) as f32;
+    }
+    match (
+        read_number(bat.join("current_now")),
+        read_number(bat.join("voltage_now")),
+    ) {
+        (Some(micro_amps), Some(micro_volts)) => {
+            (micro_amps * micro_volts / 1_000_000_000_000.0) as f32
+        }
+        _ => f32::NAN,
+    }
+}
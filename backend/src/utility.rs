@@ -1,18 +1,10 @@
-use std::fmt::Display;
 //use std::sync::{LockResult, MutexGuard};
 //use std::fs::{Permissions, metadata};
+use std::ffi::CString;
 use std::io::{Read, Write};
-use std::os::unix::fs::PermissionsExt;
-
-pub fn unwrap_maybe_fatal<T: Sized, E: Display>(result: Result<T, E>, message: &str) -> T {
-    match result {
-        Ok(x) => x,
-        Err(e) => {
-            log::error!("{}: {}", message, e);
-            panic!("{}: {}", message, e);
-        }
-    }
-}
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::sync::OnceLock;
 
 /*pub fn unwrap_lock<'a, T: Sized>(
     result: LockResult<MutexGuard<'a, T>>,
@@ -28,45 +20,171 @@ pub fn unwrap_maybe_fatal<T: Sized, E: Display>(result: Result<T, E>, message: &
 }*/
 
 pub fn settings_dir() -> std::path::PathBuf {
+    if let Some(dir) = &crate::daemon::config().settings_dir {
+        return dir.to_owned();
+    }
     usdpl_back::api::dirs::home()
         .unwrap_or_else(|| "/tmp/".into())
         .join(".config/powertools/")
 }
 
-pub fn chown_settings_dir() -> std::io::Result<()> {
-    let dir = settings_dir();
-    #[cfg(feature = "decky")]
-    let deck_user = usdpl_back::api::decky::user().map_err(|_| {
+/// User and group which settings files belong to, or none when that's who PowerTools runs as
+fn settings_owner() -> Option<(u32, u32)> {
+    static OWNER: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+    *OWNER.get_or_init(|| {
+        let daemon = crate::daemon::config();
+        let owner = if let Some(user) = &daemon.user {
+            lookup_user(user)
+        } else if daemon.headless {
+            // nobody else needs to edit the settings, so leave them owned by root
+            return None;
+        } else {
+            default_owner()
+        };
+        match owner {
+            // SAFETY: geteuid has no preconditions and can't fail
+            Ok((uid, gid)) if uid != unsafe { libc::geteuid() } => {
+                log::info!("Settings files belong to uid {} gid {}", uid, gid);
+                Some((uid, gid))
+            }
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Settings files will belong to PowerTools' user: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// Uid and gid (of the primary group) of the user named `name`, from the passwd database
+fn lookup_user(name: &str) -> std::io::Result<(u32, u32)> {
+    let c_name =
+        CString::new(name).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    // SAFETY: passwd is plain data, which getpwnam_r fills in
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    loop {
+        // SAFETY: every pointer is valid for the whole call, and buf's length is passed along with it
+        let code = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match code {
+            0 => break,
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            code => return Err(std::io::Error::from_raw_os_error(code)),
+        }
+    }
+    if result.is_null() {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("No user named `{}`", name),
+        ))
+    } else {
+        Ok((passwd.pw_uid, passwd.pw_gid))
+    }
+}
+
+#[cfg(feature = "decky")]
+fn default_owner() -> std::io::Result<(u32, u32)> {
+    let user = usdpl_back::api::decky::user().map_err(|_| {
         std::io::Error::new(
             std::io::ErrorKind::NotFound,
             "Decky missing deck user's username",
         )
     })?;
-    #[cfg(not(feature = "decky"))]
-    let deck_user = "deck".to_owned();
-    // FIXME this shouldn't need to invoke a command
-    let output = std::process::Command::new("id")
-        .args(["-u", &deck_user])
-        .output()?;
-    let uid: u32 = String::from_utf8_lossy(&output.stdout)
-        .parse()
-        .unwrap_or(1000);
-    log::info!(
-        "chmod/chown ~/.config/powertools for user `{}` ({})",
-        deck_user,
-        uid
-    );
-    let permissions = PermissionsExt::from_mode(0o755);
-    std::fs::set_permissions(&dir, permissions)?;
-    // FIXME once merged into stable https://github.com/rust-lang/rust/issues/88989
-    //std::os::unix::fs::chown(&dir, Some(uid), Some(uid))
-    std::process::Command::new("chown")
-        .args([
-            "-R",
-            &format!("{}:{}", deck_user, deck_user),
-            &dir.to_str().unwrap_or("."),
-        ])
-        .output()?;
+    lookup_user(&user)
+}
+
+/// Without Decky, the settings belong to whoever owns the home dir they're in
+#[cfg(not(feature = "decky"))]
+fn default_owner() -> std::io::Result<(u32, u32)> {
+    let home = usdpl_back::api::dirs::home()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No home dir"))?;
+    let metadata = std::fs::metadata(home)?;
+    Ok((metadata.uid(), metadata.gid()))
+}
+
+/// Create `dir` and its missing parents, owned by the settings owner
+pub fn create_settings_dir(dir: &Path) -> std::io::Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        create_settings_dir(parent)?;
+    }
+    match std::fs::create_dir(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    std::fs::set_permissions(dir, PermissionsExt::from_mode(0o755))?;
+    if let Some((uid, gid)) = settings_owner() {
+        if let Err(e) = std::os::unix::fs::chown(dir, Some(uid), Some(gid)) {
+            log::warn!("Failed to change owner of {}: {}", dir.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Create (or truncate) a file for writing, owned by the settings owner when it's new
+pub fn create_settings_file<P: AsRef<Path>>(path: P) -> std::io::Result<std::fs::File> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        create_settings_dir(parent)?;
+    }
+    let is_new = !path.exists();
+    let file = std::fs::File::create(path)?;
+    if let (true, Some((uid, gid))) = (is_new, settings_owner()) {
+        if let Err(e) = std::os::unix::fs::fchown(&file, Some(uid), Some(gid)) {
+            log::warn!("Failed to change owner of {}: {}", path.display(), e);
+        }
+    }
+    Ok(file)
+}
+
+/// Give files in the settings dir which PowerTools created to the settings owner,
+/// for files saved before PowerTools set their owner itself.
+/// Files which belong to someone else are left alone.
+pub fn chown_settings_dir() -> std::io::Result<()> {
+    match settings_owner() {
+        Some((uid, gid)) => chown_own(&settings_dir(), uid, gid),
+        None => Ok(()),
+    }
+}
+
+fn chown_own(path: &Path, uid: u32, gid: u32) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    if metadata.is_dir() {
+        // one file which can't be changed shouldn't stop the rest from being changed
+        for entry in std::fs::read_dir(path)? {
+            match entry {
+                Ok(entry) => {
+                    if let Err(e) = chown_own(&entry.path(), uid, gid) {
+                        log::warn!(
+                            "Failed to change owner of {}: {}",
+                            entry.path().display(),
+                            e
+                        );
+                    }
+                }
+                Err(e) => log::warn!("Failed to read entry of {}: {}", path.display(), e),
+            }
+        }
+    }
+    // SAFETY: geteuid has no preconditions and can't fail
+    if metadata.uid() == unsafe { libc::geteuid() } {
+        std::os::unix::fs::chown(path, Some(uid), Some(gid))?;
+    }
     Ok(())
 }
 
@@ -74,9 +192,23 @@ fn version_filepath() -> std::path::PathBuf {
     settings_dir().join(".version")
 }
 
+/// The current time in the local time zone
+pub fn local_time() -> Option<libc::tm> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs() as libc::time_t;
+    // SAFETY: tm is plain data, and localtime_r (unlike localtime) only writes to the given struct
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return None;
+    }
+    Some(tm)
+}
+
 pub fn save_version_file() -> std::io::Result<usize> {
     let path = version_filepath();
-    std::fs::File::create(path)?.write(crate::consts::PACKAGE_VERSION.as_bytes())
+    create_settings_file(path)?.write(crate::consts::PACKAGE_VERSION.as_bytes())
 }
 
 pub fn read_version_file() -> String {
@@ -98,3 +230,18 @@ pub fn read_version_file() -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup_user_test() {
+        assert_eq!(lookup_user("root").unwrap(), (0, 0));
+        assert_eq!(
+            lookup_user("no-such-user-here").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert!(lookup_user("bad\0name").is_err());
+    }
+}
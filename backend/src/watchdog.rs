@@ -0,0 +1,160 @@
+//! Drift watchdog, which notices when another process (e.g. Steam) changes applied values.
+//! Most sysfs files don't support inotify, so values are polled (see watchdog_worker).
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::api::{AppliedValue, ApplyReport};
+use crate::persist::WatchdogJson;
+use crate::settings::{OnVerify, Settings};
+
+/// Longest wait between checks while another process keeps winning
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+pub struct Watchdog {
+    config: WatchdogJson,
+    /// Consecutive checks which found drift
+    attempts: u32,
+    next_check: Instant,
+}
+
+impl Watchdog {
+    /// Load watchdog config from the settings dir, falling back to defaults (disabled)
+    pub fn load() -> Self {
+        Self {
+            config: crate::persist::open_json(Self::config_path()).unwrap_or_default(),
+            attempts: 0,
+            next_check: Instant::now(),
+        }
+    }
+
+    fn config_path() -> PathBuf {
+        crate::utility::settings_dir().join(crate::consts::WATCHDOG_CONFIG_FILE)
+    }
+
+    fn save(&self) {
+        if let Err(e) = crate::persist::save_json(&self.config, Self::config_path()) {
+            log::error!("Failed to save watchdog config: {}", e);
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.config.enabled != enabled {
+            self.config.enabled = enabled;
+            self.reset();
+            self.save();
+        }
+    }
+
+    pub fn yield_to_system(&self) -> Vec<String> {
+        self.config.yield_to_system.clone()
+    }
+
+    /// Leave `setting` (battery, cpu, gpu or fan) alone when another process changes it
+    pub fn set_yield(&mut self, setting: String, yield_to_system: bool) {
+        let setting = setting.to_lowercase();
+        let is_yielding = self.config.yield_to_system.contains(&setting);
+        if yield_to_system && !is_yielding {
+            self.config.yield_to_system.push(setting);
+        } else if !yield_to_system && is_yielding {
+            self.config.yield_to_system.retain(|s| s != &setting);
+        } else {
+            return;
+        }
+        self.save();
+    }
+
+    /// Start over with the shortest backoff, e.g. after settings were changed on purpose
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.next_check = Instant::now();
+    }
+
+    /// Drifted values which should be re-applied; empty when there's nothing to do (yet)
+    pub fn check(&mut self, settings: &Settings, period: Duration) -> Vec<AppliedValue> {
+        let now = Instant::now();
+        if !self.config.enabled || now < self.next_check {
+            return Vec::new();
+        }
+        let drifted: Vec<AppliedValue> = settings
+            .verify()
+            .into_iter()
+            .filter(|v| v.is_drifted() && !self.is_yielding(v))
+            .collect();
+        if drifted.is_empty() {
+            if self.attempts != 0 {
+                log::info!("Settings held after {} re-apply attempt(s)", self.attempts);
+                self.attempts = 0;
+            }
+            return drifted;
+        }
+        for value in &drifted {
+            log::warn!(
+                "{} value `{}` was changed to `{}` by another process (expected `{}`)",
+                value.setting,
+                value.name,
+                value.actual.as_deref().unwrap_or_default(),
+                value.requested
+            );
+        }
+        self.attempts += 1;
+        let backoff = backoff(period, self.attempts);
+        log::debug!(
+            "Re-applying settings, next drift check in {}s",
+            backoff.as_secs()
+        );
+        self.next_check = now + backoff;
+        drifted
+    }
+
+    /// Log whether PowerTools or the other process ended up with each drifted value
+    pub fn log_winners(&self, drifted: &[AppliedValue], report: &ApplyReport) {
+        for value in drifted {
+            let lost = report
+                .values
+                .iter()
+                .any(|v| v.name == value.name && v.is_drifted());
+            if lost {
+                log::warn!(
+                    "Another process won `{}` (attempt {}), backing off",
+                    value.name,
+                    self.attempts
+                );
+            } else {
+                log::info!("PowerTools won `{}` back", value.name);
+            }
+        }
+    }
+
+    fn is_yielding(&self, value: &AppliedValue) -> bool {
+        self.config
+            .yield_to_system
+            .contains(&value.setting.to_lowercase())
+    }
+}
+
+/// Wait `period`, doubled for every consecutive attempt, up to MAX_BACKOFF
+fn backoff(period: Duration, attempts: u32) -> Duration {
+    period
+        .checked_mul(1 << attempts.min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_test() {
+        let period = Duration::from_secs(5);
+        assert_eq!(backoff(period, 1), Duration::from_secs(10));
+        assert_eq!(backoff(period, 3), Duration::from_secs(40));
+        assert_eq!(backoff(period, 10), MAX_BACKOFF);
+        assert_eq!(backoff(period, u32::MAX), MAX_BACKOFF);
+    }
+}
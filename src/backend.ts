@@ -56,6 +56,7 @@ export type SettingsLimits = {
     battery: BatteryLimits;
     cpu: CpusLimits;
     gpu: GpuLimits;
+    fan: FanLimits;
     general: GeneralLimits;
 };
 
@@ -93,6 +94,214 @@ export type GpuLimits = {
     memory_control_capable: boolean;
 };
 
+export type FanLimits = {
+    speed_limits: RangeLimit | null;
+    speed_step: number;
+    speed_unit: string; // "rpm" or "pwm"
+    sensors: string[];
+};
+
+// Thermal types
+
+export type ThermalAction = {
+    fast_ppt: number | null;
+    slow_ppt: number | null;
+    cpu_clock_max: number | null;
+};
+
+export type ThermalRule = {
+    sensor: "cpu" | "gpu" | "battery";
+    threshold: number; // degrees Celsius
+    duration: number; // seconds
+    hysteresis: number; // degrees Celsius
+    action: ThermalAction;
+};
+
+// Profile switching rule types (same as rules.json)
+
+export type RuleConditions = {
+    power?: "ac" | "battery" | null;
+    external_display?: boolean | null;
+    battery_below?: number | null; // percent
+    time?: { from: string; to: string } | null; // "HH:MM" local time; to may be before from
+};
+
+export type RuleTarget =
+    | { profile: { id: number | null; name: string } } // main profile when id is null
+    | { preset: string };
+
+export type Rule = {
+    name: string;
+    when: RuleConditions;
+    target: RuleTarget;
+};
+
+export type Rules = {
+    enabled: boolean;
+    rules: Rule[]; // first matching rule wins
+};
+
+// Settings document types (same as the settings files)
+
+export type MinMaxJson = {
+    min: number;
+    max: number;
+};
+
+export type CpuJson = {
+    online: boolean;
+    clock_limits: MinMaxJson | null;
+    governor: string;
+};
+
+export type GpuJson = {
+    fast_ppt: number | null;
+    slow_ppt: number | null;
+    clock_limits: MinMaxJson | null;
+    slow_memory: boolean;
+};
+
+export type BatteryEventJson = {
+    trigger: string;
+    charge_rate: number | null;
+    charge_mode: string | null;
+};
+
+export type BatteryJson = {
+    charge_rate: number | null;
+    charge_mode: string | null;
+    events: BatteryEventJson[];
+};
+
+export type FanJson = {
+    curve: FanPoint[] | null;
+    sensor: "cpu" | "gpu" | "battery";
+};
+
+export type TriggerAction = {
+    fast_ppt?: number | null;
+    slow_ppt?: number | null;
+    gpu_clock_limits?: MinMaxJson | null;
+    slow_memory?: boolean | null;
+    smt?: boolean | null;
+    cpu_governor?: string | null; // for all online CPUs
+    charge_rate?: number | null;
+    charge_mode?: string | null;
+};
+
+export type Trigger = {
+    trigger: string; // "plug-in", "plug-out", "resume", ">N" or "<N" (battery percent)
+    action: TriggerAction;
+};
+
+export type PowerSourceValues = {
+    cpus: CpuJson[];
+    gpu: GpuJson;
+};
+
+export type SettingsJson = {
+    version: number;
+    name: string;
+    persistent: boolean;
+    cpus: CpuJson[];
+    gpu: GpuJson;
+    battery: BatteryJson;
+    fan: FanJson;
+    provider: string | null;
+    thermal: ThermalRule[];
+    preset: string | null; // preset whose values are used instead of the ones in this document
+    triggers: Trigger[];
+    power_sources: { ac: PowerSourceValues | null; battery: PowerSourceValues | null }; // used instead of cpus and gpu
+};
+
+// Profile sharing types
+
+export type ProfileExport = {
+    format: number;
+    powertools_version: string;
+    device: string | null; // limits config which matched the device it was made on
+    providers: { general: string; cpus: string; gpu: string; battery: string; fan: string };
+    app_id: number | null; // main profile when null
+    settings: SettingsJson;
+};
+
+export type ImportReport = {
+    same_device: boolean;
+    device: string | null;
+    adjusted: string[]; // values changed to fit this device
+    path: string; // settings file the profile was saved to
+};
+
+// Profile history types
+
+export type Change = {
+    field: string; // JSON pointer into the settings document, like "/gpu/fast_ppt"
+    old: any;
+    new: any;
+};
+
+export type HistoryEntry = {
+    time: number; // seconds since UNIX epoch
+    source: "api" | "event" | "rule";
+    changes: Change[];
+};
+
+export type History = {
+    entries: HistoryEntry[]; // oldest first
+    position: number; // entries in effect; later ones can be redone
+};
+
+// Apply report types
+
+export type AppliedValue = {
+    setting: string;
+    name: string;
+    requested: string;
+    actual: string | null;
+};
+
+export type ApplyReport = {
+    time: number; // seconds since UNIX epoch
+    values: AppliedValue[];
+    drifted: number;
+    reapplied: boolean;
+    outcome: "committed" | "rolled-back" | "rollback-failed";
+};
+
+// Error types
+
+export type SettingErrorInfo = {
+    setting: string;
+    kind: "permission" | "unsupported" | "io" | "out-of-range" | "off-step" | "min-over-max" | "unknown-option" | "bad-index";
+    msg: string;
+    source: string;
+    time: number; // seconds since UNIX epoch
+};
+
+// Event types
+
+export type PowerToolsEvent =
+    | { type: "plugged-in" }
+    | { type: "plugged-out" }
+    | { type: "battery-charge"; charge: number }
+    | { type: "battery"; charge_now: number | null; charge_full: number | null; current_now: number | null }
+    | { type: "resume" }
+    | { type: "profile-loaded"; name: string; path: string }
+    | { type: "applied"; outcome: string; drifted: number }
+    | ({ type: "error" } & SettingErrorInfo)
+    | { type: "rule-matched"; name: string };
+
+export type EventRecord = PowerToolsEvent & {
+    seq: number;
+    time: number; // seconds since UNIX epoch
+};
+
+export type EventBatch = {
+    seq: number; // pass to the next waitEvent() to resume from here
+    missed: boolean;
+    events: EventRecord[];
+};
+
 // API
 
 export async function getInfo(): Promise<string> {
@@ -233,6 +442,56 @@ export async function getGpuSlowMemory(): Promise<boolean> {
     return (await call_backend("GPU_get_slow_memory", []))[0];
 }
 
+// Fan
+
+export type FanPoint = {
+    temperature: number; // degrees Celsius
+    speed: number; // in FanLimits.speed_unit
+};
+
+export type FanCurve = {
+    curve: FanPoint[] | null; // null -> firmware fan control
+    sensor: "cpu" | "gpu" | "battery";
+};
+
+export async function setFanCurve(curve: FanCurve): Promise<boolean> {
+    return (await call_backend("FAN_set_curve", [JSON.stringify(curve)]))[0];
+}
+
+export async function getFanCurve(): Promise<FanCurve> {
+    return (await call_backend("FAN_get_curve", []))[0];
+}
+
+export async function unsetFanCurve(): Promise<any[]> {
+    return (await call_backend("FAN_unset_curve", []));
+}
+
+// Presets
+
+export async function createPreset(name: string): Promise<boolean | string> {
+    return (await call_backend("PRESET_create", [name]))[0]; // -> true or error message
+}
+
+export async function updatePreset(name: string): Promise<boolean | string> {
+    return (await call_backend("PRESET_update", [name]))[0]; // -> true or error message
+}
+
+export async function applyPreset(name: string): Promise<boolean | string> {
+    return (await call_backend("PRESET_apply", [name]))[0]; // -> true or error message
+}
+
+export async function deletePreset(name: string): Promise<boolean | string> {
+    return (await call_backend("PRESET_delete", [name]))[0]; // -> true or error message
+}
+
+export async function listPresets(): Promise<string[]> {
+    return (await call_backend("PRESET_list", []))[0];
+}
+
+export async function getCurrentPreset(): Promise<string | null> {
+    return (await call_backend("PRESET_get_current", []))[0];
+}
+
 // general
 
 export async function setGeneralPersistent(val: boolean): Promise<boolean> {
@@ -295,6 +554,54 @@ export async function forceApplySettings(): Promise<boolean> {
     return (await call_backend("GENERAL_apply_now", []))[0];
 }
 
+export async function setSettingsJson(settings: SettingsJson): Promise<(boolean | string)[]> {
+    return (await call_backend("GENERAL_set_settings_json", [JSON.stringify(settings)])); // -> [true] or error messages
+}
+
+export async function getSettingsJson(): Promise<SettingsJson> {
+    return (await call_backend("GENERAL_get_settings_json", []))[0];
+}
+
+export async function getApplyReport(): Promise<ApplyReport> {
+    return (await call_backend("GENERAL_get_apply_report", []))[0];
+}
+
+export async function getErrors(): Promise<SettingErrorInfo[]> {
+    return (await call_backend("GENERAL_get_errors", []))[0];
+}
+
+export async function setWatchdog(enabled: boolean): Promise<boolean> {
+    return (await call_backend("GENERAL_set_watchdog", [enabled]))[0];
+}
+
+export async function getWatchdog(): Promise<boolean> {
+    return (await call_backend("GENERAL_get_watchdog", []))[0];
+}
+
+export async function setYieldToSystem(setting: "battery" | "cpu" | "gpu" | "fan", yieldToSystem: boolean): Promise<boolean> {
+    return (await call_backend("GENERAL_set_yield_to_system", [setting, yieldToSystem]))[0];
+}
+
+export async function getYieldToSystem(): Promise<string[]> {
+    return (await call_backend("GENERAL_get_yield_to_system", []))[0];
+}
+
+export async function restoreStock(): Promise<boolean> {
+    return (await call_backend("GENERAL_restore_stock", []))[0];
+}
+
+export async function setValidationMode(mode: "reject" | "clamp"): Promise<string> {
+    return (await call_backend("GENERAL_set_validation_mode", [mode]))[0];
+}
+
+export async function getValidationMode(): Promise<"reject" | "clamp"> {
+    return (await call_backend("GENERAL_get_validation_mode", []))[0];
+}
+
+export async function waitEvent(since: number, timeoutSecs?: number): Promise<EventBatch> {
+    return (await call_backend("GENERAL_wait_event", timeoutSecs == null ? [since] : [since, timeoutSecs]))[0];
+}
+
 export async function onPluggedIn(): Promise<boolean> {
     return (await call_backend("GENERAL_on_pluggedin", []))[0];
 }
@@ -302,3 +609,71 @@ export async function onPluggedIn(): Promise<boolean> {
 export async function onUnplugged(): Promise<boolean> {
     return (await call_backend("GENERAL_on_unplugged", []))[0];
 }
+
+export async function setTelemetry(val: boolean): Promise<boolean> {
+    return (await call_backend("GENERAL_set_telemetry", [val]))[0];
+}
+
+export async function getTelemetry(): Promise<boolean> {
+    return (await call_backend("GENERAL_get_telemetry", []))[0];
+}
+
+export async function exportTelemetry(start: number | null, end: number | null): Promise<string | null> {
+    return (await call_backend("GENERAL_export_telemetry", [start, end]))[0]; // -> CSV
+}
+
+export async function setThermalRules(rules: ThermalRule[]): Promise<boolean> {
+    return (await call_backend("GENERAL_set_thermal_rules", [JSON.stringify(rules)]))[0];
+}
+
+export async function getThermalRules(): Promise<ThermalRule[]> {
+    return (await call_backend("GENERAL_get_thermal_rules", []))[0];
+}
+
+export async function setRules(rules: Rules): Promise<(boolean | string)[]> {
+    return (await call_backend("GENERAL_set_rules", [JSON.stringify(rules)])); // -> [true] or error messages
+}
+
+export async function getRules(): Promise<Rules> {
+    return (await call_backend("GENERAL_get_rules", []))[0];
+}
+
+export async function exportProfile(): Promise<ProfileExport> {
+    return (await call_backend("GENERAL_export_profile", []))[0];
+}
+
+export async function importProfile(profile: ProfileExport, overwrite: boolean = false): Promise<ImportReport | string[]> {
+    const result = await call_backend("GENERAL_import_profile", [JSON.stringify(profile), overwrite]);
+    return typeof result[0] === "string" ? result : result[0]; // -> report, or error messages
+}
+
+export async function undo(): Promise<boolean | string> {
+    return (await call_backend("GENERAL_undo", []))[0]; // -> true or error message
+}
+
+export async function redo(): Promise<boolean | string> {
+    return (await call_backend("GENERAL_redo", []))[0]; // -> true or error message
+}
+
+export async function getHistory(): Promise<History> {
+    return (await call_backend("GENERAL_get_history", []))[0];
+}
+
+// app ids, or null for the main profile
+export async function diffProfiles(old_id: number | null, new_id: number | null): Promise<Change[] | string> {
+    return (await call_backend("GENERAL_diff_profiles", [old_id, new_id]))[0]; // -> changes or error message
+}
+
+export type LogLevel = "off" | "error" | "warn" | "info" | "debug" | "trace";
+
+export async function setLogLevel(level: LogLevel): Promise<boolean | string> {
+    return (await call_backend("GENERAL_set_log_level", [level]))[0]; // -> true or error message
+}
+
+export async function getLogLevel(): Promise<LogLevel> {
+    return (await call_backend("GENERAL_get_log_level", []))[0];
+}
+
+export async function getPowerSource(): Promise<"ac" | "battery" | null> {
+    return (await call_backend("GENERAL_get_power_source", []))[0]; // -> values in use, or null for the profile's own
+}